@@ -1,12 +1,24 @@
 #version 330 core
 
 uniform sampler2D atlas;
+uniform float alpha_cutoff; // fragments less opaque than this are discarded
 
 in vec3 position;
 in vec2 texcoord;
+flat in vec4 tile;
+in vec2 shade; // ambient occlusion, light
 
 out vec4 Color;
 
 void main() {
-    Color = texture2D(atlas, texcoord);
+    // Merged faces span several blocks; repeat the texture within its tile.
+    Color = texture2D(atlas, tile.xy + fract(texcoord) * tile.zw);
+    
+    if (Color.a < alpha_cutoff) {
+        discard;
+    }
+    
+    // Occluded corners never go fully black, and neither do unlit blocks.
+    float brightness = mix(0.4, 1.0, shade.x) * mix(0.05, 1.0, shade.y);
+    Color.rgb *= brightness;
 }
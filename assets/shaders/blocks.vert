@@ -4,12 +4,18 @@ uniform mat4 transform;
 
 layout (location = 0) in vec3 Position;
 layout (location = 1) in vec2 TexCoord;
+layout (location = 2) in vec4 Tile;
+layout (location = 3) in vec2 Shade;
 
 out vec3 position;
 out vec2 texcoord;
+flat out vec4 tile;
+out vec2 shade;
 
 void main() {
     gl_Position = transform * vec4(Position, 1.0);
     position = Position;
     texcoord = TexCoord;
+    tile = Tile;
+    shade = Shade;
 }
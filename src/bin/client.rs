@@ -105,25 +105,28 @@ impl router::lens::Handler for ClientLens {
 			}
 		});
 		
-		event.downcast::<client::TickEvent>().map(|_tick| {
-			let scene = context.get_mut_component_downcast::<scene::Scene>();
-			let gfx = context.get_mut_component_downcast::<context::GlfwContextComponent>();
+		event.downcast::<client::TickEvent>().map(|tick| {
+			let scene = context.get_component_downcast::<scene::Scene>();
+			let gfx = context.get_component_downcast::<context::GlfwContextComponent>();
 			
 			if let Ok(scene) = scene {
+				let mut scene = scene.borrow_mut();
+				scene.update_chunks();
+				
 				if let Ok(gfx) = gfx {
-					scene.camera.update_movement(gfx.window.borrow());
+					scene.update_player(gfx.borrow().window.borrow(), tick.delta as f32);
 					scene.update_targeted_block();
 				}
 			}
 			
-			if let Ok(scene_renderer) = context.get_mut_component_downcast::<scene::SceneRenderer>() {
-				scene_renderer.reset();
+			if let Ok(scene_renderer) = context.get_component_downcast::<scene::SceneRenderer>() {
+				scene_renderer.borrow_mut().reset();
 			}
 		});
 		
 		event.downcast::<client::DrawEvent>().map(|draw| {
-			let scene = context.get_mut_component_downcast::<scene::Scene>();
-			let scene_renderer = context.get_mut_component_downcast::<scene::SceneRenderer>();
+			let scene = context.get_component_downcast::<scene::Scene>();
+			let scene_renderer = context.get_component_downcast::<scene::SceneRenderer>();
 			
 			if scene.is_err() {
 				panic!("This ain't supposed to happen!");
@@ -131,10 +134,11 @@ impl router::lens::Handler for ClientLens {
 			
 			if let Ok(scene) = scene {
 				if let Ok(scene_renderer) = scene_renderer {
+					let mut scene_renderer = scene_renderer.borrow_mut();
 					scene_renderer.begin();
 					scene::render(
-						scene_renderer,
-						scene,
+						&mut scene_renderer,
+						&scene.borrow(),
 						draw.window_size,
 						draw.now,
 						draw.interpolation
@@ -157,15 +161,15 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 	// ------------------------------------------
 	let gfx = context::GlfwContextComponent::new(&opts)?;
 	
-	// Give the router ownership of the Graphics-Context... then sneakily grab it back!
-	// This is the **only** place in the code where it's okay to do this.
+	// Give the router ownership of the Graphics-Context... then grab a handle to it.
+	// The handles are only ever borrowed briefly, so the router can reach the components too.
 	router.nodes.set_node_component(0, Box::new(gfx))?;
 	let gfx = router.nodes
-		.get_mut_node_component_downcast::<context::GlfwContextComponent>(0)?;
+		.get_node_component_downcast::<context::GlfwContextComponent>(0)?;
 	
 	router.nodes.set_node_component(0, Box::new(settings))?;
 	let settings = router.nodes
-		.get_mut_node_component_downcast::<settings::Settings>(0)?;
+		.get_node_component_downcast::<settings::Settings>(0)?;
 	
 	// ------------------------------------------
 	
@@ -182,15 +186,14 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 	info!("Initializing scene...");
 	
 	let mut scene = scene::Scene::new();
-	scene.camera.active = gfx.window.get_cursor_mode() == glfw::CursorMode::Disabled;
-	scene.camera.apply_settings(settings);
-	router.nodes.set_node_component(0, Box::new(scene))?;
+	scene.camera.active = gfx.borrow().window.get_cursor_mode() == glfw::CursorMode::Disabled;
+	scene.camera.apply_settings(&settings.borrow());
+	scene.player.apply_settings(&settings.borrow());
 	
 	// Create the renderer for the scene, which requires the scene for initialization, so...
-	if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
-		let scene_renderer = scene::SceneRenderer::new(&res, &scene)?;
-		router.nodes.set_node_component(0, Box::new(scene_renderer))?;
-	}
+	let scene_renderer = scene::SceneRenderer::new(&res, &scene, &settings.borrow(), &opts)?;
+	router.nodes.set_node_component(0, Box::new(scene))?;
+	router.nodes.set_node_component(0, Box::new(scene_renderer))?;
 	
 	// ------------------------------------------
 	
@@ -214,17 +217,18 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 	info!("Initializing and starting gameloop...");
 	let mut gameloop = gameloop::GameloopState::new(30, true);
 	
-	while !router.borrow_mut().update() && !gfx.window.should_close() {
-		gfx.process_events(&mut router.borrow_mut());
+	while !router.borrow_mut().update() && !gfx.borrow().window.should_close() {
+		context::GlfwContextComponent::process_events(&gfx, &mut router.borrow_mut());
 		
-		let window_size = gfx.window.get_framebuffer_size();
+		let window_size = gfx.borrow().window.get_framebuffer_size();
 		let frame_time  = gameloop.get_frame_time();
 		let last_fps = gameloop.get_frames_per_second();
 		let last_tps = gameloop.get_ticks_per_second();
+		let tick_length = gameloop.get_tick_length();
 		
-		gameloop.next(|| {gfx.glfw.get_time()},
+		gameloop.next(|| {gfx.borrow().glfw.get_time()},
 			|_now:f64| {
-				router.borrow_mut().fire_event_at_lens("client", &mut client::TickEvent {});
+				router.borrow_mut().fire_event_at_lens("client", &mut client::TickEvent {delta: tick_length});
 			},
 			
 			|now: f64, interpolation: f32| {
@@ -240,7 +244,7 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 				};
 				router.borrow_mut().fire_event_at_lens("client", &mut draw_event);
 				
-				let (w, h) = gfx.window.get_framebuffer_size();
+				let (w, h) = gfx.borrow().window.get_framebuffer_size();
 				render_state_gui.width = w as f32;
 				render_state_gui.height = h as f32;
 				
@@ -257,7 +261,8 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 				));
 				
 				if let Ok(scene) = router.borrow_mut().nodes.get_node_component_downcast::<scene::Scene>(0) {
-					let camera = scene.camera.borrow();
+					let scene = scene.borrow();
+					let camera = &scene.camera;
 					let position = camera.get_position(interpolation);
 					let rotation = camera.get_rotation(interpolation);
 					
@@ -266,13 +271,14 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 					
 					render_state_gui.debug_text.push((
 						0.0, (h as f32) - 16.0 -  2.0,
-						format!("Camera: {crane}-mode {x:.1}, {y:.1}, {z:.1} / {pitch:.0} {yaw:.0}, {block}",
+						format!("Camera: {crane}-mode {x:.1}, {y:.1}, {z:.1} / {pitch:.0} {yaw:.0}, {mode}, {block}",
 							x = position.x,
 							y = position.y,
 							z = position.z,
 							pitch = rotation.x.round(),
 							yaw   = rotation.y.round(),
 							crane = if camera.crane { "crane" } else { "drone" },
+							mode  = scene.player.mode.get_name(),
 							block = scene.blockdef.get_block_by_id(block.id).get_name()
 						)
 					));
@@ -282,7 +288,12 @@ fn run(opts: cmd_opts::CmdOptions, settings: settings::Settings) -> Result<(), f
 			}
 		);
 		
-		gfx.swap_and_poll();
+		gfx.borrow_mut().swap_and_poll();
+	}
+	
+	info!("Saving scene...");
+	if let Ok(scene) = router.borrow_mut().nodes.get_node_component_downcast::<scene::Scene>(0) {
+		scene.borrow_mut().save();
 	}
 	
 	Ok(())
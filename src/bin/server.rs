@@ -12,6 +12,7 @@ use failure::Fail;
 
 extern crate tcge;
 use tcge::resources;
+use tcge::blocks;
 //use tcge::router;
 //use tcge::util::gameloop;
 //use tcge::server;
@@ -38,5 +39,11 @@ fn main() {
 	let _res = resources::Resources::from_exe_path()
 		.expect("Failed to setup root resource provider for server.");
 	
+	info!("Initializing world...");
+	let config = toml::value::Table::new();
+	let blockdef = blocks::universe::define_universe(&config);
+	let _world = blocks::ChunkStorage::new(blockdef, &config)
+		.expect("Failed to set up world generator.");
+	
 	info!("Server shutdown!");
 }
@@ -0,0 +1,461 @@
+//! Material attributes of blocks: opacity, render layer, solidity, collision, textures and emitted light.
+//!
+//! Attributes are declared alongside the properties of a block in the `[blocks]` config:
+//!
+//! ```toml
+//! [blocks.glass]
+//! layer = "translucent"
+//!
+//! [blocks.leaves]
+//! layer = "cutout"
+//!
+//! [blocks.fence]
+//! opaque = false
+//!
+//! [blocks.stone_slab]
+//! model = "slab"
+//! texture = "stone"
+//!
+//! [blocks.grass]
+//! textures = { top = "grass_top", bottom = "dirt", side = "grass_side" }
+//!
+//! [blocks.slab]
+//! collision = [0.0, 0.0, 0.0, 1.0, 0.5, 1.0]
+//!
+//! [blocks.lamp]
+//! emission = 14
+//! ```
+//!
+//! Every attribute is optional; a block without any is an opaque, solid, full cube
+//! with its own name as the texture of every face.
+//! Blocks in the `cutout` or `translucent` layer, or with a `model`, are not opaque,
+//! unless `opaque` says otherwise. Models are loaded by the client; see `client::blocks::model`.
+//! `texture` assigns one texture to all faces, while the `textures` table accepts
+//! `all`, `side`, `top`, `bottom`, `north`, `south`, `west` and `east`,
+//! the more specific keys overriding the more general ones.
+
+use super::light::MAX_LIGHT;
+
+/// One of the six faces of a block.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BlockFace {
+	/// Facing +Y.
+	Top,
+	/// Facing -Y.
+	Bottom,
+	/// Facing -Z.
+	North,
+	/// Facing +Z.
+	South,
+	/// Facing -X.
+	West,
+	/// Facing +X.
+	East,
+}
+
+impl BlockFace {
+	pub const ALL: [BlockFace; 6] = [
+		BlockFace::Top,
+		BlockFace::Bottom,
+		BlockFace::North,
+		BlockFace::South,
+		BlockFace::West,
+		BlockFace::East,
+	];
+	
+	/// The offset to the neighbouring block this face is facing.
+	pub fn get_offset(&self) -> (isize, isize, isize) {
+		match self {
+			BlockFace::Top    => ( 0, 1, 0),
+			BlockFace::Bottom => ( 0,-1, 0),
+			BlockFace::North  => ( 0, 0,-1),
+			BlockFace::South  => ( 0, 0, 1),
+			BlockFace::West   => (-1, 0, 0),
+			BlockFace::East   => ( 1, 0, 0),
+		}
+	}
+	
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			BlockFace::Top    => "top",
+			BlockFace::Bottom => "bottom",
+			BlockFace::North  => "north",
+			BlockFace::South  => "south",
+			BlockFace::West   => "west",
+			BlockFace::East   => "east",
+		}
+	}
+	
+	/// Returns `true` for the four faces around the vertical axis.
+	pub fn is_side(&self) -> bool {
+		match self {
+			BlockFace::Top | BlockFace::Bottom => false,
+			_ => true
+		}
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The pass a block is rendered in; the layers are drawn in the order they are declared.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum RenderLayer {
+	/// Fully covered textures; drawn front to back without blending.
+	Opaque,
+	
+	/// Textures with fully transparent holes, like leaves; transparent pixels are discarded.
+	Cutout,
+	
+	/// Partially transparent textures, like glass or water; blended, and drawn back to front.
+	Translucent,
+}
+
+impl RenderLayer {
+	pub const ALL: [RenderLayer; 3] = [
+		RenderLayer::Opaque,
+		RenderLayer::Cutout,
+		RenderLayer::Translucent,
+	];
+	
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			RenderLayer::Opaque      => "opaque",
+			RenderLayer::Cutout      => "cutout",
+			RenderLayer::Translucent => "translucent",
+		}
+	}
+	
+	pub fn from_name(name: &str) -> Option<RenderLayer> {
+		RenderLayer::ALL.iter().cloned().find(|layer| layer.get_name() == name)
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// An axis-aligned box in block-local space, where a full block spans `0..1` on every axis.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlockBox {
+	pub min: cgmath::Vector3<f32>,
+	pub max: cgmath::Vector3<f32>,
+}
+
+impl BlockBox {
+	pub fn new(min: cgmath::Vector3<f32>, max: cgmath::Vector3<f32>) -> BlockBox {
+		BlockBox { min, max }
+	}
+	
+	/// The box covering the whole block.
+	pub fn full() -> BlockBox {
+		BlockBox {
+			min: cgmath::Vector3::new(0.0, 0.0, 0.0),
+			max: cgmath::Vector3::new(1.0, 1.0, 1.0),
+		}
+	}
+	
+	/// Parses a box given as `[min_x, min_y, min_z, max_x, max_y, max_z]`.
+	fn from_config(value: &toml::Value) -> Result<BlockBox, String> {
+		let values = value.as_array()
+			.ok_or("'collision' must be an array")?;
+		
+		if values.len() != 6 {
+			return Err("'collision' must have six values".to_string());
+		}
+		
+		let mut bounds = [0f32; 6];
+		for (bound, value) in bounds.iter_mut().zip(values.iter()) {
+			*bound = match value {
+				toml::Value::Float(x) => *x as f32,
+				toml::Value::Integer(x) => *x as f32,
+				_ => return Err("'collision' values must be numbers".to_string())
+			};
+		}
+		
+		let bounds = BlockBox {
+			min: cgmath::Vector3::new(bounds[0], bounds[1], bounds[2]),
+			max: cgmath::Vector3::new(bounds[3], bounds[4], bounds[5]),
+		};
+		
+		if bounds.min.x > bounds.max.x || bounds.min.y > bounds.max.y || bounds.min.z > bounds.max.z {
+			return Err("'collision' minimum must not be greater than its maximum".to_string());
+		}
+		
+		Ok(bounds)
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The material attributes of a block, shared by all of its states.
+#[derive(Clone, Debug)]
+pub struct BlockAttributes {
+	/// Whether the block fully hides the faces of its neighbours.
+	pub opaque: bool,
+	
+	/// The pass the faces of the block are rendered in.
+	pub layer: RenderLayer,
+	
+	/// Whether the block stops movement.
+	pub solid: bool,
+	
+	/// The box other things collide with; `None` for blocks that can be walked through.
+	pub collision: Option<BlockBox>,
+	
+	/// Texture name of every face, indexed like `BlockFace::ALL`;
+	/// `None` for blocks that are not rendered at all.
+	pub textures: Option<[String; 6]>,
+	
+	/// The name of the model the block is rendered with; `None` for a full cube.
+	pub model: Option<String>,
+	
+	/// The level of block light the block gives off, up to `MAX_LIGHT`.
+	pub emission: u8,
+}
+
+impl BlockAttributes {
+	/// The attributes of a block that is neither visible nor tangible, like air.
+	pub fn new_empty() -> BlockAttributes {
+		BlockAttributes {
+			opaque: false,
+			layer: RenderLayer::Opaque,
+			solid: false,
+			collision: None,
+			textures: None,
+			model: None,
+			emission: 0,
+		}
+	}
+	
+	/// The attributes of an opaque, solid, full cube with the same texture on every face.
+	pub fn new_cube(texture: &str) -> BlockAttributes {
+		BlockAttributes {
+			opaque: true,
+			layer: RenderLayer::Opaque,
+			solid: true,
+			collision: Some(BlockBox::full()),
+			textures: Some(Self::same_textures(texture)),
+			model: None,
+			emission: 0,
+		}
+	}
+	
+	/// Parses the attributes of the named block from its config-table.
+	pub fn from_config(name: &str, info: Option<&toml::Value>) -> Result<BlockAttributes, String> {
+		let mut attributes = BlockAttributes::new_cube(name);
+		
+		let info = match info.and_then(|info| info.as_table()) {
+			Some(info) => info,
+			None => return Ok(attributes)
+		};
+		
+		if let Some(layer) = info.get("layer") {
+			let layer = layer.as_str().ok_or("'layer' must be a string")?;
+			attributes.layer = RenderLayer::from_name(layer)
+				.ok_or_else(|| format!("unknown layer '{}', expected opaque, cutout or translucent", layer))?;
+			attributes.opaque = attributes.layer == RenderLayer::Opaque;
+		}
+		
+		if let Some(model) = info.get("model") {
+			attributes.model = Some(model.as_str().ok_or("'model' must be a string")?.to_string());
+			attributes.opaque = false;
+		}
+		
+		if let Some(opaque) = info.get("opaque") {
+			attributes.opaque = opaque.as_bool().ok_or("'opaque' must be a boolean")?;
+		}
+		
+		if let Some(solid) = info.get("solid") {
+			attributes.solid = solid.as_bool().ok_or("'solid' must be a boolean")?;
+			if ! attributes.solid {
+				attributes.collision = None;
+			}
+		}
+		
+		if let Some(collision) = info.get("collision") {
+			attributes.collision = Some(BlockBox::from_config(collision)?);
+		}
+		
+		if let Some(emission) = info.get("emission") {
+			attributes.emission = emission.as_integer()
+				.filter(|e| *e >= 0 && *e <= MAX_LIGHT as i64)
+				.ok_or_else(|| format!("'emission' must be an integer from 0 to {}", MAX_LIGHT))? as u8;
+		}
+		
+		if let Some(texture) = info.get("texture") {
+			let texture = texture.as_str().ok_or("'texture' must be a string")?;
+			attributes.textures = Some(Self::same_textures(texture));
+		}
+		
+		if let Some(textures) = info.get("textures") {
+			let textures = textures.as_table().ok_or("'textures' must be a table")?;
+			let faces = attributes.textures.get_or_insert_with(|| Self::same_textures(name));
+			
+			for key in textures.keys() {
+				if ! ["all", "side", "top", "bottom", "north", "south", "west", "east"].contains(&key.as_str()) {
+					return Err(format!("unknown face '{}' in 'textures'", key));
+				}
+			}
+			
+			let get = |key: &str| -> Result<Option<String>, String> {
+				match textures.get(key) {
+					Some(texture) => texture.as_str()
+						.map(|t| Some(t.to_string()))
+						.ok_or_else(|| format!("texture of '{}' must be a string", key)),
+					None => Ok(None)
+				}
+			};
+			
+			let all = get("all")?;
+			let side = get("side")?;
+			for (index, face) in BlockFace::ALL.iter().enumerate() {
+				let texture = get(face.get_name())?
+					.or_else(|| if face.is_side() { side.clone() } else { None })
+					.or_else(|| all.clone());
+				
+				if let Some(texture) = texture {
+					faces[index] = texture;
+				}
+			}
+		}
+		
+		Ok(attributes)
+	}
+	
+	/// Returns the name of the texture on the given face, if the block is rendered.
+	pub fn get_texture(&self, face: BlockFace) -> Option<&str> {
+		self.textures.as_ref().map(|textures| textures[face as usize].as_str())
+	}
+	
+	/// Returns `true` if the block has any geometry to render.
+	pub fn is_visible(&self) -> bool {
+		self.textures.is_some()
+	}
+	
+	/// Returns `true` if the block can be targeted by raycasts: if it is rendered or can be collided with,
+	/// so plants that can be walked through can be picked as well.
+	pub fn is_selectable(&self) -> bool {
+		self.is_visible() || self.collision.is_some()
+	}
+	
+	fn same_textures(texture: &str) -> [String; 6] {
+		[
+			texture.to_string(), texture.to_string(), texture.to_string(),
+			texture.to_string(), texture.to_string(), texture.to_string(),
+		]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn parse(config: &str) -> Result<BlockAttributes, String> {
+		let info: toml::Value = config.parse().unwrap();
+		BlockAttributes::from_config("block", Some(&info))
+	}
+	
+	fn textures(attributes: &BlockAttributes) -> Vec<&str> {
+		BlockFace::ALL.iter().map(|face| attributes.get_texture(*face).unwrap()).collect()
+	}
+	
+	#[test]
+	fn blocks_are_solid_opaque_cubes_by_default() {
+		for attributes in [BlockAttributes::from_config("block", None).unwrap(), parse("").unwrap()].iter() {
+			assert!(attributes.opaque);
+			assert!(attributes.solid);
+			assert_eq!(attributes.layer, RenderLayer::Opaque);
+			assert_eq!(attributes.collision, Some(BlockBox::full()));
+			assert_eq!(textures(attributes), vec!["block"; 6]);
+			assert_eq!(attributes.model, None);
+			assert_eq!(attributes.emission, 0);
+		}
+	}
+	
+	#[test]
+	fn specific_textures_override_general_ones() {
+		assert_eq!(textures(&parse(r#"texture = "stone""#).unwrap()), vec!["stone"; 6]);
+		
+		// top, bottom, north, south, west, east
+		let attributes = parse(r#"
+			texture = "stone"
+			textures = { all = "all", side = "side", north = "north" }
+		"#).unwrap();
+		assert_eq!(textures(&attributes), vec!["all", "all", "north", "side", "side", "side"]);
+		
+		// Faces that are not mentioned keep `texture`, or the name of the block.
+		let attributes = parse(r#"
+			texture = "stone"
+			textures = { top = "grass" }
+		"#).unwrap();
+		assert_eq!(textures(&attributes), vec!["grass", "stone", "stone", "stone", "stone", "stone"]);
+		
+		let attributes = parse(r#"textures = { bottom = "dirt", side = "grass_side" }"#).unwrap();
+		assert_eq!(textures(&attributes), vec!["block", "dirt", "grass_side", "grass_side", "grass_side", "grass_side"]);
+	}
+	
+	#[test]
+	fn only_the_opaque_layer_is_opaque() {
+		let opaque = |config: &str| parse(config).unwrap().opaque;
+		
+		assert!(opaque(r#"layer = "opaque""#));
+		assert!(!opaque(r#"layer = "cutout""#));
+		assert!(!opaque(r#"layer = "translucent""#));
+		assert!(!opaque(r#"model = "slab""#));
+		
+		// Unless said otherwise, either way.
+		assert!(opaque("layer = \"cutout\"\nopaque = true"));
+		assert!(opaque("model = \"slab\"\nopaque = true"));
+		assert!(!opaque("opaque = false"));
+		assert_eq!(parse("opaque = false").unwrap().layer, RenderLayer::Opaque);
+	}
+	
+	#[test]
+	fn collision_boxes() {
+		let slab = parse("collision = [0, 0, 0, 1, 0.5, 1]").unwrap();
+		assert_eq!(slab.collision, Some(BlockBox::new(cgmath::Vector3::new(0.0, 0.0, 0.0), cgmath::Vector3::new(1.0, 0.5, 1.0))));
+		assert!(slab.solid);
+		
+		// Blocks that are not solid can be walked through...
+		let plant = parse("solid = false").unwrap();
+		assert_eq!(plant.collision, None);
+		
+		// ...unless they are given a box anyway.
+		let carpet = parse("solid = false\ncollision = [0, 0, 0, 1, 0.0625, 1]").unwrap();
+		assert!(!carpet.solid);
+		assert!(carpet.collision.is_some());
+	}
+	
+	#[test]
+	fn invisible_blocks_without_a_box_are_not_selectable() {
+		assert!(parse("").unwrap().is_selectable());
+		assert!(parse("solid = false").unwrap().is_selectable());
+		assert!(!BlockAttributes::new_empty().is_selectable());
+		
+		let mut barrier = BlockAttributes::new_empty();
+		barrier.collision = Some(BlockBox::full());
+		assert!(barrier.is_selectable());
+	}
+	
+	#[test]
+	fn invalid_attributes_are_rejected() {
+		for config in [
+			r#"layer = "glass""#,
+			"layer = 1",
+			"model = true",
+			r#"opaque = "yes""#,
+			r#"solid = "no""#,
+			"collision = [0, 0, 0, 1, 1]",
+			"collision = [0, 0, 0, 1, \"1\", 1]",
+			"collision = [0, 1, 0, 1, 0.5, 1]",
+			"collision = true",
+			"emission = 16",
+			"emission = -1",
+			"emission = 1.5",
+			"texture = 1",
+			r#"textures = "stone""#,
+			r#"textures = { up = "stone" }"#,
+			"textures = { top = 1 }",
+		].iter() {
+			assert!(parse(config).is_err(), "{}", config);
+		}
+	}
+}
@@ -0,0 +1,579 @@
+//! Collision of axis-aligned boxes with the blocks of the world.
+//!
+//! Everything that moves through the world (players, items, projectiles) collides with the
+//! collision boxes of the blocks, as given by `BlockAttributes::collision`. This is plain maths
+//! on the CPU; nothing in here touches OpenGL.
+//!
+//! - `get_boxes` and `get_overlapping` query the collision boxes in a region.
+//! - `slide` moves a box one axis after the other, stopping along every axis it runs into something.
+//! - `cast` sweeps a box along its motion and finds the first block it hits.
+//! - `resolve_penetration` finds the shortest way out of the blocks a box is stuck in.
+
+use cgmath::Vector3;
+use super::attributes::BlockBox;
+use super::coords::BlockCoord;
+use super::world::ChunkStorage;
+
+/// Boxes closer than this count as touching, so rounding errors do not let boxes sink into each other.
+pub const EPSILON: f32 = 1.0e-4;
+
+/// Anything that has blocks to collide with.
+pub trait BlockColliders {
+	/// The collision box of the block at the given position, in block-local space,
+	/// or `None` if the block can be passed through.
+	fn get_collision_box(&self, pos: &BlockCoord) -> Option<BlockBox>;
+}
+
+impl BlockColliders for ChunkStorage {
+	/// Blocks in chunks that are not loaded are full cubes, so nothing falls out of the loaded world.
+	fn get_collision_box(&self, pos: &BlockCoord) -> Option<BlockBox> {
+		match self.get_block(pos) {
+			Some(block) => self.get_blockdef().get_attributes(&block).collision,
+			None => Some(BlockBox::full())
+		}
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// An axis-aligned box in world space.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Aabb {
+	pub min: Vector3<f32>,
+	pub max: Vector3<f32>,
+}
+
+impl Aabb {
+	pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+		Aabb { min, max }
+	}
+	
+	/// The given block-local box, placed at the given block.
+	pub fn from_block(pos: &BlockCoord, bounds: &BlockBox) -> Aabb {
+		let corner = pos.as_vec();
+		Aabb {
+			min: corner + bounds.min,
+			max: corner + bounds.max,
+		}
+	}
+	
+	pub fn offset(&self, offset: Vector3<f32>) -> Aabb {
+		Aabb {
+			min: self.min + offset,
+			max: self.max + offset,
+		}
+	}
+	
+	/// The box moved by the given distance along a single axis.
+	pub fn offset_axis(&self, axis: usize, distance: f32) -> Aabb {
+		let mut bounds = *self;
+		bounds.min[axis] += distance;
+		bounds.max[axis] += distance;
+		bounds
+	}
+	
+	/// The box covering both this box and where it ends up after the given motion.
+	pub fn expand(&self, motion: Vector3<f32>) -> Aabb {
+		let mut bounds = *self;
+		for axis in 0..3 {
+			if motion[axis] < 0.0 {
+				bounds.min[axis] += motion[axis];
+			} else {
+				bounds.max[axis] += motion[axis];
+			}
+		}
+		bounds
+	}
+	
+	/// Returns `true` if the boxes overlap along the given axis; touching does not count.
+	pub fn overlaps_axis(&self, other: &Aabb, axis: usize) -> bool {
+		self.max[axis] > other.min[axis] + EPSILON && self.min[axis] < other.max[axis] - EPSILON
+	}
+	
+	/// Returns `true` if the boxes overlap; touching does not count.
+	pub fn intersects(&self, other: &Aabb) -> bool {
+		(0..3).all(|axis| self.overlaps_axis(other, axis))
+	}
+	
+	/// Shortens the given distance along the axis so that moving by it does not enter any of the colliders.
+	///
+	/// Colliders that already overlap the box are ignored, so the box can always move out of them.
+	pub fn clip(&self, colliders: &[Aabb], axis: usize, mut distance: f32) -> f32 {
+		for collider in colliders {
+			let overlaps = (0..3)
+				.filter(|a| *a != axis)
+				.all(|a| self.overlaps_axis(collider, a));
+			
+			if !overlaps {
+				continue;
+			}
+			
+			if distance > 0.0 && self.max[axis] <= collider.min[axis] + EPSILON {
+				distance = distance.min(collider.min[axis] - self.max[axis]);
+			} else if distance < 0.0 && self.min[axis] >= collider.max[axis] - EPSILON {
+				distance = distance.max(collider.max[axis] - self.min[axis]);
+			}
+		}
+		distance
+	}
+	
+	/// The first and last block the box touches.
+	fn get_block_range(&self) -> (BlockCoord, BlockCoord) {
+		let to_block = |v: Vector3<f32>| BlockCoord::new(
+			v.x.floor() as isize,
+			v.y.floor() as isize,
+			v.z.floor() as isize
+		);
+		(to_block(self.min), to_block(self.max))
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Collects the collision boxes of all blocks the given box reaches into, with the position of their block.
+///
+/// These are candidates: some of them may only touch the given box, or not even that.
+pub fn get_boxes(world: &BlockColliders, bounds: &Aabb) -> Vec<(BlockCoord, Aabb)> {
+	let (min, max) = bounds.get_block_range();
+	let mut boxes = vec![];
+	
+	// One block further down, for collision boxes that are taller than their block.
+	for y in min.y - 1 ..= max.y {
+		for z in min.z ..= max.z {
+			for x in min.x ..= max.x {
+				let pos = BlockCoord::new(x, y, z);
+				if let Some(collision) = world.get_collision_box(&pos) {
+					let bounds = Aabb::from_block(&pos, &collision);
+					boxes.push((pos, bounds));
+				}
+			}
+		}
+	}
+	
+	boxes
+}
+
+/// Collects the collision boxes overlapping the given box, with the position of their block.
+pub fn get_overlapping(world: &BlockColliders, bounds: &Aabb) -> Vec<(BlockCoord, Aabb)> {
+	let mut boxes = get_boxes(world, bounds);
+	boxes.retain(|(_, other)| bounds.intersects(other));
+	boxes
+}
+
+/// Returns `true` if the given box does not overlap the collision box of any block.
+pub fn is_free(world: &BlockColliders, bounds: &Aabb) -> bool {
+	get_boxes(world, bounds).iter().all(|(_, other)| !bounds.intersects(other))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// How far a box moved by `slide`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Slide {
+	/// Where the box ended up.
+	pub bounds: Aabb,
+	
+	/// The distance the box actually moved.
+	pub offset: Vector3<f32>,
+	
+	/// The axes along which the box was stopped short.
+	pub blocked: [bool; 3],
+}
+
+/// Moves the box as far as the given motion goes without entering the collision box of any block.
+///
+/// The motion is swept along one axis after the other, vertical first, so a box running into
+/// a wall at an angle keeps sliding along it.
+pub fn slide(world: &BlockColliders, bounds: &Aabb, motion: Vector3<f32>) -> Slide {
+	let colliders: Vec<Aabb> = get_boxes(world, &bounds.expand(motion))
+		.into_iter()
+		.map(|(_, other)| other)
+		.collect();
+	
+	slide_among(&colliders, bounds, motion)
+}
+
+/// Like `slide`, but against the given colliders instead of the blocks of a world.
+pub fn slide_among(colliders: &[Aabb], bounds: &Aabb, motion: Vector3<f32>) -> Slide {
+	let mut moved = *bounds;
+	let mut blocked = [false; 3];
+	
+	for axis in [1, 0, 2].iter() {
+		let distance = moved.clip(colliders, *axis, motion[*axis]);
+		blocked[*axis] = distance != motion[*axis];
+		moved = moved.offset_axis(*axis, distance);
+	}
+	
+	Slide {
+		bounds: moved,
+		offset: moved.min - bounds.min,
+		blocked,
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Where a box sweeping through the world hit a block.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hit {
+	/// The fraction of the motion after which the boxes touch, from `0` to `1`.
+	pub time: f32,
+	
+	/// The axis along which the box hit the block; the normal of the face it hit.
+	pub axis: usize,
+	
+	/// The block that was hit.
+	pub pos: BlockCoord,
+	
+	/// The collision box that was hit.
+	pub bounds: Aabb,
+}
+
+/// Sweeps the box along the whole motion at once, and finds the first block it runs into.
+///
+/// Unlike `slide`, the box moves along a straight line, as projectiles do.
+/// Boxes the given box already overlaps are ignored, so it can always move out of them.
+pub fn cast(world: &BlockColliders, bounds: &Aabb, motion: Vector3<f32>) -> Option<Hit> {
+	let mut first: Option<Hit> = None;
+	
+	for (pos, other) in get_boxes(world, &bounds.expand(motion)) {
+		if let Some((time, axis)) = get_hit_time(bounds, &other, motion) {
+			if first.as_ref().map_or(true, |hit| time < hit.time) {
+				first = Some(Hit { time, axis, pos, bounds: other });
+			}
+		}
+	}
+	
+	first
+}
+
+/// The time and axis at which the moving box first touches the other one, if it does before the motion ends.
+fn get_hit_time(bounds: &Aabb, other: &Aabb, motion: Vector3<f32>) -> Option<(f32, usize)> {
+	let mut entry = std::f32::NEG_INFINITY;
+	let mut exit = std::f32::INFINITY;
+	let mut entry_axis = None;
+	
+	for axis in 0..3 {
+		let m = motion[axis];
+		
+		// Without moving along this axis, the boxes have to overlap along it already.
+		if m == 0.0 {
+			if !bounds.overlaps_axis(other, axis) {
+				return None;
+			}
+			continue;
+		}
+		
+		let (near, far) = if m > 0.0 {
+			(other.min[axis] - bounds.max[axis], other.max[axis] - bounds.min[axis])
+		} else {
+			(other.max[axis] - bounds.min[axis], other.min[axis] - bounds.max[axis])
+		};
+		
+		if near / m > entry {
+			entry = near / m;
+			entry_axis = Some(axis);
+		}
+		
+		exit = exit.min(far / m);
+	}
+	
+	let axis = entry_axis?;
+	
+	// Boxes that overlap before moving at all are not hit.
+	if entry * motion[axis].abs() < -EPSILON || entry > 1.0 || entry >= exit {
+		return None;
+	}
+	
+	Some((entry.max(0.0), axis))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Finds the shortest move along a single axis that gets the box out of all collision boxes.
+///
+/// Returns a zero offset if the box is not stuck in anything,
+/// and `None` if getting out would take further than `max_distance`.
+/// Of equally short moves, moving up is preferred.
+pub fn resolve_penetration(world: &BlockColliders, bounds: &Aabb, max_distance: f32) -> Option<Vector3<f32>> {
+	if is_free(world, bounds) {
+		return Some(Vector3::new(0.0, 0.0, 0.0));
+	}
+	
+	let region = bounds.expand(Vector3::new(max_distance, max_distance, max_distance))
+		.expand(Vector3::new(-max_distance, -max_distance, -max_distance));
+	
+	let colliders: Vec<Aabb> = get_boxes(world, &region)
+		.into_iter()
+		.map(|(_, other)| other)
+		.collect();
+	
+	let directions = [(1, 1.0), (1, -1.0), (0, 1.0), (0, -1.0), (2, 1.0), (2, -1.0)];
+	let mut best: Option<(usize, f32)> = None;
+	
+	for (axis, sign) in directions.iter() {
+		let (axis, sign) = (*axis, *sign);
+		let mut distance = 0.0f32;
+		
+		// Push the box along the axis until it is clear of everything, or too far.
+		loop {
+			let moved = bounds.offset_axis(axis, distance * sign);
+			let pushes: Vec<f32> = colliders.iter()
+				.filter(|other| moved.intersects(other))
+				.map(|other| if sign > 0.0 {
+					other.max[axis] - bounds.min[axis]
+				} else {
+					bounds.max[axis] - other.min[axis]
+				})
+				.collect();
+			
+			if pushes.is_empty() {
+				break;
+			}
+			
+			distance = pushes.into_iter().fold(distance, f32::max);
+			
+			if distance > max_distance {
+				break;
+			}
+		}
+		
+		if distance <= max_distance && best.map_or(true, |(_, best)| distance < best) {
+			best = Some((axis, distance * sign));
+		}
+	}
+	
+	best.map(|(axis, distance)| {
+		let mut offset = Vector3::new(0.0, 0.0, 0.0);
+		offset[axis] = distance;
+		offset
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+	
+	/// A world of the given blocks, without any chunks.
+	struct Blocks(HashMap<(isize, isize, isize), BlockBox>);
+	
+	impl BlockColliders for Blocks {
+		fn get_collision_box(&self, pos: &BlockCoord) -> Option<BlockBox> {
+			self.0.get(&(pos.x, pos.y, pos.z)).cloned()
+		}
+	}
+	
+	/// Full cubes at the given positions.
+	fn blocks(positions: &[(isize, isize, isize)]) -> Blocks {
+		Blocks(positions.iter().map(|pos| (*pos, BlockBox::full())).collect())
+	}
+	
+	fn v(x: f32, y: f32, z: f32) -> Vector3<f32> {
+		Vector3::new(x, y, z)
+	}
+	
+	fn cube(x: f32, y: f32, z: f32) -> Aabb {
+		Aabb::new(v(x, y, z), v(x + 1.0, y + 1.0, z + 1.0))
+	}
+	
+	fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+		assert!((0..3).all(|axis| (a[axis] - b[axis]).abs() < 1.0e-5), "{:?} is not {:?}", a, b);
+	}
+	
+	#[test]
+	fn slide_stops_at_walls() {
+		let colliders = [cube(2.0, 0.0, 0.0), cube(0.0, -1.0, 0.0)];
+		let bounds = Aabb::new(v(0.2, 0.0, 0.2), v(0.8, 1.0, 0.8));
+		
+		let slide = slide_among(&colliders, &bounds, v(3.0, 0.0, 0.0));
+		assert_close(slide.offset, v(1.2, 0.0, 0.0));
+		assert_eq!(slide.blocked, [true, false, false]);
+		
+		// Falling onto the floor, however far.
+		let slide = slide_among(&colliders, &bounds.offset(v(0.0, 50.0, 0.0)), v(0.0, -100.0, 0.0));
+		assert_close(slide.bounds.min, bounds.min);
+		assert_eq!(slide.blocked, [false, true, false]);
+		
+		// Into the wall at an angle: the motion along the wall is kept.
+		let slide = slide_among(&colliders, &bounds, v(3.0, 0.0, 0.5));
+		assert_close(slide.offset, v(1.2, 0.0, 0.5));
+		assert_eq!(slide.blocked, [true, false, false]);
+	}
+	
+	#[test]
+	fn slide_into_corners() {
+		// Walls on +x and +z, meeting in a corner.
+		let colliders = [cube(2.0, 0.0, 0.0), cube(2.0, 0.0, 1.0), cube(0.0, 0.0, 2.0), cube(1.0, 0.0, 2.0)];
+		let bounds = Aabb::new(v(0.2, 0.2, 0.2), v(0.8, 0.8, 0.8));
+		
+		let slide = slide_among(&colliders, &bounds, v(3.0, 0.0, 3.0));
+		assert_close(slide.offset, v(1.2, 0.0, 1.2));
+		assert_eq!(slide.blocked, [true, false, true]);
+		
+		// Heading diagonally for the edge of a single block: `x` moves first and passes
+		// beside the block, then `z` runs into its side.
+		let colliders = [cube(1.0, 0.0, 1.0)];
+		let slide = slide_among(&colliders, &bounds, v(1.0, 0.0, 1.0));
+		assert_close(slide.offset, v(1.0, 0.0, 0.2));
+		assert_eq!(slide.blocked, [false, false, true]);
+	}
+	
+	#[test]
+	fn slide_grazing_contact() {
+		let colliders = [cube(0.0, -1.0, 0.0), cube(1.0, 0.0, 0.0)];
+		
+		// Touching the floor and a wall while moving along them is not blocked.
+		let bounds = Aabb::new(v(0.2, 0.0, 0.2), v(1.0, 1.0, 0.8));
+		let slide = slide_among(&colliders, &bounds, v(0.0, 0.0, 1.0));
+		assert_eq!(slide.offset, v(0.0, 0.0, 1.0));
+		assert_eq!(slide.blocked, [false; 3]);
+		
+		// Moving away from them is not blocked either, but moving into them is.
+		let slide = slide_among(&colliders, &bounds, v(-0.5, 0.5, 0.0));
+		assert_eq!(slide.blocked, [false; 3]);
+		let slide = slide_among(&colliders, &bounds, v(0.5, -0.5, 0.0));
+		assert_eq!(slide.offset, v(0.0, 0.0, 0.0));
+		assert_eq!(slide.blocked, [true, true, false]);
+		
+		// Passing a box that only shares an edge with the path.
+		let bounds = Aabb::new(v(0.2, 1.0, 0.2), v(0.8, 2.0, 0.8));
+		let slide = slide_among(&colliders, &bounds, v(2.0, 0.0, 0.0));
+		assert_eq!(slide.blocked, [false; 3]);
+	}
+	
+	#[test]
+	fn hit_time_and_axis() {
+		let bounds = Aabb::new(v(0.25, 0.25, 0.25), v(0.75, 0.75, 0.75));
+		
+		assert_eq!(get_hit_time(&bounds, &cube(3.0, 0.0, 0.0), v(4.0, 0.0, 0.0)), Some((2.25 / 4.0, 0)));
+		assert_eq!(get_hit_time(&bounds, &cube(0.0, -2.0, 0.0), v(0.0, -2.0, 0.0)), Some((1.25 / 2.0, 1)));
+		assert_eq!(get_hit_time(&bounds, &cube(0.0, 0.0, -3.0), v(0.0, 0.0, -4.0)), Some((2.25 / 4.0, 2)));
+		
+		// The axis is the one along which the boxes start to overlap last.
+		let (time, axis) = get_hit_time(&bounds, &cube(2.0, -2.0, 0.0), v(2.0, -4.0, 0.0)).unwrap();
+		assert_eq!((time, axis), (1.25 / 2.0, 0));
+		
+		// Too short, away from it, and missing it.
+		assert_eq!(get_hit_time(&bounds, &cube(3.0, 0.0, 0.0), v(2.0, 0.0, 0.0)), None);
+		assert_eq!(get_hit_time(&bounds, &cube(3.0, 0.0, 0.0), v(-4.0, 0.0, 0.0)), None);
+		assert_eq!(get_hit_time(&bounds, &cube(3.0, 2.0, 0.0), v(4.0, 0.5, 0.0)), None);
+	}
+	
+	#[test]
+	fn hit_time_without_motion_along_an_axis() {
+		let bounds = Aabb::new(v(0.25, 0.25, 0.25), v(0.75, 0.75, 0.75));
+		
+		// Not moving along `y` and `z`, the boxes have to overlap along them already...
+		assert_eq!(get_hit_time(&bounds, &cube(2.0, 0.5, 0.0), v(2.0, 0.0, 0.0)), Some((1.25 / 2.0, 0)));
+		assert_eq!(get_hit_time(&bounds, &cube(2.0, 1.0, 0.0), v(2.0, 0.0, 0.0)), None);
+		assert_eq!(get_hit_time(&bounds, &cube(2.0, 0.0, 3.0), v(2.0, 0.0, 0.0)), None);
+		
+		// ...and without any motion, nothing is hit.
+		assert_eq!(get_hit_time(&bounds, &cube(0.75, 0.0, 0.0), v(0.0, 0.0, 0.0)), None);
+		
+		// Sliding along a face does not hit it.
+		let on_top = Aabb::new(v(0.25, 1.0, 0.25), v(0.75, 1.5, 0.75));
+		assert_eq!(get_hit_time(&on_top, &cube(0.0, 0.0, 0.0), v(0.0, 0.0, 2.0)), None);
+	}
+	
+	#[test]
+	fn hit_time_of_overlapping_boxes() {
+		// Boxes that overlap before moving are not hit, whichever way the box moves.
+		let inside = Aabb::new(v(0.25, 0.25, 0.25), v(0.75, 0.75, 0.75));
+		assert_eq!(get_hit_time(&inside, &cube(0.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), None);
+		assert_eq!(get_hit_time(&inside, &cube(0.0, 0.0, 0.0), v(-1.0, 0.5, 0.0)), None);
+		
+		// Touching boxes are hit right away when moving into each other.
+		let touching = Aabb::new(v(0.5, 0.25, 0.25), v(1.0, 0.75, 0.75));
+		assert_eq!(get_hit_time(&touching, &cube(1.0, 0.0, 0.0), v(1.0, 0.0, 0.0)), Some((0.0, 0)));
+	}
+	
+	#[test]
+	fn cast_finds_the_first_hit() {
+		let world = blocks(&[(2, 0, 0), (3, 0, 0), (5, 0, 0), (0, -1, 0)]);
+		let bounds = Aabb::new(v(0.25, 0.25, 0.25), v(0.75, 0.75, 0.75));
+		
+		let hit = cast(&world, &bounds, v(10.0, 0.0, 0.0)).unwrap();
+		assert_eq!(hit.pos, BlockCoord::new(2, 0, 0));
+		assert_eq!(hit.axis, 0);
+		assert_eq!(hit.time, 1.25 / 10.0);
+		assert_eq!(hit.bounds, cube(2.0, 0.0, 0.0));
+		
+		let hit = cast(&world, &bounds, v(0.0, -2.0, 0.0)).unwrap();
+		assert_eq!((hit.pos, hit.axis), (BlockCoord::new(0, -1, 0), 1));
+		
+		// Blocks the box starts out in are ignored.
+		let inside = bounds.offset(v(2.0, 0.0, 0.0));
+		let hit = cast(&world, &inside, v(2.0, 0.0, 0.0)).unwrap();
+		assert_eq!((hit.pos, hit.time), (BlockCoord::new(3, 0, 0), 0.25 / 2.0));
+		assert!(cast(&world, &inside, v(0.0, 2.0, 0.0)).is_none());
+		assert!(cast(&world, &bounds, v(0.0, 0.0, 0.0)).is_none());
+	}
+	
+	#[test]
+	fn penetration_prefers_up() {
+		let world = blocks(&[(0, 0, 0)]);
+		
+		let free = Aabb::new(v(0.2, 1.0, 0.2), v(0.8, 2.0, 0.8));
+		assert_eq!(resolve_penetration(&world, &free, 1.0), Some(v(0.0, 0.0, 0.0)));
+		
+		// Sunk into the top: up is the shortest way out.
+		assert_close(resolve_penetration(&world, &free.offset(v(0.0, -0.3, 0.0)), 1.0).unwrap(), v(0.0, 0.3, 0.0));
+		
+		// Poking in from the side: sideways is.
+		let side = Aabb::new(v(0.9, 0.2, 0.2), v(1.5, 0.8, 0.8));
+		assert_close(resolve_penetration(&world, &side, 1.0).unwrap(), v(0.1, 0.0, 0.0));
+		
+		// In the middle of the block, every way out is as long; up wins.
+		let centered = Aabb::new(v(0.25, 0.25, 0.25), v(0.75, 0.75, 0.75));
+		assert_eq!(resolve_penetration(&world, &centered, 2.0), Some(v(0.0, 0.75, 0.0)));
+	}
+	
+	#[test]
+	fn penetration_respects_max_distance() {
+		// Buried in a column; the way out at the sides or below is 1.8 blocks long, up is 2.8.
+		let world = blocks(&[(0, 0, 0), (0, 1, 0), (0, 2, 0), (1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1), (0, -1, 0)]);
+		let inside = Aabb::new(v(0.2, 0.2, 0.2), v(0.8, 0.8, 0.8));
+		
+		assert_eq!(resolve_penetration(&world, &inside, 1.0), None);
+		assert_eq!(resolve_penetration(&world, &inside, 1.7), None);
+		
+		let push = resolve_penetration(&world, &inside, 3.0).unwrap();
+		assert!((push.x.abs() + push.y.abs() + push.z.abs() - 1.8).abs() < 1.0e-5, "{:?}", push);
+		assert!(is_free(&world, &inside.offset(push)));
+		
+		// Buried deeper, with a shaft above; only the way up is short enough.
+		let mut positions = vec![];
+		for x in -2..3 {
+			for y in -2..3 {
+				for z in -2..3 {
+					if (x, z) != (0, 0) || y < 2 {
+						positions.push((x, y, z));
+					}
+				}
+			}
+		}
+		let world = blocks(&positions);
+		assert_eq!(resolve_penetration(&world, &inside, 1.7), None);
+		assert_close(resolve_penetration(&world, &inside, 1.8).unwrap(), v(0.0, 1.8, 0.0));
+	}
+	
+	#[test]
+	fn touching_boxes_do_not_overlap() {
+		let world = blocks(&[(0, 0, 0), (1, 0, 0)]);
+		
+		let across = Aabb::new(v(0.5, 0.2, 0.2), v(1.5, 0.8, 0.8));
+		assert_eq!(get_overlapping(&world, &across).len(), 2);
+		
+		// Touching a face, an edge or a corner.
+		for touching in [cube(2.0, 0.0, 0.0), cube(0.0, 1.0, 0.0), cube(-1.0, 0.0, 0.0), cube(2.0, 1.0, 0.0), cube(-1.0, -1.0, -1.0)].iter() {
+			assert!(get_overlapping(&world, touching).is_empty(), "{:?}", touching);
+			assert!(is_free(&world, touching));
+		}
+		
+		// Within the epsilon counts as touching, too.
+		let close = cube(2.0 - EPSILON / 2.0, 0.0, 0.0);
+		assert!(get_overlapping(&world, &close).is_empty());
+	}
+}
@@ -0,0 +1,437 @@
+//! Generators that fill new chunks with blocks.
+//!
+//! The generator of a world is chosen by the `[generator]` table of the scene config:
+//!
+//! ```toml
+//! [generator]
+//! type = "terrain"   # or "grid"
+//! seed = 1234
+//! height = 24        # average height of the surface
+//! amplitude = 12     # how far the surface goes above and below that
+//! scale = 48.0       # horizontal size of hills, in blocks
+//! octaves = 4
+//! caves = 0.55       # noise threshold above which blocks are carved out; 1.0 for no caves
+//! cave-scale = 16.0
+//! filler = "bedrock"
+//! layers = [
+//!     { block = "grass", depth = 1 },
+//!     { block = "bedrock3", depth = 3 },
+//! ]
+//! ```
+//!
+//! Every key is optional. Without a `[generator]` table, chunks get the bedrock grid.
+//!
+//! Generators are deterministic: the same seed and position always produce the same chunk.
+
+use super::universe::UniverseRef;
+use super::BlockState;
+use super::world::{Chunk, ChunkCoord, CHUNK_SIZE};
+
+#[derive(Debug, Fail)]
+pub enum GeneratorError {
+	#[fail(display = "Unknown world generator '{}'", name)]
+	UnknownGenerator { name: String },
+	
+	#[fail(display = "Unknown block state '{}' in generator config", state)]
+	UnknownBlock { state: String },
+	
+	#[fail(display = "Invalid generator config: {}", reason)]
+	InvalidConfig { reason: String },
+}
+
+/// Fills new chunks with blocks.
+pub trait WorldGenerator {
+	/// Fills the given chunk, which is empty, according to its position.
+	fn generate(&self, chunk: &mut Chunk);
+}
+
+/// Creates the generator described by the given `[generator]` config table.
+pub fn from_config(blockdef: &UniverseRef, config: Option<&toml::Value>) -> Result<Box<WorldGenerator>, GeneratorError> {
+	let config = match config {
+		Some(config) => config.as_table().ok_or_else(|| GeneratorError::InvalidConfig {
+			reason: "'generator' must be a table".to_string()
+		})?,
+		None => return Ok(Box::new(GridGenerator::new(blockdef)))
+	};
+	
+	let name = match config.get("type") {
+		Some(name) => name.as_str().ok_or_else(|| GeneratorError::InvalidConfig {
+			reason: "'type' must be a string".to_string()
+		})?,
+		None => "terrain"
+	};
+	
+	info!("Using world generator: {}", name);
+	
+	match name {
+		"grid" => Ok(Box::new(GridGenerator::new(blockdef))),
+		"terrain" => Ok(Box::new(TerrainGenerator::from_config(blockdef, config)?)),
+		_ => Err(GeneratorError::UnknownGenerator { name: name.to_string() })
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Outlines every chunk with bedrock along its edges.
+pub struct GridGenerator {
+	fill: BlockState,
+}
+
+impl GridGenerator {
+	pub fn new(blockdef: &UniverseRef) -> GridGenerator {
+		GridGenerator {
+			fill: blockdef
+				.get_block_by_name_unchecked("bedrock")
+				.get_default_state()
+		}
+	}
+}
+
+impl WorldGenerator for GridGenerator {
+	fn generate(&self, chunk: &mut Chunk) {
+		chunk.fill_with_grid(self.fill);
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A layer of blocks below the surface.
+pub struct TerrainLayer {
+	pub block: BlockState,
+	pub depth: usize,
+}
+
+/// Rolling hills of layered blocks, with caves carved into them.
+pub struct TerrainGenerator {
+	/// The noise of the heightmap.
+	surface_noise: ValueNoise,
+	
+	/// The noise of the caves; seeded apart from the heightmap, so caves do not follow the hills.
+	cave_noise: ValueNoise,
+	
+	/// The average height of the surface.
+	pub height: f64,
+	
+	/// How far the surface goes above and below its average height.
+	pub amplitude: f64,
+	
+	/// The horizontal size of hills, in blocks.
+	pub scale: f64,
+	
+	pub octaves: usize,
+	
+	/// Blocks where the cave noise is above this are left empty.
+	pub cave_threshold: f64,
+	
+	/// The size of caves, in blocks.
+	pub cave_scale: f64,
+	
+	/// The layers below the surface, from the top down.
+	pub layers: Vec<TerrainLayer>,
+	
+	/// The block below the last layer.
+	pub filler: BlockState,
+	
+	air: BlockState,
+}
+
+impl TerrainGenerator {
+	/// Creates a generator with the default parameters.
+	pub fn new(blockdef: &UniverseRef, seed: u64) -> TerrainGenerator {
+		let bedrock = blockdef
+			.get_block_by_name_unchecked("bedrock")
+			.get_default_state();
+		
+		TerrainGenerator {
+			surface_noise: ValueNoise::new_layer(seed, 0),
+			cave_noise: ValueNoise::new_layer(seed, 1),
+			height: 24.0,
+			amplitude: 12.0,
+			scale: 48.0,
+			octaves: 4,
+			cave_threshold: 0.55,
+			cave_scale: 16.0,
+			layers: vec![],
+			filler: bedrock,
+			air: blockdef
+				.get_block_by_name_unchecked("air")
+				.get_default_state(),
+		}
+	}
+	
+	pub fn from_config(blockdef: &UniverseRef, config: &toml::value::Table) -> Result<TerrainGenerator, GeneratorError> {
+		let invalid = |reason: &str| GeneratorError::InvalidConfig { reason: reason.to_string() };
+		
+		let get_number = |key: &str| -> Result<Option<f64>, GeneratorError> {
+			match config.get(key) {
+				Some(toml::Value::Float(x)) => Ok(Some(*x)),
+				Some(toml::Value::Integer(x)) => Ok(Some(*x as f64)),
+				Some(_) => Err(GeneratorError::InvalidConfig { reason: format!("'{}' must be a number", key) }),
+				None => Ok(None)
+			}
+		};
+		
+		let get_state = |state: &str| blockdef.get_state_by_string(state)
+			.ok_or_else(|| GeneratorError::UnknownBlock { state: state.to_string() });
+		
+		let seed = match config.get("seed") {
+			Some(seed) => seed.as_integer().ok_or_else(|| invalid("'seed' must be an integer"))? as u64,
+			None => 0
+		};
+		
+		let mut generator = TerrainGenerator::new(blockdef, seed);
+		
+		if let Some(height) = get_number("height")? {
+			generator.height = height;
+		}
+		
+		if let Some(amplitude) = get_number("amplitude")? {
+			generator.amplitude = amplitude;
+		}
+		
+		if let Some(scale) = get_number("scale")? {
+			if scale <= 0.0 {
+				return Err(invalid("'scale' must be positive"));
+			}
+			generator.scale = scale;
+		}
+		
+		if let Some(octaves) = config.get("octaves") {
+			generator.octaves = octaves.as_integer()
+				.filter(|o| *o > 0)
+				.ok_or_else(|| invalid("'octaves' must be a positive integer"))? as usize;
+		}
+		
+		if let Some(caves) = get_number("caves")? {
+			generator.cave_threshold = caves;
+		}
+		
+		if let Some(cave_scale) = get_number("cave-scale")? {
+			if cave_scale <= 0.0 {
+				return Err(invalid("'cave-scale' must be positive"));
+			}
+			generator.cave_scale = cave_scale;
+		}
+		
+		if let Some(filler) = config.get("filler") {
+			generator.filler = get_state(filler.as_str().ok_or_else(|| invalid("'filler' must be a string"))?)?;
+		}
+		
+		if let Some(layers) = config.get("layers") {
+			let layers = layers.as_array().ok_or_else(|| invalid("'layers' must be an array"))?;
+			for layer in layers {
+				let block = layer.get("block")
+					.and_then(|b| b.as_str())
+					.ok_or_else(|| invalid("every layer needs a 'block'"))?;
+				
+				let depth = match layer.get("depth") {
+					Some(depth) => depth.as_integer()
+						.filter(|d| *d > 0)
+						.ok_or_else(|| invalid("the 'depth' of a layer must be a positive integer"))? as usize,
+					None => 1
+				};
+				
+				generator.layers.push(TerrainLayer {
+					block: get_state(block)?,
+					depth,
+				});
+			}
+		}
+		
+		Ok(generator)
+	}
+	
+	/// The height of the surface at the given column; blocks above it are empty.
+	pub fn get_surface(&self, x: isize, z: isize) -> isize {
+		let noise = self.surface_noise.fractal2(x as f64 / self.scale, z as f64 / self.scale, self.octaves);
+		(self.height + noise * self.amplitude).floor() as isize
+	}
+	
+	/// Whether the block at the given position is carved out by a cave.
+	pub fn is_cave(&self, x: isize, y: isize, z: isize) -> bool {
+		let s = self.cave_scale;
+		let noise = self.cave_noise.fractal3(x as f64 / s, y as f64 / s, z as f64 / s, 2);
+		noise > self.cave_threshold
+	}
+	
+	/// The block at the given depth below the surface, counting the surface itself as zero.
+	fn get_layer(&self, depth: usize) -> BlockState {
+		let mut top = 0;
+		for layer in self.layers.iter() {
+			if depth < top + layer.depth {
+				return layer.block;
+			}
+			top += layer.depth;
+		}
+		self.filler
+	}
+}
+
+impl WorldGenerator for TerrainGenerator {
+	fn generate(&self, chunk: &mut Chunk) {
+		let size = CHUNK_SIZE as isize;
+		let ChunkCoord { x: cx, y: cy, z: cz } = chunk.pos;
+		
+		for z in 0..size {
+			for x in 0..size {
+				let (wx, wz) = (cx * size + x, cz * size + z);
+				let surface = self.get_surface(wx, wz);
+				
+				for y in 0..size {
+					let wy = cy * size + y;
+					if wy > surface {
+						break;
+					}
+					
+					let block = if self.is_cave(wx, wy, wz) {
+						self.air
+					} else {
+						self.get_layer((surface - wy) as usize)
+					};
+					
+					chunk.set_block(x, y, z, block);
+				}
+			}
+		}
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Smoothly interpolated random values on an integer lattice.
+///
+/// The values only depend on the seed and the position, so the noise is the same on every run.
+struct ValueNoise {
+	seed: u64,
+}
+
+impl ValueNoise {
+	/// Noise for one of several independent layers generated from the same seed.
+	fn new_layer(seed: u64, layer: u64) -> ValueNoise {
+		ValueNoise {
+			seed: mix(seed.wrapping_add(layer.wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+		}
+	}
+	
+	/// A random value from -1 to 1 for the given lattice point.
+	fn lattice(&self, x: i64, y: i64, z: i64) -> f64 {
+		let h = mix(self.seed
+			^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+			^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+			^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9));
+		(h >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+	}
+	
+	fn noise3(&self, x: f64, y: f64, z: f64) -> f64 {
+		let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+		let (fx, fy, fz) = (smooth(x - x0), smooth(y - y0), smooth(z - z0));
+		let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+		
+		let plane = |y: i64, z: i64| lerp(self.lattice(x0, y, z), self.lattice(x0 + 1, y, z), fx);
+		let row = |z: i64| lerp(plane(y0, z), plane(y0 + 1, z), fy);
+		lerp(row(z0), row(z0 + 1), fz)
+	}
+	
+	/// Noise summed over octaves of doubling frequency and halving amplitude, from -1 to 1.
+	fn fractal3(&self, x: f64, y: f64, z: f64, octaves: usize) -> f64 {
+		let mut sum = 0.0;
+		let mut total = 0.0;
+		let mut amplitude = 1.0;
+		let mut frequency = 1.0;
+		
+		for octave in 0..octaves {
+			// Shift every octave, so their lattices do not line up.
+			let offset = octave as f64 * 17.31;
+			sum += self.noise3(x * frequency + offset, y * frequency + offset, z * frequency + offset) * amplitude;
+			total += amplitude;
+			amplitude *= 0.5;
+			frequency *= 2.0;
+		}
+		
+		sum / total
+	}
+	
+	fn fractal2(&self, x: f64, z: f64, octaves: usize) -> f64 {
+		self.fractal3(x, 0.0, z, octaves)
+	}
+}
+
+/// The SplitMix64 finalizer: scrambles the bits of a value.
+fn mix(mut h: u64) -> u64 {
+	h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	h ^ (h >> 31)
+}
+
+fn smooth(t: f64) -> f64 {
+	t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+	a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::universe::define_universe;
+	use super::super::world::CHUNK_VOLUME;
+	
+	const CONFIG: &str = r#"
+		[blocks.grass]
+		[blocks.bedrock3]
+		
+		[generator]
+		layers = [{ block = "grass" }, { block = "bedrock3", depth = 3 }]
+	"#;
+	
+	/// Generates a few chunks around the surface with the given seed, returning their blocks.
+	fn generate(seed: i64) -> Vec<Vec<BlockState>> {
+		let mut config: toml::Value = CONFIG.parse().unwrap();
+		config["generator"].as_table_mut().unwrap().insert("seed".to_string(), toml::Value::Integer(seed));
+		
+		let blockdef = define_universe(config.as_table().unwrap());
+		let generator = from_config(&blockdef, config.get("generator")).unwrap();
+		
+		let mut chunks = vec![];
+		for &(x, y, z) in [(0, 0, 0), (0, 1, 0), (-1, 1, 2), (3, 2, -5), (-7, 0, -7)].iter() {
+			let mut chunk = Chunk::new_empty(blockdef.clone(), x, y, z);
+			generator.generate(&mut chunk);
+			chunks.push((0..CHUNK_VOLUME).map(|location| chunk.blocks.get(location)).collect());
+		}
+		chunks
+	}
+	
+	#[test]
+	fn same_seed_gives_same_chunks() {
+		let chunks = generate(1234);
+		assert_eq!(chunks, generate(1234));
+		
+		// Not trivially equal: the chunks hold more than a single kind of block.
+		let states: std::collections::HashSet<&BlockState> = chunks.iter().flat_map(|chunk| chunk.iter()).collect();
+		assert!(states.len() > 2);
+	}
+	
+	/// FNV-1a over the ids and data of all given states; unlike the std hashers, it never changes.
+	fn checksum(chunks: &[Vec<BlockState>]) -> u64 {
+		let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+		for state in chunks.iter().flat_map(|chunk| chunk.iter()) {
+			let bytes = [state.id.get_raw_id() as u16, state.data];
+			for byte in bytes.iter().flat_map(|x| x.to_le_bytes().to_vec()) {
+				hash = (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+			}
+		}
+		hash
+	}
+	
+	#[test]
+	fn seed_1234_gives_the_same_chunks_as_ever() {
+		// Changing this breaks every world that was saved with unmodified chunks left out.
+		assert_eq!(checksum(&generate(1234)), 0xe1eb_4ac6_4e3d_0d54);
+	}
+	
+	#[test]
+	fn different_seed_gives_different_chunks() {
+		assert!(generate(1234) != generate(99));
+	}
+}
@@ -0,0 +1,551 @@
+//! Sky light and block light, spread through the world by flood fill.
+//!
+//! Every block holds two light levels from 0 to `MAX_LIGHT`: sky light, which shines into the
+//! top of every chunk that has no chunk above it and travels straight down without getting weaker,
+//! and block light, which is given off by emissive blocks. Both lose one level for every block they
+//! spread, and neither enters opaque blocks.
+//!
+//! The chunk storage keeps light up to date as blocks change and chunks come and go, using the
+//! usual pair of queues: one taking away light whose source is gone, and one spreading light out.
+
+use std::collections::VecDeque;
+use rustc_hash::FxHashSet;
+use super::world::{Chunk, ChunkCoord, ChunkStorage};
+use super::world::{CHUNK_SIZE, CHUNK_SIZE_MASK, CHUNK_SIZE_SHIFT, CHUNK_SLICE, CHUNK_VOLUME};
+
+/// The brightest light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The two kinds of light every block holds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum LightChannel {
+	Sky,
+	Block,
+}
+
+impl LightChannel {
+	pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+}
+
+/// The light levels of a number of blocks, both channels packed into one byte per block.
+#[derive(Clone)]
+pub struct LightStorage {
+	levels: Vec<u8>,
+}
+
+impl LightStorage {
+	/// Creates storage for the given number of blocks, all of them dark.
+	pub fn new(len: usize) -> LightStorage {
+		LightStorage {
+			levels: vec![0; len],
+		}
+	}
+	
+	pub fn len(&self) -> usize {
+		self.levels.len()
+	}
+	
+	pub fn get(&self, index: usize, channel: LightChannel) -> u8 {
+		match channel {
+			LightChannel::Sky => self.levels[index] >> 4,
+			LightChannel::Block => self.levels[index] & 0xF,
+		}
+	}
+	
+	pub fn set(&mut self, index: usize, channel: LightChannel, level: u8) {
+		let level = level.min(MAX_LIGHT);
+		let packed = &mut self.levels[index];
+		*packed = match channel {
+			LightChannel::Sky => (*packed & 0x0F) | (level << 4),
+			LightChannel::Block => (*packed & 0xF0) | level,
+		};
+	}
+	
+	/// The brighter of both channels.
+	pub fn get_max(&self, index: usize) -> u8 {
+		self.get(index, LightChannel::Sky).max(self.get(index, LightChannel::Block))
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The six directions light spreads in.
+const DIRECTIONS: [(isize, isize, isize); 6] = [(-1,0,0), (1,0,0), (0,-1,0), (0,1,0), (0,0,-1), (0,0,1)];
+
+/// The direction sky light keeps its full strength in.
+const DOWN: (isize, isize, isize) = (0, -1, 0);
+
+type Pos = (isize, isize, isize);
+
+/// The index of a block within its chunk.
+fn chunk_index(pos: Pos) -> usize {
+	let csm = CHUNK_SIZE_MASK as isize;
+	((pos.1 & csm) as usize) * CHUNK_SLICE + ((pos.2 & csm) as usize) * CHUNK_SIZE + (pos.0 & csm) as usize
+}
+
+fn chunk_pos(pos: Pos) -> ChunkCoord {
+	let shift = CHUNK_SIZE_SHIFT;
+	ChunkCoord::new_from_chunk(pos.0 >> shift, pos.1 >> shift, pos.2 >> shift)
+}
+
+/// The light a block has after spreading one step in the given direction.
+fn spread(level: u8, channel: LightChannel, direction: Pos) -> u8 {
+	if channel == LightChannel::Sky && direction == DOWN && level == MAX_LIGHT {
+		MAX_LIGHT
+	} else {
+		level.saturating_sub(1)
+	}
+}
+
+/// A single change to the light of a chunk storage, in progress.
+struct LightUpdate<'a> {
+	storage: &'a mut ChunkStorage,
+	
+	/// Blocks whose light spreads to their neighbours.
+	add: VecDeque<(Pos, LightChannel)>,
+	
+	/// Blocks whose light was taken away, with the level they had.
+	remove: VecDeque<(Pos, LightChannel, u8)>,
+	
+	/// Chunks whose meshes have to be rebuilt.
+	dirty: FxHashSet<ChunkCoord>,
+}
+
+impl<'a> LightUpdate<'a> {
+	fn new(storage: &'a mut ChunkStorage) -> LightUpdate<'a> {
+		LightUpdate {
+			storage,
+			add: VecDeque::new(),
+			remove: VecDeque::new(),
+			dirty: FxHashSet::default(),
+		}
+	}
+	
+	fn get_chunk(&self, pos: Pos) -> Option<&Chunk> {
+		self.storage.get_chunk(&chunk_pos(pos))
+	}
+	
+	fn get_light(&self, pos: Pos, channel: LightChannel) -> Option<u8> {
+		self.get_chunk(pos).map(|chunk| chunk.light.get(chunk_index(pos), channel))
+	}
+	
+	fn set_light(&mut self, pos: Pos, channel: LightChannel, level: u8) {
+		let cpos = chunk_pos(pos);
+		if let Some(chunk) = self.storage.get_chunk_mut(&cpos) {
+			chunk.light.set(chunk_index(pos), channel, level);
+			self.dirty.insert(cpos);
+			
+			// Meshes of the neighbours read the border of this chunk too.
+			let csm = CHUNK_SIZE_MASK as isize;
+			let border = |v: isize| if v & csm == 0 { -1 } else if v & csm == csm { 1 } else { 0 };
+			let (bx, by, bz) = (border(pos.0), border(pos.1), border(pos.2));
+			for x in bx.min(0)..=bx.max(0) {
+				for y in by.min(0)..=by.max(0) {
+					for z in bz.min(0)..=bz.max(0) {
+						self.dirty.insert(ChunkCoord::new_from_chunk(cpos.x + x, cpos.y + y, cpos.z + z));
+					}
+				}
+			}
+		}
+	}
+	
+	/// Whether the block at the given position keeps light out; `None` if its chunk is not loaded.
+	fn is_opaque(&self, pos: Pos) -> Option<bool> {
+		let chunk = self.get_chunk(pos)?;
+		let block = chunk.blocks.get(chunk_index(pos));
+		Some(chunk.blockdef.get_attributes(&block).opaque)
+	}
+	
+	/// The light the block at the given position has on its own, regardless of its neighbours:
+	/// its emission for block light, and full sky light at the top of a chunk with nothing above it.
+	fn get_source(&self, pos: Pos, channel: LightChannel) -> u8 {
+		let chunk = match self.get_chunk(pos) {
+			Some(chunk) => chunk,
+			None => return 0
+		};
+		
+		let block = chunk.blocks.get(chunk_index(pos));
+		let attributes = chunk.blockdef.get_attributes(&block);
+		
+		match channel {
+			LightChannel::Block => attributes.emission,
+			LightChannel::Sky => {
+				let csm = CHUNK_SIZE_MASK as isize;
+				let top = pos.1 & csm == csm;
+				let above = ChunkCoord::new_from_chunk(chunk.pos.x, chunk.pos.y + 1, chunk.pos.z);
+				
+				if top && !attributes.opaque && !self.storage.contains_chunk(&above) {
+					MAX_LIGHT
+				} else {
+					0
+				}
+			}
+		}
+	}
+	
+	/// Sets the given block to the light it has on its own, and queues it for spreading.
+	fn add_source(&mut self, pos: Pos, channel: LightChannel) {
+		let source = self.get_source(pos, channel);
+		if source > self.get_light(pos, channel).unwrap_or(MAX_LIGHT) {
+			self.set_light(pos, channel, source);
+			self.add.push_back((pos, channel));
+		}
+	}
+	
+	/// Queues the given block for spreading its light, if it has any.
+	fn add_neighbour(&mut self, pos: Pos, channel: LightChannel) {
+		if self.get_light(pos, channel).unwrap_or(0) > 0 {
+			self.add.push_back((pos, channel));
+		}
+	}
+	
+	/// Takes away the light of the given block, and everything that was lit by it.
+	fn remove_light(&mut self, pos: Pos, channel: LightChannel) {
+		let level = self.get_light(pos, channel).unwrap_or(0);
+		if level > 0 {
+			self.set_light(pos, channel, 0);
+			self.remove.push_back((pos, channel, level));
+		}
+	}
+	
+	/// Empties the removal queue. Neighbours that are at least as bright as the removed light
+	/// have another source, so they are queued for spreading their light back.
+	fn run_removal(&mut self) {
+		while let Some((pos, channel, level)) = self.remove.pop_front() {
+			for direction in DIRECTIONS.iter() {
+				let npos = (pos.0 + direction.0, pos.1 + direction.1, pos.2 + direction.2);
+				let other = match self.get_light(npos, channel) {
+					Some(0) | None => continue,
+					Some(other) => other
+				};
+				
+				if other < level || spread(level, channel, *direction) == other {
+					self.set_light(npos, channel, 0);
+					self.remove.push_back((npos, channel, other));
+					self.add_source(npos, channel);
+				} else {
+					self.add.push_back((npos, channel));
+				}
+			}
+		}
+	}
+	
+	/// Empties the spreading queue.
+	fn run_add(&mut self) {
+		while let Some((pos, channel)) = self.add.pop_front() {
+			let level = match self.get_light(pos, channel) {
+				Some(0) | None => continue,
+				Some(level) => level
+			};
+			
+			for direction in DIRECTIONS.iter() {
+				let npos = (pos.0 + direction.0, pos.1 + direction.1, pos.2 + direction.2);
+				if self.is_opaque(npos) != Some(false) {
+					continue;
+				}
+				
+				let spread = spread(level, channel, *direction);
+				if spread > self.get_light(npos, channel).unwrap_or(MAX_LIGHT) {
+					self.set_light(npos, channel, spread);
+					self.add.push_back((npos, channel));
+				}
+			}
+		}
+	}
+	
+	fn finish(mut self) {
+		self.run_removal();
+		self.run_add();
+		
+		for cpos in self.dirty.iter() {
+			if let Some(chunk) = self.storage.get_chunk_mut(cpos) {
+				chunk.mark_dirty();
+			}
+		}
+	}
+}
+
+/// Iterates over the world positions of all blocks of the given chunk.
+fn chunk_blocks(cpos: ChunkCoord) -> impl Iterator<Item = Pos> {
+	let size = CHUNK_SIZE as isize;
+	let (ox, oy, oz) = (cpos.x * size, cpos.y * size, cpos.z * size);
+	(0..CHUNK_VOLUME as isize).map(move |i| (ox + i % size, oy + i / (size * size), oz + i / size % size))
+}
+
+/// Updates the light around a block that was just changed.
+pub fn update_block(storage: &mut ChunkStorage, x: isize, y: isize, z: isize) {
+	let mut update = LightUpdate::new(storage);
+	let pos = (x, y, z);
+	
+	for channel in LightChannel::ALL.iter() {
+		update.remove_light(pos, *channel);
+	}
+	
+	update.run_removal();
+	
+	for channel in LightChannel::ALL.iter() {
+		update.add_source(pos, *channel);
+		
+		// The block may let light through now.
+		for direction in DIRECTIONS.iter() {
+			update.add_neighbour((x + direction.0, y + direction.1, z + direction.2), *channel);
+		}
+	}
+	
+	update.finish();
+}
+
+/// Lights a chunk that was just inserted, along with everything its light reaches.
+///
+/// Any light the chunk still holds (from before it was removed, say) is thrown away first,
+/// as its sources would not spread into the neighbours otherwise.
+/// The chunk above the top of the chunk below is no longer open sky,
+/// so its sky light is taken away too.
+pub fn light_chunk(storage: &mut ChunkStorage, cpos: &ChunkCoord) {
+	if let Some(chunk) = storage.get_chunk_mut(cpos) {
+		chunk.light = LightStorage::new(CHUNK_VOLUME);
+	}
+	
+	let mut update = LightUpdate::new(storage);
+	let size = CHUNK_SIZE as isize;
+	
+	let below = ChunkCoord::new_from_chunk(cpos.x, cpos.y - 1, cpos.z);
+	let below_top = cpos.y * size - 1;
+	for pos in chunk_blocks(below).filter(|pos| pos.1 == below_top) {
+		if update.get_light(pos, LightChannel::Sky) == Some(MAX_LIGHT) {
+			update.remove_light(pos, LightChannel::Sky);
+		}
+	}
+	
+	update.run_removal();
+	
+	for pos in chunk_blocks(*cpos) {
+		for channel in LightChannel::ALL.iter() {
+			update.add_source(pos, *channel);
+		}
+	}
+	
+	// Light of the neighbours spreads into the chunk.
+	for pos in chunk_blocks(*cpos) {
+		for direction in DIRECTIONS.iter() {
+			let npos = (pos.0 + direction.0, pos.1 + direction.1, pos.2 + direction.2);
+			if chunk_pos(npos) != *cpos {
+				for channel in LightChannel::ALL.iter() {
+					update.add_neighbour(npos, *channel);
+				}
+			}
+		}
+	}
+	
+	update.finish();
+}
+
+/// Takes away the light a chunk that was just removed spread into its neighbours.
+///
+/// The top of the chunk below is open sky again afterwards.
+pub fn unlight_chunk(storage: &mut ChunkStorage, removed: &Chunk) {
+	let mut update = LightUpdate::new(storage);
+	let cpos = removed.pos;
+	let size = CHUNK_SIZE as isize;
+	
+	// Only the border of the chunk touches its neighbours.
+	let csm = CHUNK_SIZE_MASK as isize;
+	let border = |v: isize| v & csm == 0 || v & csm == csm;
+	for pos in chunk_blocks(cpos).filter(|pos| border(pos.0) || border(pos.1) || border(pos.2)) {
+		for channel in LightChannel::ALL.iter() {
+			let level = removed.light.get(chunk_index(pos), *channel);
+			if level > 0 {
+				update.remove.push_back((pos, *channel, level));
+			}
+		}
+	}
+	
+	update.run_removal();
+	
+	let below = ChunkCoord::new_from_chunk(cpos.x, cpos.y - 1, cpos.z);
+	let below_top = cpos.y * size - 1;
+	for pos in chunk_blocks(below).filter(|pos| pos.1 == below_top) {
+		update.add_source(pos, LightChannel::Sky);
+	}
+	
+	update.finish();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::universe::{define_universe, UniverseRef};
+	use super::super::coords::BlockCoord;
+	use super::super::BlockState;
+	
+	const CONFIG: &str = r#"
+		[blocks.stone]
+		[blocks.lamp]
+		emission = 14
+	"#;
+	
+	fn universe() -> UniverseRef {
+		let config: toml::Value = CONFIG.parse().unwrap();
+		define_universe(config.as_table().unwrap())
+	}
+	
+	fn state(blockdef: &UniverseRef, name: &str) -> BlockState {
+		blockdef.get_block_by_name_unchecked(name).get_default_state()
+	}
+	
+	/// An empty world of 2x2x2 chunks, from `(0, 0, 0)` to `(31, 31, 31)`.
+	fn world(blockdef: &UniverseRef) -> ChunkStorage {
+		let mut storage = ChunkStorage::new_empty(blockdef.clone(), &toml::value::Table::new()).unwrap();
+		
+		// Top first, so the sky light does not have to be pushed down again.
+		for y in (0..2).rev() {
+			for z in 0..2 {
+				for x in 0..2 {
+					storage.insert_chunk(Chunk::new_empty(blockdef.clone(), x, y, z));
+				}
+			}
+		}
+		storage
+	}
+	
+	/// Covers the whole world with a roof of stone at the given height.
+	fn roof(storage: &mut ChunkStorage, blockdef: &UniverseRef, y: isize) {
+		let stone = state(blockdef, "stone");
+		for x in 0..32 {
+			for z in 0..32 {
+				storage.set_block(&BlockCoord::new(x, y, z), stone);
+			}
+		}
+	}
+	
+	fn light(storage: &ChunkStorage, x: isize, y: isize, z: isize, channel: LightChannel) -> u8 {
+		storage.get_light(&BlockCoord::new(x, y, z), channel).unwrap()
+	}
+	
+	#[test]
+	fn sky_light_falls_and_spreads() {
+		let blockdef = universe();
+		let mut storage = world(&blockdef);
+		assert_eq!(light(&storage, 5, 0, 5, LightChannel::Sky), MAX_LIGHT);
+		
+		roof(&mut storage, &blockdef, 20);
+		assert_eq!(light(&storage, 5, 21, 5, LightChannel::Sky), MAX_LIGHT);
+		assert_eq!(light(&storage, 5, 20, 5, LightChannel::Sky), 0);
+		assert_eq!(light(&storage, 5, 3, 5, LightChannel::Sky), 0);
+		
+		// A hole lets a column of full sky light through, which gets weaker to the sides.
+		storage.set_block(&BlockCoord::new(10, 20, 10), state(&blockdef, "air"));
+		assert_eq!(light(&storage, 10, 0, 10, LightChannel::Sky), MAX_LIGHT);
+		assert_eq!(light(&storage, 11, 0, 10, LightChannel::Sky), MAX_LIGHT - 1);
+		assert_eq!(light(&storage, 13, 0, 12, LightChannel::Sky), MAX_LIGHT - 5);
+		
+		// Closing it again takes the light away.
+		storage.set_block(&BlockCoord::new(10, 20, 10), state(&blockdef, "stone"));
+		assert_eq!(light(&storage, 10, 0, 10, LightChannel::Sky), 0);
+		assert_eq!(light(&storage, 13, 0, 12, LightChannel::Sky), 0);
+	}
+	
+	#[test]
+	fn block_light_spreads_and_is_removed() {
+		let blockdef = universe();
+		let mut storage = world(&blockdef);
+		roof(&mut storage, &blockdef, 30);
+		
+		storage.set_block(&BlockCoord::new(8, 8, 8), state(&blockdef, "lamp"));
+		assert_eq!(light(&storage, 8, 8, 8, LightChannel::Block), 14);
+		assert_eq!(light(&storage, 5, 8, 8, LightChannel::Block), 11);
+		assert_eq!(light(&storage, 6, 9, 7, LightChannel::Block), 10);
+		
+		// Light goes around opaque blocks, not through them.
+		storage.set_block(&BlockCoord::new(9, 8, 8), state(&blockdef, "stone"));
+		assert_eq!(light(&storage, 9, 8, 8, LightChannel::Block), 0);
+		assert_eq!(light(&storage, 10, 8, 8, LightChannel::Block), 10);
+		
+		// Breaking the lamp leaves no light behind.
+		storage.set_block(&BlockCoord::new(8, 8, 8), state(&blockdef, "air"));
+		for x in 0..20 {
+			for y in 0..20 {
+				for z in 0..20 {
+					assert_eq!(light(&storage, x, y, z, LightChannel::Block), 0, "at {} {} {}", x, y, z);
+				}
+			}
+		}
+	}
+	
+	#[test]
+	fn light_crosses_chunk_borders() {
+		let blockdef = universe();
+		let mut storage = world(&blockdef);
+		roof(&mut storage, &blockdef, 30);
+		
+		// Right next to the corner of eight chunks.
+		storage.set_block(&BlockCoord::new(15, 15, 15), state(&blockdef, "lamp"));
+		assert_eq!(light(&storage, 16, 15, 15, LightChannel::Block), 13);
+		assert_eq!(light(&storage, 16, 16, 16, LightChannel::Block), 11);
+		assert_eq!(light(&storage, 20, 15, 15, LightChannel::Block), 9);
+		
+		storage.set_block(&BlockCoord::new(15, 15, 15), state(&blockdef, "air"));
+		assert_eq!(light(&storage, 16, 15, 15, LightChannel::Block), 0);
+		assert_eq!(light(&storage, 16, 16, 16, LightChannel::Block), 0);
+		
+		// Removing a chunk takes its light out of the neighbours; putting it back brings it back.
+		storage.set_block(&BlockCoord::new(20, 5, 5), state(&blockdef, "lamp"));
+		assert_eq!(light(&storage, 14, 5, 5, LightChannel::Block), 8);
+		
+		let removed = storage.remove_chunk(&ChunkCoord::new_from_chunk(1, 0, 0)).unwrap();
+		assert_eq!(light(&storage, 14, 5, 5, LightChannel::Block), 0);
+		assert_eq!(light(&storage, 15, 5, 5, LightChannel::Block), 0);
+		
+		storage.insert_chunk(removed);
+		assert_eq!(light(&storage, 14, 5, 5, LightChannel::Block), 8);
+	}
+	
+	#[test]
+	fn incremental_updates_match_a_fresh_world() {
+		let blockdef = universe();
+		let mut storage = world(&blockdef);
+		let states = [state(&blockdef, "air"), state(&blockdef, "stone"), state(&blockdef, "lamp"), state(&blockdef, "air")];
+		
+		// A fixed sequence of random edits.
+		let mut seed = 12345u64;
+		let mut random = |n: u64| {
+			seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+			((seed >> 33) % n) as isize
+		};
+		
+		for _ in 0..400 {
+			let pos = BlockCoord::new(random(32), random(32), random(32));
+			storage.set_block(&pos, states[random(4) as usize]);
+		}
+		
+		// The same blocks, lit from scratch.
+		let mut fresh = ChunkStorage::new_empty(blockdef.clone(), &toml::value::Table::new()).unwrap();
+		for y in (0..2).rev() {
+			for z in 0..2 {
+				for x in 0..2 {
+					let cpos = ChunkCoord::new_from_chunk(x, y, z);
+					let mut chunk = Chunk::new_empty(blockdef.clone(), x, y, z);
+					for location in 0..CHUNK_VOLUME {
+						let state = storage.get_chunk(&cpos).unwrap().blocks.get(location);
+						chunk.blocks.set(location, &state);
+					}
+					fresh.insert_chunk(chunk);
+				}
+			}
+		}
+		
+		for x in 0..32 {
+			for y in 0..32 {
+				for z in 0..32 {
+					for channel in LightChannel::ALL.iter() {
+						assert_eq!(
+							light(&storage, x, y, z, *channel),
+							light(&fresh, x, y, z, *channel),
+							"{:?} light at {} {} {}", channel, x, y, z
+						);
+					}
+				}
+			}
+		}
+	}
+}
@@ -7,8 +7,45 @@ pub use self::universe::Block;
 pub use self::universe::BlockId;
 pub use self::universe::BlockState;
 
+pub mod property;
+pub use self::property::BlockProperty;
+pub use self::property::PropertyValue;
+
+pub mod attributes;
+pub use self::attributes::BlockAttributes;
+pub use self::attributes::BlockBox;
+pub use self::attributes::BlockFace;
+pub use self::attributes::RenderLayer;
+
 pub mod storage;
 pub use self::storage::BlockStorage;
 
 pub mod coords;
 pub use self::coords::BlockCoord;
+
+pub mod raycast;
+pub use self::raycast::BlockRaycast;
+
+pub mod collision;
+pub use self::collision::Aabb;
+
+pub mod world;
+pub use self::world::Chunk;
+pub use self::world::ChunkCoord;
+pub use self::world::ChunkStorage;
+
+pub mod light;
+pub use self::light::LightChannel;
+pub use self::light::LightStorage;
+
+pub mod generator;
+pub use self::generator::WorldGenerator;
+
+pub mod streaming;
+pub use self::streaming::ChunkStreamer;
+
+pub mod snapshot;
+pub use self::snapshot::ChunkSnapshot;
+
+pub mod region;
+pub use self::region::RegionCoord;
@@ -0,0 +1,338 @@
+//! Typed properties of blocks, whose value-combinations make up the states of a block.
+//!
+//! Properties are declared in the `properties` table of a block in the `[blocks]` config:
+//!
+//! ```toml
+//! [blocks.furnace.properties]
+//! facing = ["north", "east", "south", "west"]
+//! lit = "bool"
+//! level = { min = 0, max = 15, default = 7 }
+//! ```
+//!
+//! An array declares an enumeration, `"bool"` a boolean and a table with `min`/`max` an integer range.
+//! The table forms (`{ values = [...] }`, `{ type = "bool" }`) accept an optional `default`.
+//! Names and enumeration values end up in state strings (`name[property=value,...]`),
+//! so they must not contain `[`, `]`, `,` or `=`, nor start or end with whitespace.
+
+/// The type of a property, along with the values it can take.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyKind {
+	Bool,
+	Int { min: i64, max: i64 },
+	Enum(Vec<String>),
+}
+
+/// A single value of a property.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PropertyValue {
+	Bool(bool),
+	Int(i64),
+	Enum(String),
+}
+
+impl std::fmt::Display for PropertyValue {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			PropertyValue::Bool(x) => write!(fmt, "{}", x),
+			PropertyValue::Int(x) => write!(fmt, "{}", x),
+			PropertyValue::Enum(x) => write!(fmt, "{}", x),
+		}
+	}
+}
+
+/// A named, typed property of a block.
+#[derive(Clone, Debug)]
+pub struct BlockProperty {
+	name: String,
+	kind: PropertyKind,
+	
+	/// Index of the default value.
+	default: usize,
+	
+	/// Multiplier of the value-index within the packed state data.
+	pub(super) stride: usize,
+}
+
+impl BlockProperty {
+	pub fn new(name: &str, kind: PropertyKind) -> BlockProperty {
+		BlockProperty {
+			name: name.to_string(),
+			kind,
+			default: 0,
+			stride: 1,
+		}
+	}
+	
+	/// Parses a property declaration from the block config.
+	pub fn from_config(name: &str, value: &toml::Value) -> Result<BlockProperty, String> {
+		Self::check_name("the name", name)?;
+		
+		let (kind, default) = match value {
+			toml::Value::String(kind) if kind == "bool" => (PropertyKind::Bool, None),
+			toml::Value::Array(values) => (PropertyKind::Enum(Self::parse_values(values)?), None),
+			toml::Value::Table(table) => {
+				let kind = if let Some(values) = table.get("values") {
+					match values.as_array() {
+						Some(values) => PropertyKind::Enum(Self::parse_values(values)?),
+						None => return Err("'values' must be an array".to_string())
+					}
+				} else if table.get("type").and_then(|t| t.as_str()) == Some("bool") {
+					PropertyKind::Bool
+				} else {
+					let min = table.get("min").and_then(|v| v.as_integer());
+					let max = table.get("max").and_then(|v| v.as_integer());
+					match (min, max) {
+						(Some(min), Some(max)) if min > max => return Err("'min' must not be greater than 'max'".to_string()),
+						// A single property can never have more values than a block has states.
+						(Some(min), Some(max)) if max as i128 - min as i128 > u16::max_value() as i128 => {
+							return Err(format!("the range from 'min' to 'max' must not have more than {} values", u16::max_value() as usize + 1))
+						},
+						(Some(min), Some(max)) => PropertyKind::Int { min, max },
+						_ => return Err("expected 'values', 'type = \"bool\"' or 'min' and 'max'".to_string())
+					}
+				};
+				(kind, table.get("default"))
+			},
+			_ => return Err("expected an array, \"bool\" or a table".to_string())
+		};
+		
+		let mut property = BlockProperty::new(name, kind);
+		
+		if let Some(default) = default {
+			let default = property.parse_value(&match default {
+				toml::Value::String(x) => x.clone(),
+				other => other.to_string()
+			}).ok_or_else(|| format!("invalid default value '{}'", default))?;
+			property.default = property.index_of(&default).unwrap();
+		}
+		
+		Ok(property)
+	}
+	
+	fn parse_values(values: &[toml::Value]) -> Result<Vec<String>, String> {
+		if values.is_empty() {
+			return Err("an enumeration needs at least one value".to_string());
+		}
+		
+		let mut names: Vec<String> = vec![];
+		for value in values {
+			let value = value.as_str().ok_or("enumeration values must be strings")?;
+			Self::check_name("value", value)?;
+			if names.iter().any(|v| v == value) {
+				return Err(format!("duplicate value '{}'", value));
+			}
+			names.push(value.to_string());
+		}
+		Ok(names)
+	}
+	
+	/// Checks that a name or value can be written into a state string (`name[property=value,...]`)
+	/// and parsed back out of it.
+	fn check_name(what: &str, name: &str) -> Result<(), String> {
+		if name.is_empty() {
+			return Err(format!("{} must not be empty", what));
+		}
+		if name.trim() != name {
+			return Err(format!("{} '{}' must not start or end with whitespace", what, name));
+		}
+		if name.contains(|c| "[],=".contains(c)) {
+			return Err(format!("{} '{}' must not contain '[', ']', ',' or '='", what, name));
+		}
+		Ok(())
+	}
+	
+	pub fn get_name(&self) -> &str {
+		self.name.as_str()
+	}
+	
+	pub fn get_kind(&self) -> &PropertyKind {
+		&self.kind
+	}
+	
+	pub fn get_default(&self) -> PropertyValue {
+		self.value_at(self.default)
+	}
+	
+	pub(super) fn get_default_index(&self) -> usize {
+		self.default
+	}
+	
+	/// The number of distinct values this property can take.
+	pub fn get_value_count(&self) -> usize {
+		match &self.kind {
+			PropertyKind::Bool => 2,
+			PropertyKind::Int { min, max } => (max - min + 1) as usize,
+			PropertyKind::Enum(values) => values.len(),
+		}
+	}
+	
+	/// Returns the value with the given index.
+	pub fn value_at(&self, index: usize) -> PropertyValue {
+		match &self.kind {
+			PropertyKind::Bool => PropertyValue::Bool(index != 0),
+			PropertyKind::Int { min, .. } => PropertyValue::Int(min + index as i64),
+			PropertyKind::Enum(values) => PropertyValue::Enum(values[index].clone()),
+		}
+	}
+	
+	/// Returns the index of the given value, if it is valid for this property.
+	pub fn index_of(&self, value: &PropertyValue) -> Option<usize> {
+		match (&self.kind, value) {
+			(PropertyKind::Bool, PropertyValue::Bool(x)) => Some(*x as usize),
+			(PropertyKind::Int { min, max }, PropertyValue::Int(x)) => {
+				if x < min || x > max {
+					None
+				} else {
+					Some((x - min) as usize)
+				}
+			},
+			(PropertyKind::Enum(values), PropertyValue::Enum(x)) => values.iter().position(|v| v == x),
+			_ => None
+		}
+	}
+	
+	/// Parses a value of this property from its string form.
+	pub fn parse_value(&self, value: &str) -> Option<PropertyValue> {
+		let value = match &self.kind {
+			PropertyKind::Bool => PropertyValue::Bool(value.parse::<bool>().ok()?),
+			PropertyKind::Int { .. } => PropertyValue::Int(value.parse::<i64>().ok()?),
+			PropertyKind::Enum(_) => PropertyValue::Enum(value.to_string()),
+		};
+		
+		self.index_of(&value).map(|_| value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::universe::{define_universe, define_universe_with_ids, UniverseError, UniverseRef};
+	use super::super::BlockState;
+	
+	/// The properties are laid out in the order of their names: `facing`, `level`, `lit`.
+	const CONFIG: &str = r#"
+		[blocks.stone]
+		[blocks.furnace.properties]
+		facing = ["north", "east", "south", "west"]
+		lit = "bool"
+		level = { min = 0, max = 2, default = 1 }
+	"#;
+	
+	fn universe() -> UniverseRef {
+		let config: toml::Value = CONFIG.parse().unwrap();
+		define_universe(config.as_table().unwrap())
+	}
+	
+	fn define(config: &str) -> Result<UniverseRef, UniverseError> {
+		let config: toml::Value = config.parse().unwrap();
+		define_universe_with_ids(config.as_table().unwrap(), None)
+	}
+	
+	fn facing(name: &str) -> PropertyValue {
+		PropertyValue::Enum(name.to_string())
+	}
+	
+	#[test]
+	fn states_are_laid_out_mixed_radix() {
+		let blockdef = universe();
+		let furnace = blockdef.get_block_by_name_unchecked("furnace");
+		assert_eq!(furnace.get_state_count(), 4 * 3 * 2);
+		
+		let facings = ["north", "east", "south", "west"];
+		for state in furnace.get_states() {
+			let data = state.data as usize;
+			assert_eq!(furnace.get_property(&state, "facing"), Some(facing(facings[data % 4])));
+			assert_eq!(furnace.get_property(&state, "level"), Some(PropertyValue::Int((data / 4 % 3) as i64)));
+			assert_eq!(furnace.get_property(&state, "lit"), Some(PropertyValue::Bool(data / 12 == 1)));
+		}
+		
+		// North, level 1, not lit.
+		assert_eq!(furnace.get_default_state().data, 4);
+	}
+	
+	#[test]
+	fn with_property_changes_only_that_property() {
+		let blockdef = universe();
+		let furnace = blockdef.get_block_by_name_unchecked("furnace");
+		
+		for state in furnace.get_states() {
+			for property in furnace.get_properties() {
+				for index in 0..property.get_value_count() {
+					let value = property.value_at(index);
+					let changed = furnace.with_property(&state, property.get_name(), &value).unwrap();
+					
+					for other in furnace.get_properties() {
+						let expected = if other.get_name() == property.get_name() {
+							value.clone()
+						} else {
+							furnace.get_property(&state, other.get_name()).unwrap()
+						};
+						assert_eq!(furnace.get_property(&changed, other.get_name()), Some(expected));
+					}
+				}
+			}
+		}
+		
+		let state = furnace.get_default_state();
+		assert_eq!(furnace.with_property(&state, "level", &PropertyValue::Int(3)), None);
+		assert_eq!(furnace.with_property(&state, "facing", &facing("up")), None);
+		assert_eq!(furnace.with_property(&state, "lit", &facing("north")), None);
+		assert_eq!(furnace.with_property(&state, "color", &facing("red")), None);
+		
+		// States of other blocks are not touched.
+		let stone = blockdef.get_block_by_name_unchecked("stone").get_default_state();
+		assert_eq!(furnace.with_property(&stone, "lit", &PropertyValue::Bool(true)), None);
+		assert_eq!(furnace.get_property(&stone, "lit"), None);
+	}
+	
+	#[test]
+	fn states_round_trip_through_strings() {
+		let blockdef = universe();
+		let furnace = blockdef.get_block_by_name_unchecked("furnace");
+		
+		for state in furnace.get_states() {
+			let string = blockdef.state_to_string(&state);
+			assert_eq!(blockdef.get_state_by_string(&string), Some(state), "{}", string);
+		}
+		
+		assert_eq!(blockdef.state_to_string(&furnace.get_default_state()), "furnace[facing=north,level=1,lit=false]");
+		assert_eq!(blockdef.get_state_by_string("furnace[lit=true]"), Some(BlockState { id: furnace.get_id(), data: 16 }));
+		assert_eq!(blockdef.get_state_by_string("furnace[lit=maybe]"), None);
+		assert_eq!(blockdef.get_state_by_string("furnace[lit=true"), None);
+	}
+	
+	#[test]
+	fn too_many_states_are_rejected() {
+		// 256 * 256 states just fit...
+		assert!(define(r#"
+			[blocks.big.properties]
+			a = { min = 0, max = 255 }
+			b = { min = 0, max = 255 }
+		"#).is_ok());
+		
+		// ...one more value does not.
+		match define(r#"
+			[blocks.big.properties]
+			a = { min = 0, max = 255 }
+			b = { min = 0, max = 256 }
+		"#) {
+			Err(UniverseError::TooManyStates { ref block }) if block == "big" => (),
+			_ => panic!("expected too many states")
+		}
+	}
+	
+	#[test]
+	fn names_that_break_state_strings_are_rejected() {
+		let is_invalid = |config: String| match define(&config) {
+			Err(UniverseError::InvalidProperty { .. }) => true,
+			_ => false
+		};
+		
+		for name in ["a,b", "a]", "[a", "a=b", " a", "a ", ""].iter() {
+			assert!(is_invalid(format!("[blocks.x.properties]\np = [\"{}\"]", name)), "value '{}'", name);
+			assert!(is_invalid(format!("[blocks.x.properties]\n\"{}\" = \"bool\"", name)), "name '{}'", name);
+		}
+		
+		assert!(!is_invalid("[blocks.x.properties]\np = [\"a b\", \"a-b\"]".to_string()));
+	}
+}
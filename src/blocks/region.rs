@@ -0,0 +1,596 @@
+//! Binary on-disk format for saving and loading chunks, grouped into regions.
+//!
+//! A region is a cube of `REGION_SIZE`³ chunks stored in a single file.
+//! All values are little-endian; the layout (version 2) is:
+//!
+//! ```text
+//! magic:    [u8; 4] = "TCRG"
+//! version:  u16
+//! position: i32 x3            (region coordinates)
+//! palette:  u32 count, then per entry: u16 length + UTF-8 block state
+//! chunks:   u32 count, then per chunk:
+//!           i32 x3            (chunk coordinates)
+//!           u32 length + zlib-compressed `CHUNK_VOLUME` x u16 palette indices
+//! ```
+//!
+//! Block states are stored as strings (`name[property=value,...]`), not by id,
+//! so files stay valid when the registration order of the universe changes.
+//! Version 1 files, which only stored block names, are still readable.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use rustc_hash::FxHashMap;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use super::universe::UniverseRef;
+use super::BlockState;
+use super::world::{Chunk, ChunkCoord, CHUNK_VOLUME};
+
+/// The magic bytes every region file starts with.
+const REGION_MAGIC: &[u8; 4] = b"TCRG";
+
+/// The current version of the region file format.
+pub const REGION_VERSION: u16 = 2;
+
+pub const REGION_SIZE: isize = 8;
+pub const REGION_SIZE_SHIFT: isize = 3;
+
+/// The file extension used for region files.
+pub const REGION_EXTENSION: &str = "tcr";
+
+/// The most distinct block states a region can hold, as they are indexed by `u16`.
+const MAX_PALETTE_LEN: usize = std::u16::MAX as usize + 1;
+
+/// The length of the uncompressed block data of a chunk.
+const RAW_CHUNK_LEN: usize = CHUNK_VOLUME * 2;
+
+/// The most bytes the block data of a chunk can take up once compressed;
+/// the bound of zlib's `compressBound` of that length.
+const MAX_PAYLOAD_LEN: usize = RAW_CHUNK_LEN + (RAW_CHUNK_LEN >> 12) + (RAW_CHUNK_LEN >> 14) + (RAW_CHUNK_LEN >> 25) + 13;
+
+#[derive(Debug, Fail)]
+pub enum RegionError {
+	#[fail(display = "I/O error")]
+	Io(#[cause] io::Error),
+	
+	#[fail(display = "File is not a region file")]
+	InvalidMagic,
+	
+	#[fail(display = "Unsupported region file version {}", version)]
+	UnsupportedVersion { version: u16 },
+	
+	#[fail(display = "Corrupt region file: {}", reason)]
+	Corrupt { reason: String },
+	
+	#[fail(display = "Region {} has more than {} distinct block states", pos, limit)]
+	PaletteFull { pos: RegionCoord, limit: usize },
+}
+
+impl From<io::Error> for RegionError {
+	fn from(other: io::Error) -> Self {
+		RegionError::Io(other)
+	}
+}
+
+/// The position of a region, in region-space.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub struct RegionCoord {
+	pub x: isize,
+	pub y: isize,
+	pub z: isize,
+}
+
+impl RegionCoord {
+	pub fn new_from_chunk(pos: &ChunkCoord) -> RegionCoord {
+		RegionCoord {
+			x: pos.x >> REGION_SIZE_SHIFT,
+			y: pos.y >> REGION_SIZE_SHIFT,
+			z: pos.z >> REGION_SIZE_SHIFT,
+		}
+	}
+	
+	/// The name of the file this region is stored in.
+	pub fn get_file_name(&self) -> String {
+		format!("r.{}.{}.{}.{}", self.x, self.y, self.z, REGION_EXTENSION)
+	}
+	
+	/// The full path of the file this region is stored in, given a world directory.
+	pub fn get_file_path(&self, dir: &Path) -> PathBuf {
+		dir.join(self.get_file_name())
+	}
+}
+
+impl std::fmt::Display for RegionCoord {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(fmt, "[x: {}, y: {}, z: {}]",
+			self.x,
+			self.y,
+			self.z,
+		)
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Writes the given chunks, which must all belong to the given region, into a region file.
+///
+/// The file is written to a temporary location first and then moved into place,
+/// so an interrupted save never leaves a truncated region behind.
+pub fn write_region(dir: &Path, pos: &RegionCoord, chunks: &[&Chunk]) -> Result<(), RegionError> {
+	let path = pos.get_file_path(dir);
+	let temp = path.with_extension(format!("{}.tmp", REGION_EXTENSION));
+	
+	trace!("Writing region {} with {} chunks...", pos, chunks.len());
+	
+	let mut buffer: Vec<u8> = vec![];
+	write_region_to(&mut buffer, pos, chunks)?;
+	
+	fs::create_dir_all(dir)?;
+	fs::write(&temp, &buffer)?;
+	fs::rename(&temp, &path)?;
+	Ok(())
+}
+
+/// Reads all chunks from the region file at the given path.
+pub fn read_region(path: &Path, blockdef: &UniverseRef) -> Result<(RegionCoord, Vec<Chunk>), RegionError> {
+	trace!("Reading region file: {}", path.to_str().unwrap_or("ERROR"));
+	let mut file = io::BufReader::new(fs::File::open(path)?);
+	read_region_from(&mut file, blockdef)
+}
+
+/// Reads the chunks of the given region from its file in the given directory;
+/// no chunks at all if the region has no file yet.
+pub fn read_region_chunks(dir: &Path, pos: &RegionCoord, blockdef: &UniverseRef) -> Result<Vec<Chunk>, RegionError> {
+	let path = pos.get_file_path(dir);
+	if ! path.is_file() {
+		return Ok(vec![]);
+	}
+	
+	Ok(read_region(&path, blockdef)?.1)
+}
+
+/// Writes the given chunks into the region files of the given directory,
+/// keeping the chunks already stored in those files that are not being replaced.
+/// Returns the number of region files written.
+pub fn save_chunks(dir: &Path, chunks: &[&Chunk], blockdef: &UniverseRef) -> Result<usize, RegionError> {
+	let mut regions: FxHashMap<RegionCoord, Vec<&Chunk>> = FxHashMap::default();
+	for chunk in chunks {
+		regions.entry(RegionCoord::new_from_chunk(&chunk.pos))
+			.or_insert_with(Vec::new)
+			.push(chunk);
+	}
+	
+	for (pos, chunks) in regions.iter() {
+		let stored = read_region_chunks(dir, pos, blockdef)?;
+		
+		let mut merged = chunks.clone();
+		merged.extend(stored.iter().filter(|old| !chunks.iter().any(|chunk| chunk.pos == old.pos)));
+		
+		write_region(dir, pos, &merged)?;
+	}
+	
+	Ok(regions.len())
+}
+
+/// Serializes the given chunks as a region into a writer.
+pub fn write_region_to<W: Write>(out: &mut W, pos: &RegionCoord, chunks: &[&Chunk]) -> Result<(), RegionError> {
+	// Build the palette shared by all chunks in the region...
+	let mut palette: Vec<BlockState> = vec![];
+	let mut palette_index: FxHashMap<BlockState, u16> = FxHashMap::default();
+	let mut payloads: Vec<Vec<u8>> = Vec::with_capacity(chunks.len());
+	
+	for chunk in chunks {
+		if RegionCoord::new_from_chunk(&chunk.pos) != *pos {
+			return Err(RegionError::Corrupt {
+				reason: format!("Chunk {} does not belong to region {}", chunk.pos, pos)
+			});
+		}
+		
+		let mut raw: Vec<u8> = Vec::with_capacity(RAW_CHUNK_LEN);
+		for location in 0..CHUNK_VOLUME {
+			let state = chunk.blocks.get(location);
+			let index = match palette_index.get(&state) {
+				Some(index) => *index,
+				None => {
+					// The indices are stored as `u16`.
+					if palette.len() >= MAX_PALETTE_LEN {
+						return Err(RegionError::PaletteFull {
+							pos: *pos,
+							limit: MAX_PALETTE_LEN
+						});
+					}
+					
+					let index = palette.len() as u16;
+					palette.push(state);
+					palette_index.insert(state, index);
+					index
+				}
+			};
+			raw.extend_from_slice(&index.to_le_bytes());
+		}
+		
+		let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+		encoder.write_all(&raw)?;
+		payloads.push(encoder.finish()?);
+	}
+	
+	// ...then write everything out.
+	out.write_all(REGION_MAGIC)?;
+	write_u16(out, REGION_VERSION)?;
+	write_i32(out, pos.x as i32)?;
+	write_i32(out, pos.y as i32)?;
+	write_i32(out, pos.z as i32)?;
+	
+	let blockdef = match chunks.first() {
+		Some(chunk) => chunk.blockdef.clone(),
+		None => {
+			write_u32(out, 0)?;
+			write_u32(out, 0)?;
+			return Ok(());
+		}
+	};
+	
+	write_u32(out, palette.len() as u32)?;
+	for state in palette.iter() {
+		let name = blockdef.state_to_string(state);
+		let name = name.as_bytes();
+		write_u16(out, name.len() as u16)?;
+		out.write_all(name)?;
+	}
+	
+	write_u32(out, chunks.len() as u32)?;
+	for (chunk, payload) in chunks.iter().zip(payloads.iter()) {
+		write_i32(out, chunk.pos.x as i32)?;
+		write_i32(out, chunk.pos.y as i32)?;
+		write_i32(out, chunk.pos.z as i32)?;
+		write_u32(out, payload.len() as u32)?;
+		out.write_all(payload)?;
+	}
+	
+	Ok(())
+}
+
+/// Deserializes a region from a reader, resolving block names against the given universe.
+///
+/// Names that do not exist in the universe are replaced with air.
+pub fn read_region_from<R: Read>(input: &mut R, blockdef: &UniverseRef) -> Result<(RegionCoord, Vec<Chunk>), RegionError> {
+	let mut magic = [0u8; 4];
+	input.read_exact(&mut magic)?;
+	if &magic != REGION_MAGIC {
+		return Err(RegionError::InvalidMagic);
+	}
+	
+	let version = read_u16(input)?;
+	if version == 0 || version > REGION_VERSION {
+		return Err(RegionError::UnsupportedVersion { version });
+	}
+	
+	let pos = RegionCoord {
+		x: read_i32(input)? as isize,
+		y: read_i32(input)? as isize,
+		z: read_i32(input)? as isize,
+	};
+	
+	let air = blockdef
+		.get_block_by_name_unchecked("air")
+		.get_default_state();
+	
+	let palette_len = read_u32(input)? as usize;
+	if palette_len > MAX_PALETTE_LEN {
+		return Err(RegionError::Corrupt {
+			reason: format!("Palette has {} entries", palette_len)
+		});
+	}
+	
+	let mut palette: Vec<BlockState> = Vec::with_capacity(palette_len);
+	for _ in 0..palette_len {
+		let name_len = read_u16(input)? as usize;
+		let mut name = vec![0u8; name_len];
+		input.read_exact(&mut name)?;
+		
+		let name = String::from_utf8(name)
+			.map_err(|_| RegionError::Corrupt { reason: "Block state is not valid UTF-8".to_string() })?;
+		
+		palette.push(match blockdef.get_state_by_string(&name) {
+			Some(state) => state,
+			None => {
+				// Fall back to the default state if only the properties changed...
+				let block_name = name.split('[').next().unwrap_or("");
+				match blockdef.get_block_by_name(block_name) {
+					Some(block) => {
+						warn!("Region {} contains unknown state '{}', using the default state.", pos, name);
+						block.get_default_state()
+					},
+					None => {
+						warn!("Region {} contains unknown block '{}', replacing it with air.", pos, name);
+						air
+					}
+				}
+			}
+		});
+	}
+	
+	let chunk_count = read_u32(input)? as usize;
+	let mut chunks: Vec<Chunk> = Vec::with_capacity(chunk_count);
+	for _ in 0..chunk_count {
+		let cx = read_i32(input)? as isize;
+		let cy = read_i32(input)? as isize;
+		let cz = read_i32(input)? as isize;
+		
+		let payload_len = read_u32(input)? as usize;
+		if payload_len > MAX_PAYLOAD_LEN {
+			return Err(RegionError::Corrupt {
+				reason: format!("Chunk [{}, {}, {}] has {} bytes of compressed block data", cx, cy, cz, payload_len)
+			});
+		}
+		
+		let mut payload = vec![0u8; payload_len];
+		input.read_exact(&mut payload)?;
+		
+		let chunk_pos = ChunkCoord::new_from_chunk(cx, cy, cz);
+		if RegionCoord::new_from_chunk(&chunk_pos) != pos {
+			warn!("Region {} contains chunk {}, which belongs to another region; skipping it.", pos, chunk_pos);
+			continue;
+		}
+		
+		// One byte more than needed, so too much data is noticed without inflating all of it.
+		let mut raw: Vec<u8> = Vec::with_capacity(RAW_CHUNK_LEN + 1);
+		ZlibDecoder::new(payload.as_slice())
+			.take(RAW_CHUNK_LEN as u64 + 1)
+			.read_to_end(&mut raw)
+			.map_err(|e| RegionError::Corrupt {
+				reason: format!("Chunk [{}, {}, {}] has invalid block data: {}", cx, cy, cz, e)
+			})?;
+		
+		if raw.len() != RAW_CHUNK_LEN {
+			return Err(RegionError::Corrupt {
+				reason: format!("Chunk [{}, {}, {}] has {} bytes of block data", cx, cy, cz, raw.len())
+			});
+		}
+		
+		let mut chunk = Chunk::new_empty(blockdef.clone(), cx, cy, cz);
+		for location in 0..CHUNK_VOLUME {
+			let index = u16::from_le_bytes([raw[location * 2], raw[location * 2 + 1]]) as usize;
+			let state = palette.get(index).ok_or_else(|| RegionError::Corrupt {
+				reason: format!("Palette index {} out of bounds", index)
+			})?;
+			chunk.blocks.set(location, state);
+		}
+		
+		chunks.push(chunk);
+	}
+	
+	Ok((pos, chunks))
+}
+
+/// Returns `true` if the given path looks like a region file.
+pub fn is_region_file(path: &Path) -> bool {
+	path.extension().map_or(false, |ext| ext == REGION_EXTENSION)
+}
+
+fn write_u16<W: Write>(out: &mut W, value: u16) -> io::Result<()> {
+	out.write_all(&value.to_le_bytes())
+}
+
+fn write_u32<W: Write>(out: &mut W, value: u32) -> io::Result<()> {
+	out.write_all(&value.to_le_bytes())
+}
+
+fn write_i32<W: Write>(out: &mut W, value: i32) -> io::Result<()> {
+	out.write_all(&value.to_le_bytes())
+}
+
+fn read_u16<R: Read>(input: &mut R) -> io::Result<u16> {
+	let mut buf = [0u8; 2];
+	input.read_exact(&mut buf)?;
+	Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+	let mut buf = [0u8; 4];
+	input.read_exact(&mut buf)?;
+	Ok(u32::from_le_bytes(buf))
+}
+
+fn read_i32<R: Read>(input: &mut R) -> io::Result<i32> {
+	let mut buf = [0u8; 4];
+	input.read_exact(&mut buf)?;
+	Ok(i32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::universe::define_universe;
+	
+	const CONFIG: &str = r#"
+		[blocks.stone]
+		[blocks.slab.properties]
+		half = ["bottom", "top"]
+		wet = "bool"
+	"#;
+	
+	fn universe(config: &str) -> UniverseRef {
+		let config: toml::Value = config.parse().unwrap();
+		define_universe(config.as_table().unwrap())
+	}
+	
+	fn state(blockdef: &UniverseRef, string: &str) -> BlockState {
+		blockdef.get_state_by_string(string).unwrap()
+	}
+	
+	/// A chunk with the given states at the start of its block data, and air after them.
+	fn chunk(blockdef: &UniverseRef, x: isize, y: isize, z: isize, states: &[&str]) -> Chunk {
+		let mut chunk = Chunk::new_empty(blockdef.clone(), x, y, z);
+		for (location, string) in states.iter().enumerate() {
+			chunk.blocks.set(location, &state(blockdef, string));
+		}
+		chunk
+	}
+	
+	fn blocks(chunk: &Chunk) -> Vec<BlockState> {
+		(0..CHUNK_VOLUME).map(|location| chunk.blocks.get(location)).collect()
+	}
+	
+	fn write(chunks: &[&Chunk]) -> Vec<u8> {
+		let mut buffer = vec![];
+		write_region_to(&mut buffer, &RegionCoord { x: 0, y: 0, z: 0 }, chunks).unwrap();
+		buffer
+	}
+	
+	fn read(bytes: &[u8], blockdef: &UniverseRef) -> Result<(RegionCoord, Vec<Chunk>), RegionError> {
+		read_region_from(&mut &bytes[..], blockdef)
+	}
+	
+	/// An empty directory to save into, unique to the test and the process.
+	fn save_dir(name: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("tcge-region-{}-{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		dir
+	}
+	
+	#[test]
+	fn chunks_survive_a_round_trip() {
+		let blockdef = universe(CONFIG);
+		let states = ["stone", "slab[half=top,wet=false]", "slab[half=bottom,wet=true]", "air", "slab[half=top,wet=true]"];
+		let written = [chunk(&blockdef, 1, 2, 3, &states), chunk(&blockdef, 7, 0, 0, &[])];
+		
+		let (pos, read) = read(&write(&[&written[0], &written[1]]), &blockdef).unwrap();
+		assert_eq!(pos, RegionCoord { x: 0, y: 0, z: 0 });
+		assert_eq!(read.len(), 2);
+		
+		for (written, read) in written.iter().zip(read.iter()) {
+			assert!(read.pos == written.pos);
+			assert_eq!(blocks(read), blocks(written));
+		}
+	}
+	
+	#[test]
+	fn saving_merges_with_the_existing_file() {
+		let blockdef = universe(CONFIG);
+		let dir = save_dir("merge");
+		
+		let first = chunk(&blockdef, 0, 0, 0, &["stone"]);
+		let second = chunk(&blockdef, 1, 0, 0, &["slab[half=top,wet=false]"]);
+		assert_eq!(save_chunks(&dir, &[&first], &blockdef).unwrap(), 1);
+		assert_eq!(save_chunks(&dir, &[&second], &blockdef).unwrap(), 1);
+		
+		// Saving a chunk again replaces it, and keeps the others.
+		let replaced = chunk(&blockdef, 0, 0, 0, &["air", "stone"]);
+		assert_eq!(save_chunks(&dir, &[&replaced], &blockdef).unwrap(), 1);
+		
+		let mut chunks = read_region_chunks(&dir, &RegionCoord { x: 0, y: 0, z: 0 }, &blockdef).unwrap();
+		chunks.sort_by_key(|chunk| chunk.pos.x);
+		assert_eq!(chunks.len(), 2);
+		assert_eq!(blocks(&chunks[0]), blocks(&replaced));
+		assert_eq!(blocks(&chunks[1]), blocks(&second));
+		
+		let _ = fs::remove_dir_all(&dir);
+	}
+	
+	#[test]
+	fn unknown_states_fall_back() {
+		let bytes = write(&[&chunk(&universe(CONFIG), 0, 0, 0, &["stone", "slab[half=top,wet=true]"])]);
+		
+		// Without stone, and without the `wet` property of slabs.
+		let blockdef = universe(r#"
+			[blocks.slab.properties]
+			half = ["bottom", "top"]
+		"#);
+		let (_, chunks) = read(&bytes, &blockdef).unwrap();
+		
+		assert_eq!(chunks[0].blocks.get(0), state(&blockdef, "air"));
+		assert_eq!(chunks[0].blocks.get(1), state(&blockdef, "slab[half=bottom]"));
+	}
+	
+	#[test]
+	fn version_1_files_are_readable() {
+		// Version 1 only differs in storing plain block names, which states without properties are written as.
+		let blockdef = universe("[blocks.stone]");
+		let written = chunk(&blockdef, 0, 0, 0, &["stone", "air", "stone"]);
+		let mut bytes = write(&[&written]);
+		bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+		
+		let (_, chunks) = read(&bytes, &blockdef).unwrap();
+		assert_eq!(blocks(&chunks[0]), blocks(&written));
+	}
+	
+	#[test]
+	fn corrupt_files_are_rejected() {
+		let blockdef = universe(CONFIG);
+		let bytes = write(&[&chunk(&blockdef, 0, 0, 0, &["stone"])]);
+		
+		let is_corrupt = |bytes: &[u8]| match read(bytes, &blockdef) {
+			Err(RegionError::Corrupt { .. }) => true,
+			_ => false
+		};
+		
+		/// The start of a region file with the given palette and a single chunk of the given payload length.
+		fn header(palette: &[&str], payload_len: u32) -> Vec<u8> {
+			let mut out = vec![];
+			out.extend_from_slice(REGION_MAGIC);
+			write_u16(&mut out, REGION_VERSION).unwrap();
+			for _ in 0..3 {
+				write_i32(&mut out, 0).unwrap();
+			}
+			write_u32(&mut out, palette.len() as u32).unwrap();
+			for name in palette {
+				write_u16(&mut out, name.len() as u16).unwrap();
+				out.extend_from_slice(name.as_bytes());
+			}
+			write_u32(&mut out, 1).unwrap();
+			for _ in 0..3 {
+				write_i32(&mut out, 0).unwrap();
+			}
+			write_u32(&mut out, payload_len).unwrap();
+			out
+		}
+		
+		fn compress(raw: &[u8]) -> Vec<u8> {
+			let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+			encoder.write_all(raw).unwrap();
+			encoder.finish().unwrap()
+		}
+		
+		// Not a region file, or one from the future.
+		let mut other = bytes.clone();
+		other[0] = b'X';
+		assert!(match read(&other, &blockdef) { Err(RegionError::InvalidMagic) => true, _ => false });
+		
+		let mut other = bytes.clone();
+		other[4..6].copy_from_slice(&(REGION_VERSION + 1).to_le_bytes());
+		assert!(match read(&other, &blockdef) { Err(RegionError::UnsupportedVersion { version }) => version == REGION_VERSION + 1, _ => false });
+		
+		// Cut off anywhere.
+		for len in 0..bytes.len() {
+			assert!(read(&bytes[..len], &blockdef).is_err(), "truncated to {} bytes", len);
+		}
+		
+		// Lengths too large to be real are rejected before anything is allocated for them.
+		let mut other = header(&[], 0);
+		other[18..22].copy_from_slice(&std::u32::MAX.to_le_bytes());
+		assert!(is_corrupt(&other));
+		assert!(is_corrupt(&header(&["stone"], std::u32::MAX)));
+		
+		// Block data that is not zlib, or not the size of a chunk.
+		let mut other = header(&["stone"], 4);
+		other.extend_from_slice(b"junk");
+		assert!(is_corrupt(&other));
+		
+		for len in [RAW_CHUNK_LEN - 2, RAW_CHUNK_LEN + 2, RAW_CHUNK_LEN * 16].iter() {
+			let payload = compress(&vec![0u8; *len]);
+			let mut other = header(&["stone"], payload.len() as u32);
+			other.extend_from_slice(&payload);
+			assert!(is_corrupt(&other), "{} bytes of block data", len);
+		}
+		
+		// Indices past the end of the palette.
+		let payload = compress(&[1u8, 0].repeat(CHUNK_VOLUME));
+		let mut other = header(&["stone"], payload.len() as u32);
+		other.extend_from_slice(&payload);
+		assert!(is_corrupt(&other));
+	}
+}
@@ -0,0 +1,118 @@
+//! Immutable copies of a chunk together with the blocks bordering it.
+
+use super::BlockState;
+use super::light::{LightChannel, LightStorage, MAX_LIGHT};
+use super::world::{ChunkCoord, ChunkStorage, CHUNK_SIZE, CHUNK_SIZE_MASK, CHUNK_SLICE};
+
+/// The width of a snapshot, including the border on both sides.
+pub const SNAPSHOT_SIZE: usize = CHUNK_SIZE + 2;
+pub const SNAPSHOT_VOLUME: usize = SNAPSHOT_SIZE * SNAPSHOT_SIZE * SNAPSHOT_SIZE;
+
+/// A copy of the blocks of a chunk and their light, padded with a one block wide border
+/// taken from the surrounding chunks.
+///
+/// Things that look at the neighbours of blocks (like meshing) can work on a snapshot
+/// without borrowing the chunk storage, and without special cases at chunk borders.
+/// Positions outside of any loaded chunk read as air under open sky.
+pub struct ChunkSnapshot {
+	pub pos: ChunkCoord,
+	
+	/// The `last_update` of the chunk at the time the snapshot was taken.
+	pub last_update: u128,
+	
+	blocks: Vec<BlockState>,
+	light: LightStorage,
+}
+
+impl ChunkSnapshot {
+	/// Copies the chunk at the given position and its border; `None` if the chunk is not loaded.
+	pub fn new(storage: &ChunkStorage, pos: &ChunkCoord) -> Option<ChunkSnapshot> {
+		let center = storage.get_chunk(pos)?;
+		
+		let air = center.blockdef
+			.get_block_by_name_unchecked("air")
+			.get_default_state();
+		
+		// The chunk itself and its 26 neighbours, indexed by offset.
+		let mut chunks = [None; 27];
+		for (index, chunk) in chunks.iter_mut().enumerate() {
+			let offset = ChunkCoord::new_from_chunk(
+				pos.x + (index % 3) as isize - 1,
+				pos.y + (index / 9) as isize - 1,
+				pos.z + (index / 3 % 3) as isize - 1,
+			);
+			*chunk = storage.get_chunk(&offset);
+		}
+		
+		let mut blocks = Vec::with_capacity(SNAPSHOT_VOLUME);
+		let mut light = LightStorage::new(SNAPSHOT_VOLUME);
+		let size = CHUNK_SIZE as isize;
+		
+		for y in -1..=size {
+			for z in -1..=size {
+				for x in -1..=size {
+					let index = Self::chunk_index(x) + Self::chunk_index(z) * 3 + Self::chunk_index(y) * 9;
+					let target = blocks.len();
+					
+					match chunks[index] {
+						Some(chunk) => {
+							let csm = CHUNK_SIZE_MASK as isize;
+							let (x, y, z) = ((x & csm) as usize, (y & csm) as usize, (z & csm) as usize);
+							let source = y*CHUNK_SLICE + z*CHUNK_SIZE + x;
+							
+							blocks.push(chunk.blocks.get(source));
+							for channel in LightChannel::ALL.iter() {
+								light.set(target, *channel, chunk.light.get(source, *channel));
+							}
+						},
+						None => {
+							blocks.push(air);
+							light.set(target, LightChannel::Sky, MAX_LIGHT);
+						}
+					}
+				}
+			}
+		}
+		
+		Some(ChunkSnapshot {
+			pos: *pos,
+			last_update: center.last_update,
+			blocks,
+			light,
+		})
+	}
+	
+	/// Which of the three chunks along an axis a chunk-relative coordinate falls into.
+	fn chunk_index(value: isize) -> usize {
+		if value < 0 {
+			0
+		} else if value >= CHUNK_SIZE as isize {
+			2
+		} else {
+			1
+		}
+	}
+	
+	/// The index of the given chunk-relative position, if it is within the snapshot.
+	fn index(x: isize, y: isize, z: isize) -> Option<usize> {
+		let range = -1..=CHUNK_SIZE as isize;
+		if !range.contains(&x) || !range.contains(&y) || !range.contains(&z) {
+			return None;
+		}
+		
+		let (x, y, z) = ((x + 1) as usize, (y + 1) as usize, (z + 1) as usize);
+		Some((y * SNAPSHOT_SIZE + z) * SNAPSHOT_SIZE + x)
+	}
+	
+	/// Returns the block at the given chunk-relative position,
+	/// which may be up to one block outside of the chunk.
+	pub fn get_block(&self, x: isize, y: isize, z: isize) -> Option<BlockState> {
+		Self::index(x, y, z).map(|index| self.blocks[index])
+	}
+	
+	/// Returns the light level of the given channel at the given chunk-relative position,
+	/// which may be up to one block outside of the chunk.
+	pub fn get_light(&self, x: isize, y: isize, z: isize, channel: LightChannel) -> Option<u8> {
+		Self::index(x, y, z).map(|index| self.light.get(index, channel))
+	}
+}
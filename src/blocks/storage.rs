@@ -5,6 +5,16 @@ use super::universe::BlockState;
 pub trait BlockStorage {
 	fn get(&self, location: usize) -> BlockState;
 	fn set(&mut self, location: usize, state: &BlockState);
+	
+	/// The number of locations in this storage.
+	fn len(&self) -> usize;
+	
+	/// Sets every location in this storage to the given state.
+	fn fill(&mut self, state: &BlockState) {
+		for location in 0..self.len() {
+			self.set(location, state);
+		}
+	}
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -13,6 +23,14 @@ pub struct SimpleBlockStorage {
 	blocks: Vec<BlockState>,
 }
 
+impl SimpleBlockStorage {
+	pub fn new(size: usize, fill: BlockState) -> SimpleBlockStorage {
+		SimpleBlockStorage {
+			blocks: vec![fill; size]
+		}
+	}
+}
+
 impl BlockStorage for SimpleBlockStorage {
 	fn get(&self, location: usize) -> BlockState {
 		return self.blocks[location].clone();
@@ -20,4 +38,290 @@ impl BlockStorage for SimpleBlockStorage {
 	fn set(&mut self, location: usize, state: &BlockState) {
 		self.blocks[location] = state.clone();
 	}
+	fn len(&self) -> usize {
+		self.blocks.len()
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Block storage that stores a palette of distinct states,
+/// and for every location a variable-width index into that palette.
+///
+/// As long as only a single state is stored, no indices are stored at all.
+pub struct PaletteBlockStorage {
+	/// Number of locations.
+	size: usize,
+	
+	/// The distinct states, indexed by the packed indices.
+	palette: Vec<BlockState>,
+	
+	/// How many locations reference each palette entry;
+	/// entries with a count of zero can be reused.
+	counts: Vec<usize>,
+	
+	/// Width of a single index in bits; zero if the palette has a single entry.
+	bits: usize,
+	
+	/// The packed indices; an index never straddles two words.
+	data: Vec<u64>,
+}
+
+impl PaletteBlockStorage {
+	/// Creates a new storage of the given size, with every location set to `fill`.
+	pub fn new(size: usize, fill: BlockState) -> PaletteBlockStorage {
+		PaletteBlockStorage {
+			size,
+			palette: vec![fill],
+			counts: vec![size],
+			bits: 0,
+			data: vec![],
+		}
+	}
+	
+	/// The distinct states stored, including ones no longer referenced.
+	pub fn get_palette(&self) -> &[BlockState] {
+		&self.palette
+	}
+	
+	/// The current width of a single index in bits.
+	pub fn get_bits_per_index(&self) -> usize {
+		self.bits
+	}
+	
+	/// Approximate amount of heap memory used by this storage, in bytes.
+	pub fn get_heap_size(&self) -> usize {
+		self.palette.capacity() * std::mem::size_of::<BlockState>()
+			+ self.counts.capacity() * std::mem::size_of::<usize>()
+			+ self.data.capacity() * std::mem::size_of::<u64>()
+	}
+	
+	/// Rebuilds the palette from the referenced states only,
+	/// shrinking the indices to the smallest possible width.
+	pub fn compact(&mut self) {
+		if self.size == 0 {
+			return;
+		}
+		
+		let live = self.counts.iter().filter(|c| **c > 0).count();
+		
+		if live == self.palette.len() && Self::bits_for(live) == self.bits {
+			return;
+		}
+		
+		let states: Vec<BlockState> = (0..self.size).map(|i| self.get(i)).collect();
+		
+		*self = PaletteBlockStorage::new(self.size, states[0]);
+		for (location, state) in states.iter().enumerate() {
+			self.set(location, state);
+		}
+	}
+	
+	/// Returns the smallest index width able to address `entries` palette entries.
+	fn bits_for(entries: usize) -> usize {
+		if entries <= 1 {
+			return 0;
+		}
+		
+		let mut bits = 1;
+		while (1 << bits) < entries {
+			bits += 1;
+		}
+		bits
+	}
+	
+	fn get_index(&self, location: usize) -> usize {
+		if self.bits == 0 {
+			return 0;
+		}
+		
+		let per_word = 64 / self.bits;
+		let word = self.data[location / per_word];
+		let shift = (location % per_word) * self.bits;
+		let mask = (1u64 << self.bits) - 1;
+		((word >> shift) & mask) as usize
+	}
+	
+	fn set_index(&mut self, location: usize, index: usize) {
+		let per_word = 64 / self.bits;
+		let word = &mut self.data[location / per_word];
+		let shift = (location % per_word) * self.bits;
+		let mask = (1u64 << self.bits) - 1;
+		*word = (*word & !(mask << shift)) | (((index as u64) & mask) << shift);
+	}
+	
+	/// Repacks all indices into the given width.
+	fn resize(&mut self, bits: usize) {
+		let indices: Vec<usize> = (0..self.size).map(|i| self.get_index(i)).collect();
+		
+		self.bits = bits;
+		if bits == 0 {
+			self.data = vec![];
+			return;
+		}
+		
+		let per_word = 64 / bits;
+		self.data = vec![0; (self.size + per_word - 1) / per_word];
+		
+		for (location, index) in indices.into_iter().enumerate() {
+			self.set_index(location, index);
+		}
+	}
+	
+	/// Returns the palette index for the given state, adding it if necessary.
+	fn index_of(&mut self, state: &BlockState) -> usize {
+		if let Some(index) = self.palette.iter().position(|s| s == state) {
+			return index;
+		}
+		
+		// Reuse an entry that is no longer referenced...
+		if let Some(index) = self.counts.iter().position(|c| *c == 0) {
+			self.palette[index] = *state;
+			return index;
+		}
+		
+		// ...or grow the palette, widening the indices if they run out.
+		self.palette.push(*state);
+		self.counts.push(0);
+		
+		let bits = Self::bits_for(self.palette.len());
+		if bits > self.bits {
+			self.resize(bits);
+		}
+		
+		self.palette.len() - 1
+	}
+}
+
+impl BlockStorage for PaletteBlockStorage {
+	fn get(&self, location: usize) -> BlockState {
+		assert!(location < self.size, "Location {} is out of bounds.", location);
+		self.palette[self.get_index(location)]
+	}
+	
+	fn set(&mut self, location: usize, state: &BlockState) {
+		assert!(location < self.size, "Location {} is out of bounds.", location);
+		
+		let old = self.get_index(location);
+		if self.palette[old] == *state {
+			return;
+		}
+		
+		let new = self.index_of(state);
+		self.counts[old] -= 1;
+		self.counts[new] += 1;
+		
+		// Collapse to a single value once every location holds the same state.
+		if self.counts[new] == self.size {
+			*self = PaletteBlockStorage::new(self.size, *state);
+			return;
+		}
+		
+		self.set_index(location, new);
+	}
+	
+	fn len(&self) -> usize {
+		self.size
+	}
+	
+	fn fill(&mut self, state: &BlockState) {
+		*self = PaletteBlockStorage::new(self.size, *state);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::universe::BlockId;
+	
+	const SIZE: usize = 4096;
+	
+	fn state(id: usize, data: u16) -> BlockState {
+		BlockState { id: BlockId::new(id), data }
+	}
+	
+	/// A state that is different for every location, and from the initial fill.
+	fn distinct(location: usize) -> BlockState {
+		state(1, location as u16)
+	}
+	
+	#[test]
+	fn roundtrip_of_every_index() {
+		let mut storage = PaletteBlockStorage::new(SIZE, state(0, 0));
+		let mut bits = storage.get_bits_per_index();
+		
+		for location in 0..SIZE {
+			storage.set(location, &distinct(location));
+			
+			// Every time the indices widen, everything written so far must survive the repacking.
+			if storage.get_bits_per_index() != bits {
+				bits = storage.get_bits_per_index();
+				assert_eq!(bits, PaletteBlockStorage::bits_for(location + 2));
+				
+				for written in 0..=location {
+					assert_eq!(storage.get(written), distinct(written), "at {} bits", bits);
+				}
+				for unwritten in location + 1..SIZE {
+					assert_eq!(storage.get(unwritten), state(0, 0), "at {} bits", bits);
+				}
+			}
+		}
+		
+		assert_eq!(bits, 13);
+		for location in 0..SIZE {
+			assert_eq!(storage.get(location), distinct(location));
+		}
+	}
+	
+	#[test]
+	fn collapses_when_a_single_state_is_left() {
+		let mut storage = PaletteBlockStorage::new(SIZE, state(0, 0));
+		for location in 0..SIZE {
+			storage.set(location, &state(location % 3, 0));
+		}
+		assert_eq!(storage.get_bits_per_index(), 2);
+		
+		for location in 0..SIZE - 1 {
+			storage.set(location, &state(7, 0));
+		}
+		assert_eq!(storage.get_bits_per_index(), 2);
+		
+		storage.set(SIZE - 1, &state(7, 0));
+		assert_eq!(storage.get_bits_per_index(), 0);
+		assert_eq!(storage.get_palette(), &[state(7, 0)]);
+		
+		for location in 0..SIZE {
+			assert_eq!(storage.get(location), state(7, 0));
+		}
+	}
+	
+	#[test]
+	fn compact_keeps_every_value() {
+		let mut storage = PaletteBlockStorage::new(SIZE, state(0, 0));
+		for location in 0..SIZE {
+			storage.set(location, &distinct(location));
+		}
+		
+		// Leave only a handful of the states referenced.
+		for location in 0..SIZE {
+			if location % 1000 != 0 {
+				storage.set(location, &state(2, 0));
+			}
+		}
+		
+		assert_eq!(storage.get_bits_per_index(), 13);
+		storage.compact();
+		assert_eq!(storage.get_palette().len(), 6);
+		assert_eq!(storage.get_bits_per_index(), 3);
+		
+		for location in 0..SIZE {
+			let expected = if location % 1000 == 0 { distinct(location) } else { state(2, 0) };
+			assert_eq!(storage.get(location), expected);
+		}
+		
+		// A storage that is already compact stays as it is.
+		storage.compact();
+		assert_eq!(storage.get_palette().len(), 6);
+		assert_eq!(storage.get(3000), distinct(3000));
+	}
 }
@@ -0,0 +1,367 @@
+//! Loads and unloads chunks around observers as they move through the world.
+//!
+//! Chunks within the view distance of any observer are loaded, nearest first, from the save
+//! if they were saved before and from the generator otherwise. Chunks that are out of range of
+//! every observer are dropped, and saved first if they were modified. Configured by the `[streaming]` table of the scene config:
+//!
+//! ```toml
+//! [streaming]
+//! view-distance = 4   # horizontal radius, in chunks
+//! view-height = 2     # chunks above and below the observer
+//! loads-per-update = 4
+//! ```
+
+use std::path::{Path, PathBuf};
+use rustc_hash::{FxHashMap, FxHashSet};
+use super::region::{self, RegionCoord};
+use super::world::{Chunk, ChunkCoord, ChunkStorage, CHUNK_SIZE_SHIFT};
+
+/// Chunks are only unloaded once they are this many chunks beyond the view distance,
+/// so moving back and forth across a chunk border does not reload chunks over and over.
+const UNLOAD_MARGIN: isize = 1;
+
+pub struct ChunkStreamer {
+	/// The horizontal radius around every observer within which chunks are loaded, in chunks.
+	pub view_distance: isize,
+	
+	/// How many chunks above and below every observer are loaded.
+	pub view_height: isize,
+	
+	/// The most chunks loaded in a single update.
+	pub loads_per_update: usize,
+	
+	save_dir: Option<PathBuf>,
+	
+	/// Saved chunks that have been read from their region file, but are not loaded yet.
+	/// A region is in here once its file has been read.
+	regions: FxHashMap<RegionCoord, FxHashMap<ChunkCoord, Chunk>>,
+}
+
+impl ChunkStreamer {
+	/// Creates a streamer from the `[streaming]` config table,
+	/// loading and saving chunks in the given directory, if any.
+	pub fn new(config: &toml::value::Table, save_dir: Option<PathBuf>) -> ChunkStreamer {
+		let streaming = config.get("streaming").and_then(|s| s.as_table());
+		let get = |key: &str, default: isize| {
+			streaming
+				.and_then(|s| s.get(key))
+				.and_then(|v| v.as_integer())
+				.filter(|v| *v >= 0)
+				.map(|v| v as isize)
+				.unwrap_or(default)
+		};
+		
+		ChunkStreamer {
+			view_distance: get("view-distance", 4),
+			view_height: get("view-height", 2),
+			loads_per_update: get("loads-per-update", 4).max(1) as usize,
+			save_dir,
+			regions: FxHashMap::default(),
+		}
+	}
+	
+	pub fn get_save_dir(&self) -> Option<&Path> {
+		self.save_dir.as_ref().map(|dir| dir.as_path())
+	}
+	
+	/// Loads the missing chunks around the given observers, and unloads the ones out of their range.
+	pub fn update(&mut self, storage: &mut ChunkStorage, observers: &[cgmath::Vector3<f32>]) {
+		let centers: Vec<ChunkCoord> = observers.iter()
+			.map(|pos| ChunkCoord::new_from_chunk(
+				(pos.x.floor() as isize) >> CHUNK_SIZE_SHIFT,
+				(pos.y.floor() as isize) >> CHUNK_SIZE_SHIFT,
+				(pos.z.floor() as isize) >> CHUNK_SIZE_SHIFT,
+			))
+			.collect();
+		
+		self.unload_distant(storage, &centers);
+		
+		let wanted = self.get_wanted(&centers);
+		
+		// Forget the saved chunks of regions nobody is near anymore.
+		let regions: FxHashSet<RegionCoord> = wanted.iter()
+			.map(RegionCoord::new_from_chunk)
+			.collect();
+		self.regions.retain(|rpos, _| regions.contains(rpos));
+		
+		// Nearest first; columns from the top down, so sky light only has to spread once.
+		let mut missing: Vec<(isize, isize, ChunkCoord)> = wanted
+			.into_iter()
+			.filter(|cpos| !storage.contains_chunk(cpos))
+			.map(|cpos| (Self::get_distance(&centers, &cpos), -cpos.y, cpos))
+			.collect();
+		
+		missing.sort_by_key(|(distance, y, _)| (*distance, *y));
+		
+		for (_, _, cpos) in missing.into_iter().take(self.loads_per_update) {
+			let chunk = self.load_chunk(storage, &cpos);
+			storage.insert_chunk(chunk);
+		}
+	}
+	
+	/// The positions of all chunks within view distance of any of the given chunks.
+	fn get_wanted(&self, centers: &[ChunkCoord]) -> FxHashSet<ChunkCoord> {
+		let (r, h) = (self.view_distance, self.view_height);
+		let mut wanted = FxHashSet::default();
+		
+		for center in centers {
+			for y in -h..=h {
+				for z in -r..=r {
+					for x in -r..=r {
+						if x*x + z*z <= r*r {
+							wanted.insert(ChunkCoord::new_from_chunk(center.x + x, center.y + y, center.z + z));
+						}
+					}
+				}
+			}
+		}
+		
+		wanted
+	}
+	
+	/// The squared horizontal distance to the nearest of the given chunks.
+	fn get_distance(centers: &[ChunkCoord], cpos: &ChunkCoord) -> isize {
+		centers.iter()
+			.map(|center| {
+				let (x, z) = (cpos.x - center.x, cpos.z - center.z);
+				x*x + z*z
+			})
+			.min()
+			.unwrap_or(0)
+	}
+	
+	/// Takes the chunk from the save if it is there, and generates it otherwise.
+	fn load_chunk(&mut self, storage: &ChunkStorage, cpos: &ChunkCoord) -> Chunk {
+		let save_dir = match &self.save_dir {
+			Some(save_dir) => save_dir,
+			None => return storage.generate_chunk(cpos)
+		};
+		
+		let rpos = RegionCoord::new_from_chunk(cpos);
+		if ! self.regions.contains_key(&rpos) {
+			let chunks = match region::read_region_chunks(save_dir, &rpos, storage.get_blockdef()) {
+				Ok(chunks) => chunks,
+				Err(e) => {
+					error!("Failed to read region {}: {}", rpos, e);
+					vec![]
+				}
+			};
+			
+			self.regions.insert(rpos, chunks.into_iter().map(|chunk| (chunk.pos, chunk)).collect());
+		}
+		
+		match self.regions.get_mut(&rpos).and_then(|chunks| chunks.remove(cpos)) {
+			Some(chunk) => chunk,
+			None => storage.generate_chunk(cpos)
+		}
+	}
+	
+	/// Removes the chunks that are out of range of all observers, saving the modified ones if there is a save.
+	///
+	/// Modified chunks that fail to save stay loaded, and saving them is tried again on the next update.
+	fn unload_distant(&mut self, storage: &mut ChunkStorage, centers: &[ChunkCoord]) {
+		let (r, h) = (self.view_distance + UNLOAD_MARGIN, self.view_height + UNLOAD_MARGIN);
+		
+		let mut distant: Vec<ChunkCoord> = storage.iter()
+			.map(|chunk| chunk.pos)
+			.filter(|cpos| !centers.iter().any(|center| {
+				let (x, y, z) = (cpos.x - center.x, cpos.y - center.y, cpos.z - center.z);
+				x*x + z*z <= r*r && y.abs() <= h
+			}))
+			.collect();
+		
+		if distant.is_empty() {
+			return;
+		}
+		
+		if let Some(save_dir) = &self.save_dir {
+			let modified: Vec<&Chunk> = distant.iter()
+				.filter_map(|cpos| storage.get_chunk(cpos))
+				.filter(|chunk| chunk.modified)
+				.collect();
+			
+			if !modified.is_empty() {
+				match region::save_chunks(save_dir, &modified, storage.get_blockdef()) {
+					Ok(_) => {
+						// The files of these regions changed, so they have to be read again.
+						for chunk in modified.iter() {
+							self.regions.remove(&RegionCoord::new_from_chunk(&chunk.pos));
+						}
+					},
+					Err(e) => {
+						error!("Failed to save {} unloaded chunks, keeping them loaded: {}", modified.len(), e);
+						let failed: FxHashSet<ChunkCoord> = modified.iter().map(|chunk| chunk.pos).collect();
+						distant.retain(|cpos| !failed.contains(cpos));
+					}
+				}
+			}
+		}
+		
+		let chunks: Vec<Chunk> = distant.iter()
+			.filter_map(|cpos| storage.remove_chunk(cpos))
+			.collect();
+		
+		trace!("Unloaded {} chunks.", chunks.len());
+		
+		if self.save_dir.is_some() {
+			// Chunks that were not modified are still the same as in the save or the generator;
+			// the ones taken from a region that is still read go back to it.
+			for chunk in chunks.into_iter().filter(|chunk| !chunk.modified) {
+				if let Some(chunks) = self.regions.get_mut(&RegionCoord::new_from_chunk(&chunk.pos)) {
+					chunks.insert(chunk.pos, chunk);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+	use super::*;
+	use super::super::universe::{define_universe, UniverseRef};
+	use super::super::coords::BlockCoord;
+	use super::super::BlockState;
+	
+	const CONFIG: &str = r#"
+		[blocks.stone]
+		
+		[streaming]
+		view-distance = 2
+		view-height = 0
+		loads-per-update = 100
+	"#;
+	
+	fn setup(save_dir: Option<PathBuf>) -> (UniverseRef, ChunkStorage, ChunkStreamer) {
+		let config: toml::Value = CONFIG.parse().unwrap();
+		let config = config.as_table().unwrap();
+		let blockdef = define_universe(config);
+		let storage = ChunkStorage::new_empty(blockdef.clone(), config).unwrap();
+		(blockdef, storage, ChunkStreamer::new(config, save_dir))
+	}
+	
+	fn stone(blockdef: &UniverseRef) -> BlockState {
+		blockdef.get_block_by_name_unchecked("stone").get_default_state()
+	}
+	
+	/// An empty directory to save into, unique to the test and the process.
+	fn save_dir(name: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("tcge-streaming-{}-{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		dir
+	}
+	
+	/// The position of an observer in the middle of the given chunk.
+	fn observer(x: isize, z: isize) -> cgmath::Vector3<f32> {
+		cgmath::Vector3::new(x as f32 * 16.0 + 8.0, 8.0, z as f32 * 16.0 + 8.0)
+	}
+	
+	fn positions(storage: &ChunkStorage) -> FxHashSet<ChunkCoord> {
+		storage.iter().map(|chunk| chunk.pos).collect()
+	}
+	
+	#[test]
+	fn chunks_are_loaded_nearest_first() {
+		let (_, mut storage, mut streamer) = setup(None);
+		streamer.loads_per_update = 1;
+		
+		let mut distances = vec![];
+		for _ in 0..13 {
+			let before = positions(&storage);
+			streamer.update(&mut storage, &[observer(0, 0)]);
+			
+			let loaded: Vec<ChunkCoord> = positions(&storage).difference(&before).cloned().collect();
+			assert_eq!(loaded.len(), 1);
+			distances.push(loaded[0].x * loaded[0].x + loaded[0].z * loaded[0].z);
+		}
+		
+		assert_eq!(distances, vec![0, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4]);
+		
+		// Everything in range is loaded.
+		streamer.update(&mut storage, &[observer(0, 0)]);
+		assert_eq!(storage.len(), 13);
+	}
+	
+	#[test]
+	fn chunks_are_unloaded_past_the_margin() {
+		let (_, mut storage, mut streamer) = setup(None);
+		streamer.update(&mut storage, &[observer(0, 0)]);
+		assert_eq!(storage.len(), 13);
+		
+		// Three chunks away from the observer is within the margin...
+		streamer.update(&mut storage, &[observer(3, 0)]);
+		assert!(storage.contains_chunk(&ChunkCoord::new_from_chunk(0, 0, 0)));
+		
+		// ...four chunks away is not.
+		assert!(!storage.contains_chunk(&ChunkCoord::new_from_chunk(-1, 0, 0)));
+		assert!(!storage.contains_chunk(&ChunkCoord::new_from_chunk(0, 0, 2)));
+		
+		// A single chunk above or below is kept as well.
+		streamer.update(&mut storage, &[cgmath::Vector3::new(8.0, 24.0, 8.0)]);
+		assert!(storage.contains_chunk(&ChunkCoord::new_from_chunk(0, 0, 0)));
+		streamer.update(&mut storage, &[cgmath::Vector3::new(8.0, 40.0, 8.0)]);
+		assert!(!storage.contains_chunk(&ChunkCoord::new_from_chunk(0, 0, 0)));
+	}
+	
+	#[test]
+	fn only_modified_chunks_are_saved() {
+		let dir = save_dir("modified");
+		let (blockdef, mut storage, mut streamer) = setup(Some(dir.clone()));
+		let block = BlockCoord::new(5, 5, 5);
+		
+		// The chunks around the origin span the regions on both sides of it.
+		streamer.update(&mut storage, &[observer(0, 0)]);
+		assert!(storage.set_block(&block, stone(&blockdef)));
+		
+		streamer.update(&mut storage, &[observer(100, 0)]);
+		assert!(!storage.contains_chunk(&ChunkCoord::new_from_chunk(0, 0, 0)));
+		
+		let files: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+		assert_eq!(files, vec![RegionCoord { x: 0, y: 0, z: 0 }.get_file_path(&dir)]);
+		
+		let (_, chunks) = region::read_region(&files[0], &blockdef).unwrap();
+		assert_eq!(chunks.len(), 1);
+		assert!(chunks[0].pos == ChunkCoord::new_from_chunk(0, 0, 0));
+		
+		// Coming back loads the change from the save.
+		streamer.update(&mut storage, &[observer(0, 0)]);
+		assert_eq!(storage.get_block(&block), Some(stone(&blockdef)));
+		assert!(storage.iter().all(|chunk| !chunk.modified));
+		
+		let _ = fs::remove_dir_all(&dir);
+	}
+	
+	#[test]
+	fn chunks_that_fail_to_save_stay_loaded() {
+		let dir = save_dir("failing");
+		let (blockdef, mut storage, mut streamer) = setup(Some(dir.clone()));
+		let block = BlockCoord::new(5, 5, 5);
+		let cpos = ChunkCoord::new_from_chunk(0, 0, 0);
+		
+		streamer.update(&mut storage, &[observer(0, 0)]);
+		assert!(storage.set_block(&block, stone(&blockdef)));
+		
+		// Saving has to merge with the existing region file, which is not readable.
+		let path = RegionCoord::new_from_chunk(&cpos).get_file_path(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(&path, b"garbage").unwrap();
+		
+		streamer.update(&mut storage, &[observer(100, 0)]);
+		assert!(storage.contains_chunk(&cpos));
+		assert!(storage.get_chunk(&cpos).unwrap().modified);
+		assert_eq!(storage.get_block(&block), Some(stone(&blockdef)));
+		
+		// The unmodified chunks are unloaded regardless.
+		assert!(!storage.contains_chunk(&ChunkCoord::new_from_chunk(1, 0, 0)));
+		
+		// Once the file is out of the way, the next update saves the chunk.
+		fs::remove_file(&path).unwrap();
+		streamer.update(&mut storage, &[observer(100, 0)]);
+		assert!(!storage.contains_chunk(&cpos));
+		
+		streamer.update(&mut storage, &[observer(0, 0)]);
+		assert_eq!(storage.get_block(&block), Some(stone(&blockdef)));
+		
+		let _ = fs::remove_dir_all(&dir);
+	}
+}
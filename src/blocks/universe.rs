@@ -1,39 +1,203 @@
 //! This file defines the 'universe' of blocks.
 //! Its simply the definition of blocks and their states.
 //!
-//! TODO: Load the universe from a file.
+//! Block ids are handed out in order of registration, which depends on the config;
+//! to keep them stable between runs, the name-to-id table can be saved
+//! with `Universe::get_id_mapping` and handed back to `define_universe_with_ids`.
 
 use rustc_hash::FxHashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::collections::hash_map::Iter;
+use std::path::Path;
+use std::fs;
+use std::io;
+use super::property::{BlockProperty, PropertyValue};
+use super::attributes::BlockAttributes;
+
+/// The file name used for id-mappings within a world directory.
+pub const ID_MAPPING_FILE: &str = "blocks.toml";
+
+#[derive(Debug, Fail)]
+pub enum UniverseError {
+	#[fail(display = "I/O error")]
+	Io(#[cause] io::Error),
+	
+	#[fail(display = "Failed to parse id-mapping")]
+	MappingParse(#[cause] toml::de::Error),
+	
+	#[fail(display = "Failed to write id-mapping")]
+	MappingWrite(#[cause] toml::ser::Error),
+	
+	#[fail(display = "The universe is frozen, can not register block '{}'", name)]
+	Frozen { name: String },
+	
+	#[fail(display = "Block '{}' is already registered", name)]
+	DuplicateBlock { name: String },
+	
+	#[fail(display = "Can not register block '{}', all {} block ids are in use", name, limit)]
+	TooManyBlocks { name: String, limit: usize },
+	
+	#[fail(display = "Invalid id for block '{}' in id-mapping", name)]
+	InvalidMapping { name: String },
+	
+	#[fail(display = "Blocks '{}' and '{}' share the same id in id-mapping", first, second)]
+	DuplicateId { first: String, second: String },
+	
+	#[fail(display = "Invalid property '{}' of block '{}': {}", property, block, reason)]
+	InvalidProperty { block: String, property: String, reason: String },
+	
+	#[fail(display = "Invalid attributes of block '{}': {}", block, reason)]
+	InvalidAttributes { block: String, reason: String },
+	
+	#[fail(display = "Block '{}' has more than 65536 states", block)]
+	TooManyStates { block: String },
+}
+
+impl From<io::Error> for UniverseError {
+	fn from(other: io::Error) -> Self {
+		UniverseError::Io(other)
+	}
+}
 
 pub struct Universe {
 	blocks: FxHashMap<BlockId, Block>,
+	
+	/// Name-to-id table of every block ever known to this universe,
+	/// including blocks from a loaded id-mapping that are not registered.
+	ids: FxHashMap<String, BlockId>,
+	
+	/// The id that is given to the next *new* block.
+	next_id: usize,
+	
+	/// Once frozen, no more blocks can be registered.
+	frozen: bool,
 }
 
 impl Universe {
 	
 	fn new() -> Universe {
 		Universe {
-			blocks: FxHashMap::default()
+			blocks: FxHashMap::default(),
+			ids: FxHashMap::default(),
+			next_id: 0,
+			frozen: false,
 		}
 	}
 	
-	fn register_block(&mut self, name: &str) {
-		let next_id = self.blocks.len();
-		let block_id = BlockId::new(next_id);
+	/// Registers a new block, reusing its id if it is already known from an id-mapping.
+	///
+	/// The optional `info` is the config-table of the block, declaring its properties and attributes.
+	fn register_block(&mut self, name: &str, info: Option<&toml::Value>) -> Result<BlockId, UniverseError> {
+		if self.frozen {
+			return Err(UniverseError::Frozen { name: name.to_string() });
+		}
+		
+		// This includes the built-in blocks, which must not be replaced by the config.
+		if self.get_block_by_name(name).is_some() {
+			return Err(UniverseError::DuplicateBlock { name: name.to_string() });
+		}
+		
+		let properties = Block::parse_properties(name, info)?;
+		let attributes = BlockAttributes::from_config(name, info)
+			.map_err(|reason| UniverseError::InvalidAttributes { block: name.to_string(), reason })?;
 		
-		let default_state = BlockState {
-			id: block_id, data: ()
+		let block_id = match self.ids.get(name) {
+			Some(id) => *id,
+			None => {
+				if self.next_id > u16::max_value() as usize {
+					return Err(UniverseError::TooManyBlocks {
+						name: name.to_string(),
+						limit: u16::max_value() as usize + 1
+					});
+				}
+				
+				let id = BlockId::new(self.next_id);
+				self.next_id += 1;
+				self.ids.insert(name.to_string(), id);
+				id
+			}
 		};
 		
-		let block = Block {
+		let mut block = Block {
 			id: block_id,
 			name: name.to_string(),
-			default_state
+			properties,
+			attributes,
+			state_count: 1,
+			default_state: BlockState {
+				id: block_id, data: 0
+			}
 		};
 		
+		// Lay out the properties within the state data, mixed-radix style.
+		let mut default_data = 0;
+		for property in block.properties.iter_mut() {
+			property.stride = block.state_count;
+			default_data += property.get_default_index() * property.stride;
+			block.state_count = match block.state_count.checked_mul(property.get_value_count()) {
+				Some(count) if count <= u16::max_value() as usize + 1 => count,
+				_ => return Err(UniverseError::TooManyStates { block: name.to_string() })
+			};
+		}
+		
+		block.default_state.data = default_data as u16;
+		
 		self.blocks.insert(block.id, block);
+		Ok(block_id)
+	}
+	
+	/// Loads a name-to-id table (as written by `get_id_mapping`),
+	/// so that blocks registered afterwards keep the ids they had back then.
+	///
+	/// Must be called before any blocks are registered.
+	fn load_id_mapping(&mut self, mapping: &toml::value::Table) -> Result<(), UniverseError> {
+		let mut names: FxHashMap<BlockId, &str> = FxHashMap::default();
+		
+		for (name, id) in mapping {
+			let id = match id.as_integer() {
+				Some(id) if id >= 0 && id <= u16::max_value() as i64 => BlockId::new(id as usize),
+				_ => return Err(UniverseError::InvalidMapping { name: name.to_string() })
+			};
+			
+			if let Some(first) = names.insert(id, name) {
+				return Err(UniverseError::DuplicateId { first: first.to_string(), second: name.to_string() });
+			}
+			
+			self.ids.insert(name.to_string(), id);
+			self.next_id = self.next_id.max(id.get_raw_id() + 1);
+		}
+		
+		Ok(())
+	}
+	
+	/// Prevents any further blocks from being registered.
+	pub fn freeze(&mut self) {
+		self.frozen = true;
+	}
+	
+	pub fn is_frozen(&self) -> bool {
+		self.frozen
+	}
+	
+	/// Returns the name-to-id table of this universe, for persisting it.
+	///
+	/// Contains blocks that were loaded from an earlier mapping but are not registered anymore,
+	/// so their ids are never handed out to different blocks.
+	pub fn get_id_mapping(&self) -> toml::value::Table {
+		let mut mapping = toml::value::Table::new();
+		for (name, id) in self.ids.iter() {
+			mapping.insert(name.clone(), toml::Value::Integer(id.get_raw_id() as i64));
+		}
+		mapping
+	}
+	
+	/// Writes the id-mapping of this universe into a TOML file.
+	pub fn write_id_mapping(&self, path: &Path) -> Result<(), UniverseError> {
+		let mapping = toml::Value::Table(self.get_id_mapping());
+		let mapping = toml::to_string(&mapping)
+			.map_err(UniverseError::MappingWrite)?;
+		fs::write(path, mapping)?;
+		Ok(())
 	}
 	
 	pub fn list_blocks(&self) -> Iter<BlockId, Block> {
@@ -41,13 +205,7 @@ impl Universe {
 	}
 	
 	pub fn get_block_by_name(&self, name: &str) -> Option<&Block> {
-		for (_id, block) in self.blocks.iter() {
-			if block.name == name {
-				return Some(block)
-			}
-		}
-		
-		None
+		self.blocks.get(self.ids.get(name)?)
 	}
 	
 	pub fn get_block_by_name_unchecked(&self, name: &str) -> &Block {
@@ -57,9 +215,49 @@ impl Universe {
 	pub fn get_block_by_id(&self, id: BlockId) -> &Block {
 		return &self.blocks[&id];
 	}
+	
+	/// Returns the material attributes of the block of the given state.
+	pub fn get_attributes(&self, state: &BlockState) -> &BlockAttributes {
+		self.get_block_by_id(state.id).get_attributes()
+	}
+	
+	/// Formats a state as `name[property=value,...]`.
+	pub fn state_to_string(&self, state: &BlockState) -> String {
+		self.get_block_by_id(state.id).state_to_string(state)
+	}
+	
+	/// Parses a state formatted as `name[property=value,...]`.
+	///
+	/// Properties that are not given keep their default value.
+	pub fn get_state_by_string(&self, string: &str) -> Option<BlockState> {
+		let (name, properties) = match string.find('[') {
+			Some(start) => {
+				if ! string.ends_with(']') {
+					return None;
+				}
+				(&string[..start], &string[start + 1 .. string.len() - 1])
+			},
+			None => (string, "")
+		};
+		
+		let block = self.get_block_by_name(name)?;
+		let mut state = block.get_default_state();
+		
+		for pair in properties.split(',').filter(|p| !p.is_empty()) {
+			let mut pair = pair.splitn(2, '=');
+			let key = pair.next()?.trim();
+			let value = pair.next()?.trim();
+			
+			let value = block.get_property_by_name(key)?.parse_value(value)?;
+			state = block.with_property(&state, key, &value)?;
+		}
+		
+		Some(state)
+	}
 }
 
-pub type UniverseRef = Rc<Universe>;
+/// Shared handle to a universe; it is immutable once defined, so it can be shared across threads.
+pub type UniverseRef = Arc<Universe>;
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -90,10 +288,43 @@ impl PartialEq for BlockId {
 pub struct Block {
 	id: BlockId,
 	name: String,
+	properties: Vec<BlockProperty>,
+	attributes: BlockAttributes,
+	state_count: usize,
 	default_state: BlockState,
 }
 
 impl Block {
+	fn parse_properties(name: &str, info: Option<&toml::Value>) -> Result<Vec<BlockProperty>, UniverseError> {
+		let declarations = match info.and_then(|info| info.get("properties")) {
+			Some(declarations) => declarations,
+			None => return Ok(vec![])
+		};
+		
+		let declarations = declarations.as_table().ok_or_else(|| UniverseError::InvalidProperty {
+			block: name.to_string(),
+			property: "properties".to_string(),
+			reason: "must be a table".to_string()
+		})?;
+		
+		let mut properties = vec![];
+		for (property, declaration) in declarations {
+			properties.push(BlockProperty::from_config(property, declaration)
+				.map_err(|reason| UniverseError::InvalidProperty {
+					block: name.to_string(),
+					property: property.to_string(),
+					reason
+				})?
+			);
+		}
+		
+		Ok(properties)
+	}
+	
+	pub fn get_id(&self) -> BlockId {
+		self.id
+	}
+	
 	pub fn get_name(&self) -> &str {
 		return self.name.as_str();
 	}
@@ -101,20 +332,81 @@ impl Block {
 	pub fn get_default_state(&self) -> BlockState {
 		return self.default_state.clone();
 	}
+	
+	pub fn get_properties(&self) -> &[BlockProperty] {
+		&self.properties
+	}
+	
+	pub fn get_property_by_name(&self, name: &str) -> Option<&BlockProperty> {
+		self.properties.iter().find(|p| p.get_name() == name)
+	}
+	
+	pub fn get_attributes(&self) -> &BlockAttributes {
+		&self.attributes
+	}
+	
+	/// The number of distinct states of this block.
+	pub fn get_state_count(&self) -> usize {
+		self.state_count
+	}
+	
+	/// Iterates over every state of this block.
+	pub fn get_states<'a>(&'a self) -> impl Iterator<Item=BlockState> + 'a {
+		(0..self.state_count).map(move |data| BlockState {
+			id: self.id,
+			data: data as u16
+		})
+	}
+	
+	/// Returns the value of the named property in the given state.
+	pub fn get_property(&self, state: &BlockState, name: &str) -> Option<PropertyValue> {
+		if state.id != self.id {
+			return None;
+		}
+		
+		let property = self.get_property_by_name(name)?;
+		let index = (state.data as usize / property.stride) % property.get_value_count();
+		Some(property.value_at(index))
+	}
+	
+	/// Returns the given state with the named property changed to the given value.
+	pub fn with_property(&self, state: &BlockState, name: &str, value: &PropertyValue) -> Option<BlockState> {
+		if state.id != self.id {
+			return None;
+		}
+		
+		let property = self.get_property_by_name(name)?;
+		let new_index = property.index_of(value)?;
+		let old_index = (state.data as usize / property.stride) % property.get_value_count();
+		
+		let data = state.data as usize - old_index * property.stride + new_index * property.stride;
+		Some(BlockState {
+			id: self.id,
+			data: data as u16
+		})
+	}
+	
+	/// Formats a state of this block as `name[property=value,...]`.
+	pub fn state_to_string(&self, state: &BlockState) -> String {
+		if self.properties.is_empty() {
+			return self.name.clone();
+		}
+		
+		let properties = self.properties.iter()
+			.map(|p| format!("{}={}", p.get_name(), self.get_property(state, p.get_name()).unwrap_or(p.get_default())))
+			.collect::<Vec<String>>();
+		
+		format!("{}[{}]", self.name, properties.join(","))
+	}
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Copy, Clone, Eq, Debug)]
+/// A specific state of a block: its id, plus the packed values of its properties.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct BlockState {
 	pub id: BlockId,
-	pub data: ()
-}
-
-impl PartialEq for BlockState {
-	fn eq(&self, other: &BlockState) -> bool {
-		self.id == other.id
-	}
+	pub data: u16
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -122,20 +414,164 @@ impl PartialEq for BlockState {
 pub fn define_universe(
 	config: &toml::value::Table
 ) -> UniverseRef {
+	define_universe_with_ids(config, None)
+		.expect("Failed to define universe.")
+}
+
+/// Reads an id-mapping from a TOML file, as written by `Universe::write_id_mapping`.
+pub fn read_id_mapping(path: &Path) -> Result<toml::value::Table, UniverseError> {
+	let mapping = fs::read_to_string(path)?
+		.parse::<toml::Value>()
+		.map_err(UniverseError::MappingParse)?;
+	
+	match mapping {
+		toml::Value::Table(mapping) => Ok(mapping),
+		_ => Ok(toml::value::Table::new())
+	}
+}
+
+/// Defines the universe from the given config,
+/// keeping the ids of an earlier id-mapping if one is given.
+pub fn define_universe_with_ids(
+	config: &toml::value::Table,
+	mapping: Option<&toml::value::Table>
+) -> Result<UniverseRef, UniverseError> {
 	let mut universe = Universe::new();
 	
-	universe.register_block("air");
-	universe.register_block("bedrock");
+	if let Some(mapping) = mapping {
+		universe.load_id_mapping(mapping)?;
+	}
+	
+	let air = universe.register_block("air", None)?;
+	universe.blocks.get_mut(&air).unwrap().attributes = BlockAttributes::new_empty();
+	
+	universe.register_block("bedrock", None)?;
 	
 	if let Some(blocks) = config
 		.get("blocks")
 		.unwrap_or(&toml::Value::Boolean(false))
 		.as_table()
 	{
-		for (block_name, _block_info) in blocks {
-			universe.register_block(block_name);
+		for (block_name, block_info) in blocks {
+			universe.register_block(block_name, Some(block_info))?;
 		}
 	};
 	
-	return Rc::new(universe)
+	universe.freeze();
+	return Ok(Arc::new(universe))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn define(config: &str, mapping: Option<&toml::value::Table>) -> Result<UniverseRef, UniverseError> {
+		let config: toml::Value = config.parse().unwrap();
+		define_universe_with_ids(config.as_table().unwrap(), mapping)
+	}
+	
+	fn id(blockdef: &UniverseRef, name: &str) -> BlockId {
+		blockdef.get_block_by_name_unchecked(name).get_id()
+	}
+	
+	#[test]
+	fn ids_are_kept_with_a_mapping() {
+		let old = define("[blocks.bush]\n[blocks.stone]", None).unwrap();
+		let mapping = old.get_id_mapping();
+		
+		// `ash` is registered first, which would move every other block up by one.
+		let config = "[blocks.ash]\n[blocks.stone]\n[blocks.bush]";
+		assert!(id(&define(config, None).unwrap(), "bush") != id(&old, "bush"));
+		
+		let new = define(config, Some(&mapping)).unwrap();
+		for name in ["air", "bedrock", "bush", "stone"].iter() {
+			assert_eq!(id(&new, name), id(&old, name), "{}", name);
+		}
+		
+		// New blocks never take ids from the mapping, even of blocks that are gone.
+		let ash = id(&new, "ash").get_raw_id();
+		assert!(mapping.values().all(|id| id.as_integer() != Some(ash as i64)));
+		
+		let newer = define("[blocks.ash]", Some(&new.get_id_mapping())).unwrap();
+		assert_eq!(id(&newer, "ash"), id(&new, "ash"));
+		assert!(newer.get_block_by_name("stone").is_none());
+		assert_eq!(newer.get_id_mapping(), new.get_id_mapping());
+	}
+	
+	#[test]
+	fn mappings_round_trip_through_files() {
+		let blockdef = define("[blocks.stone]\n[blocks.dirt]", None).unwrap();
+		let path = std::env::temp_dir().join(format!("tcge-universe-{}.toml", std::process::id()));
+		
+		blockdef.write_id_mapping(&path).unwrap();
+		let mapping = read_id_mapping(&path).unwrap();
+		let _ = fs::remove_file(&path);
+		
+		assert_eq!(mapping, blockdef.get_id_mapping());
+	}
+	
+	#[test]
+	fn invalid_mappings_are_rejected() {
+		let mapping = |string: &str| string.parse::<toml::Value>().unwrap().as_table().unwrap().clone();
+		
+		match define("", Some(&mapping("stone = 1\ndirt = 1"))) {
+			Err(UniverseError::DuplicateId { .. }) => (),
+			_ => panic!("expected a duplicate id")
+		}
+		
+		for invalid in ["stone = -1", "stone = 65536", "stone = \"one\""].iter() {
+			match define("", Some(&mapping(invalid))) {
+				Err(UniverseError::InvalidMapping { .. }) => (),
+				_ => panic!("expected '{}' to be invalid", invalid)
+			}
+		}
+	}
+	
+	#[test]
+	fn blocks_are_only_registered_once() {
+		for name in ["air", "bedrock"].iter() {
+			match define(&format!("[blocks.{}]\nsolid = false", name), None) {
+				Err(UniverseError::DuplicateBlock { name: ref duplicate }) if duplicate == name => (),
+				_ => panic!("expected '{}' to be rejected", name)
+			}
+		}
+		
+		let mut universe = Universe::new();
+		universe.register_block("stone", None).unwrap();
+		match universe.register_block("stone", None) {
+			Err(UniverseError::DuplicateBlock { .. }) => (),
+			_ => panic!("expected a duplicate block")
+		}
+	}
+	
+	#[test]
+	fn blocks_can_not_be_registered_once_frozen() {
+		let mut universe = Universe::new();
+		universe.register_block("stone", None).unwrap();
+		universe.freeze();
+		
+		match universe.register_block("dirt", None) {
+			Err(UniverseError::Frozen { .. }) => (),
+			_ => panic!("expected the universe to be frozen")
+		}
+		assert!(universe.get_block_by_name("dirt").is_none());
+	}
+	
+	#[test]
+	fn ids_do_not_overflow() {
+		let mut mapping = toml::value::Table::new();
+		mapping.insert("last".to_string(), toml::Value::Integer(u16::max_value() as i64));
+		
+		let mut universe = Universe::new();
+		universe.load_id_mapping(&mapping).unwrap();
+		
+		// Blocks from the mapping still get their id...
+		assert_eq!(universe.register_block("last", None).unwrap(), BlockId::new(u16::max_value() as usize));
+		
+		// ...but there is none left for new ones.
+		match universe.register_block("stone", None) {
+			Err(UniverseError::TooManyBlocks { .. }) => (),
+			_ => panic!("expected the ids to run out")
+		}
+	}
 }
@@ -0,0 +1,450 @@
+//! The headless block-world: chunks of blocks, and the storage owning them.
+//!
+//! Nothing in here knows about rendering; the client only consumes it.
+
+use super::universe as blockdef;
+use super::BlockState;
+use super::BlockCoord;
+use super::BlockRaycast;
+use super::storage::BlockStorage;
+use super::storage::PaletteBlockStorage;
+use crate::util::current_time_nanos;
+use super::region::{self, RegionError};
+use super::snapshot::ChunkSnapshot;
+use super::light::{self, LightChannel, LightStorage};
+use super::generator::{self, GeneratorError, WorldGenerator};
+use rustc_hash::FxHashMap;
+use std::path::Path;
+use std::collections::hash_map::{Values, ValuesMut};
+
+pub const CHUNK_SIZE: usize = 16;
+pub const CHUNK_SIZE_SHIFT: usize = 4;
+pub const CHUNK_SIZE_MASK: usize = 0b1111;
+
+pub const CHUNK_SLICE: usize = CHUNK_SIZE*CHUNK_SIZE;
+pub const CHUNK_VOLUME: usize = CHUNK_SLICE*CHUNK_SIZE;
+
+#[derive(Eq, Copy, Clone)]
+pub struct ChunkCoord {
+	pub x: isize,
+	pub y: isize,
+	pub z: isize,
+}
+
+impl ChunkCoord {
+	pub fn new_from_chunk(x: isize, y: isize, z: isize) -> ChunkCoord {
+		ChunkCoord {
+			x, y, z
+		}
+	}
+	
+	pub fn new_from_block(pos: &BlockCoord) -> ChunkCoord {
+		ChunkCoord {
+			x: pos.x >> CHUNK_SIZE_SHIFT,
+			y: pos.y >> CHUNK_SIZE_SHIFT,
+			z: pos.z >> CHUNK_SIZE_SHIFT,
+		}
+	}
+	
+	pub fn as_vec(&self) -> cgmath::Vector3<f32> {
+		cgmath::Vector3 {
+			x: self.x as f32,
+			y: self.y as f32,
+			z: self.z as f32
+		}
+	}
+}
+
+impl PartialEq for ChunkCoord {
+	fn eq(&self, other: &ChunkCoord) -> bool {
+		self.x == other.x
+			&& self.y == other.y
+			&& self.z == other.z
+	}
+}
+
+impl std::hash::Hash for ChunkCoord {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		(self.x).hash(state);
+		(self.z).hash(state);
+		(self.y).hash(state);
+	}
+}
+
+impl std::fmt::Display for ChunkCoord {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(fmt, "[x: {}, y: {}, z: {}]",
+			self.x,
+			self.y,
+			self.z,
+		)
+	}
+}
+
+pub struct Chunk {
+	pub pos: ChunkCoord,
+	pub blockdef: blockdef::UniverseRef,
+	pub blocks: Box<BlockStorage>,
+	
+	/// Kept up to date by the chunk storage; dark while the chunk is not part of one.
+	pub light: LightStorage,
+	
+	pub last_update: u128,
+	
+	/// Whether blocks were changed since the chunk was generated or last saved.
+	/// Chunks that were not modified are not saved, as they can be loaded or generated again.
+	pub modified: bool,
+}
+
+impl Chunk {
+	
+	/// Creates a new chunk that is filled with nothing but air.
+	pub fn new_empty(blockdef: blockdef::UniverseRef, x: isize, y: isize, z: isize) -> Chunk {
+		let air = blockdef
+			.get_block_by_name_unchecked("air")
+			.get_default_state();
+		
+		Chunk {
+			pos: ChunkCoord {x,y,z},
+			blockdef,
+			blocks: Box::new(PaletteBlockStorage::new(CHUNK_VOLUME, air)),
+			light: LightStorage::new(CHUNK_VOLUME),
+			last_update: current_time_nanos(),
+			modified: false,
+		}
+	}
+	
+	pub fn clamp_chunk_coord(value: isize) -> Option<usize> {
+		if value < 0 {
+			return None
+		}
+		
+		if value >= CHUNK_SIZE as isize {
+			return None
+		}
+		
+		return Some(value as usize)
+	}
+	
+	pub fn fill_with_grid(&mut self, fill: BlockState) {
+		const I: isize = (CHUNK_SIZE - 1) as isize;
+		for i in 0..=I {
+			self.set_block(i,0,0,fill);
+			self.set_block(i,I,0,fill);
+			self.set_block(i,0,I,fill);
+			self.set_block(i,I,I,fill);
+			self.set_block(0,i,0,fill);
+			self.set_block(I,i,0,fill);
+			self.set_block(0,i,I,fill);
+			self.set_block(I,i,I,fill);
+			self.set_block(0,0,i,fill);
+			self.set_block(I,0,i,fill);
+			self.set_block(0,I,i,fill);
+			self.set_block(I,I,i,fill);
+		}
+	}
+	
+	pub fn get_block(&self, x: isize, y: isize, z: isize) -> Option<BlockState> {
+		let x = Chunk::clamp_chunk_coord(x)?;
+		let y = Chunk::clamp_chunk_coord(y)?;
+		let z = Chunk::clamp_chunk_coord(z)?;
+		
+		let index = y*CHUNK_SLICE + z*CHUNK_SIZE + x;
+		Some(self.blocks.get(index))
+	}
+	
+	pub fn set_block(&mut self, x: isize, y: isize, z: isize, state: BlockState) -> Option<()> {
+		let x = Chunk::clamp_chunk_coord(x)?;
+		let y = Chunk::clamp_chunk_coord(y)?;
+		let z = Chunk::clamp_chunk_coord(z)?;
+		
+		let index = y*CHUNK_SLICE + z*CHUNK_SIZE + x;
+		self.blocks.set(index, &state);
+		self.last_update = current_time_nanos();
+		self.modified = true;
+		Some(())
+	}
+	
+	/// Marks the chunk as changed, so everything derived from it gets rebuilt.
+	pub fn mark_dirty(&mut self) {
+		self.last_update = current_time_nanos();
+	}
+
+}
+
+/// Owns all loaded chunks of a world, indexed by their position.
+pub struct ChunkStorage {
+	blockdef: blockdef::UniverseRef,
+	chunks: FxHashMap<ChunkCoord, Chunk>,
+	generator: Box<WorldGenerator>,
+}
+
+impl ChunkStorage {
+	/// Creates an empty storage, with the generator of the `[generator]` config table.
+	pub fn new_empty(
+		blockdef: blockdef::UniverseRef,
+		config: &toml::value::Table
+	) -> Result<ChunkStorage, GeneratorError> {
+		Ok(ChunkStorage {
+			blockdef: blockdef.clone(),
+			chunks: FxHashMap::default(),
+			generator: generator::from_config(&blockdef, config.get("generator"))?,
+		})
+	}
+	
+	/// Creates the storage with the generator of the `[generator]` config table,
+	/// and generates a box of `range` chunks around the origin, `height` chunks high.
+	pub fn new(
+		blockdef: blockdef::UniverseRef,
+		config: &toml::value::Table
+	) -> Result<ChunkStorage, GeneratorError> {
+		let mut storage = ChunkStorage::new_empty(blockdef.clone(), config)?;
+		
+		let mut range = 4;
+		let mut height = 3;
+		
+		if let Some(rv) = config.get("range") {
+			if let Some(r) = rv.as_integer() {
+				range = r as isize;
+			}
+		}
+		
+		if let Some(hv) = config.get("height") {
+			if let Some(h) = hv.as_integer() {
+				height = h as isize;
+			}
+		}
+		
+		// Top to bottom, so the sky light of the lower chunks does not have to be taken back.
+		for y in (0..height).rev() {
+			for z in -range..range {
+				for x in -range..range {
+					let chunk = storage.generate_chunk(&ChunkCoord::new_from_chunk(x, y, z));
+					storage.insert_chunk(chunk);
+				}
+			}
+		}
+		
+		Ok(storage)
+	}
+	
+	pub fn get_blockdef(&self) -> &blockdef::UniverseRef {
+		&self.blockdef
+	}
+	
+	/// Generates the chunk at the given position, without inserting it.
+	pub fn generate_chunk(&self, cpos: &ChunkCoord) -> Chunk {
+		let mut chunk = Chunk::new_empty(self.blockdef.clone(), cpos.x, cpos.y, cpos.z);
+		self.generator.generate(&mut chunk);
+		chunk.modified = false;
+		chunk
+	}
+	
+	/// Inserts a chunk at its own position, returning the chunk it replaced (if any).
+	///
+	/// The chunk is lit, and the neighbours of the chunk are marked dirty, as their borders changed.
+	pub fn insert_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+		let cpos = chunk.pos;
+		let old = self.remove_chunk(&cpos);
+		self.chunks.insert(cpos, chunk);
+		light::light_chunk(self, &cpos);
+		self.mark_neighbours_dirty(&cpos);
+		old
+	}
+	
+	/// Removes and returns the chunk at the given position, if it exists.
+	///
+	/// The light of the chunk is taken out of its neighbours,
+	/// and they are marked dirty, as their borders changed.
+	pub fn remove_chunk(&mut self, cpos: &ChunkCoord) -> Option<Chunk> {
+		let old = self.chunks.remove(cpos)?;
+		light::unlight_chunk(self, &old);
+		self.mark_neighbours_dirty(cpos);
+		Some(old)
+	}
+	
+	/// Marks the 26 chunks sharing a face, edge or corner with the given chunk as dirty,
+	/// as the border of their snapshots includes all of them.
+	pub fn mark_neighbours_dirty(&mut self, cpos: &ChunkCoord) {
+		for x in -1..=1 {
+			for y in -1..=1 {
+				for z in -1..=1 {
+					if (x, y, z) == (0, 0, 0) {
+						continue;
+					}
+					
+					let npos = ChunkCoord::new_from_chunk(cpos.x + x, cpos.y + y, cpos.z + z);
+					if let Some(chunk) = self.chunks.get_mut(&npos) {
+						chunk.mark_dirty();
+					}
+				}
+			}
+		}
+	}
+	
+	/// Takes a snapshot of the chunk at the given position, including the border of its neighbours.
+	pub fn get_snapshot(&self, cpos: &ChunkCoord) -> Option<ChunkSnapshot> {
+		ChunkSnapshot::new(self, cpos)
+	}
+	
+	/// Borrow the chunk at the given position, if it exists.
+	pub fn get_chunk(&self, cpos: &ChunkCoord) -> Option<&Chunk> {
+		self.chunks.get(cpos)
+	}
+	
+	/// Mutably borrow the chunk at the given position, if it exists.
+	pub fn get_chunk_mut(&mut self, cpos: &ChunkCoord) -> Option<&mut Chunk> {
+		self.chunks.get_mut(cpos)
+	}
+	
+	/// Returns `true` if a chunk exists at the given position.
+	pub fn contains_chunk(&self, cpos: &ChunkCoord) -> bool {
+		self.chunks.contains_key(cpos)
+	}
+	
+	/// Returns the number of chunks held by this storage.
+	pub fn len(&self) -> usize {
+		self.chunks.len()
+	}
+	
+	/// Iterate over all chunks currently held by this storage, in no particular order.
+	pub fn iter(&self) -> Values<ChunkCoord, Chunk> {
+		self.chunks.values()
+	}
+	
+	/// Mutably iterate over all chunks currently held by this storage, in no particular order.
+	pub fn iter_mut(&mut self) -> ValuesMut<ChunkCoord, Chunk> {
+		self.chunks.values_mut()
+	}
+	
+	pub fn get_block(&self, pos: &BlockCoord) -> Option<BlockState> {
+		let cpos = ChunkCoord::new_from_block(pos);
+		let csm = CHUNK_SIZE_MASK as isize;
+		
+		let chunk = self.chunks.get(&cpos)?;
+		chunk.get_block(pos.x & csm, pos.y & csm, pos.z & csm)
+	}
+	
+	/// Returns the light level of the given channel at the given position, if its chunk is loaded.
+	pub fn get_light(&self, pos: &BlockCoord, channel: LightChannel) -> Option<u8> {
+		let cpos = ChunkCoord::new_from_block(pos);
+		let csm = CHUNK_SIZE_MASK as isize;
+		let (x, y, z) = ((pos.x & csm) as usize, (pos.y & csm) as usize, (pos.z & csm) as usize);
+		
+		let chunk = self.chunks.get(&cpos)?;
+		Some(chunk.light.get(y*CHUNK_SLICE + z*CHUNK_SIZE + x, channel))
+	}
+	
+	/// Sets the block at the given position, if its chunk is loaded, and updates the light around it.
+	///
+	/// Changing a block on the border of a chunk also marks the chunks across that border dirty,
+	/// including the ones across its edges and corners, up to seven in total.
+	pub fn set_block(&mut self, pos: &BlockCoord, state: BlockState) -> bool {
+		let cpos = ChunkCoord::new_from_block(pos);
+		let csm = CHUNK_SIZE_MASK as isize;
+		let (x, y, z) = (pos.x & csm, pos.y & csm, pos.z & csm);
+		
+		match self.chunks.get_mut(&cpos) {
+			Some(chunk) => {
+				chunk.set_block(x, y, z, state);
+			},
+			None => return false
+		}
+		
+		let border = |v: isize| if v == 0 { -1 } else if v == csm { 1 } else { 0 };
+		let (bx, by, bz) = (border(x), border(y), border(z));
+		
+		// Every combination of the borders the block is on: the chunks across faces, edges and corners.
+		let offsets = |b: isize| if b == 0 { vec![0] } else { vec![0, b] };
+		for ox in offsets(bx) {
+			for oy in offsets(by) {
+				for oz in offsets(bz) {
+					if (ox, oy, oz) == (0, 0, 0) {
+						continue;
+					}
+					
+					let npos = ChunkCoord::new_from_chunk(cpos.x + ox, cpos.y + oy, cpos.z + oz);
+					if let Some(chunk) = self.chunks.get_mut(&npos) {
+						chunk.mark_dirty();
+					}
+				}
+			}
+		}
+		
+		light::update_block(self, pos.x, pos.y, pos.z);
+		true
+	}
+	
+	/// Saves the modified chunks into region files within the given directory,
+	/// keeping the chunks of those regions that are saved but not loaded.
+	/// Returns the number of region files written.
+	pub fn save_regions(&mut self, dir: &Path) -> Result<usize, RegionError> {
+		let chunks: Vec<&Chunk> = self.chunks.values().filter(|chunk| chunk.modified).collect();
+		info!("Saving {} modified chunks to: {}", chunks.len(), dir.to_str().unwrap_or("ERROR"));
+		
+		let regions = region::save_chunks(dir, &chunks, &self.blockdef)?;
+		
+		for chunk in self.chunks.values_mut() {
+			chunk.modified = false;
+		}
+		
+		Ok(regions)
+	}
+	
+	/// Loads all region files within the given directory,
+	/// replacing any chunks that already exist at the same positions.
+	/// Returns the number of chunks loaded.
+	pub fn load_regions(&mut self, dir: &Path) -> Result<usize, RegionError> {
+		info!("Loading regions from: {}", dir.to_str().unwrap_or("ERROR"));
+		
+		let mut count = 0;
+		for entry in std::fs::read_dir(dir)? {
+			let path = entry?.path();
+			if ! region::is_region_file(&path) {
+				continue;
+			}
+			
+			let (_, chunks) = region::read_region(&path, &self.blockdef)?;
+			for chunk in chunks {
+				self.insert_chunk(chunk);
+				count += 1;
+			}
+		}
+		
+		Ok(count)
+	}
+	
+	/// Steps the raycast until it hits a selectable block,
+	/// returning the position before the hit, the position of the hit and the block hit.
+	pub fn raycast(&mut self, raycast: &mut BlockRaycast) -> Option<(BlockCoord, BlockCoord, BlockState)> {
+		loop {
+			let (lx, ly, lz) = raycast.previous();
+			
+			let (cx, cy, cz) = match raycast.step() {
+				Some(pos) => pos,
+				None => break
+			};
+			
+			let last_pos = BlockCoord::new(lx, ly, lz);
+			let pos = BlockCoord::new(cx, cy, cz);
+			
+			match self.get_block(&pos) {
+				Some(block) => {
+					if self.blockdef.get_attributes(&block).is_selectable() {
+						return Some((last_pos, pos, block))
+					}
+				}
+				_ => ()
+			}
+		}
+		
+		return None;
+	}
+	
+	pub fn raycast_fill(&mut self, raycast: &mut BlockRaycast, state: BlockState) {
+		while let Some((x,y,z)) = raycast.step() {
+			let pos = BlockCoord::new(x, y, z);
+			self.set_block(&pos, state);
+		}
+	}
+}
@@ -0,0 +1,179 @@
+//! The table of block textures within the texture atlas.
+
+use rustc_hash::FxHashMap;
+use super::super::super::blocks as blockdef;
+use super::super::super::blocks::BlockFace;
+
+/// Name of the texture used for faces whose texture is not in the atlas.
+pub const MISSING_TEXTURE: &str = "missing";
+
+/// The texture coordinates of a single texture within the atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockUv {
+	pub umin: f32,
+	pub umax: f32,
+	pub vmin: f32,
+	pub vmax: f32,
+}
+
+impl BlockUv {
+	/// The texture coordinates of the given rectangle, in an atlas of the given size in pixels.
+	pub fn new_from_rect(rect: &AtlasRect, width: u32, height: u32) -> Self {
+		let w = width as f32;
+		let h = height as f32;
+		Self {
+			umin: rect.x as f32 / w,
+			umax: (rect.x + rect.width) as f32 / w,
+			vmin: rect.y as f32 / h,
+			vmax: (rect.y + rect.height) as f32 / h,
+		}
+	}
+}
+
+/// The location of a single texture within the atlas, in pixels.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AtlasRect {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+/// Maps texture names to their location within the atlas.
+///
+/// The table can be written to and read from a TOML index:
+///
+/// ```toml
+/// width = 64
+/// height = 64
+///
+/// [textures]
+/// bedrock = [0, 0, 16, 16] # x, y, width, height
+/// ```
+pub struct TextureAtlas {
+	width: u32,
+	height: u32,
+	rects: FxHashMap<String, AtlasRect>,
+}
+
+impl TextureAtlas {
+	/// Creates an empty table for an atlas of the given size in pixels.
+	pub fn new(width: u32, height: u32) -> TextureAtlas {
+		TextureAtlas {
+			width,
+			height,
+			rects: FxHashMap::default(),
+		}
+	}
+	
+	/// Builds the table from a parsed atlas index.
+	pub fn from_index(index: &toml::Value) -> Result<TextureAtlas, String> {
+		let width = index.get("width")
+			.and_then(|t| t.as_integer())
+			.filter(|t| *t > 0)
+			.ok_or("'width' must be a positive integer")? as u32;
+		
+		let height = index.get("height")
+			.and_then(|t| t.as_integer())
+			.filter(|t| *t > 0)
+			.ok_or("'height' must be a positive integer")? as u32;
+		
+		let mut atlas = TextureAtlas::new(width, height);
+		
+		if let Some(entries) = index.get("textures").and_then(|t| t.as_table()) {
+			for (name, rect) in entries {
+				let rect = rect.as_array()
+					.filter(|rect| rect.len() == 4)
+					.ok_or_else(|| format!("rectangle of texture '{}' must be an array of four integers", name))?;
+				
+				let mut values = [0u32; 4];
+				for (value, entry) in values.iter_mut().zip(rect.iter()) {
+					*value = entry.as_integer()
+						.filter(|v| *v >= 0)
+						.ok_or_else(|| format!("rectangle of texture '{}' must be an array of four integers", name))? as u32;
+				}
+				
+				let rect = AtlasRect { x: values[0], y: values[1], width: values[2], height: values[3] };
+				if rect.x + rect.width > width || rect.y + rect.height > height {
+					return Err(format!("rectangle of texture '{}' is outside of the atlas", name));
+				}
+				
+				atlas.insert(name, rect);
+			}
+		}
+		
+		Ok(atlas)
+	}
+	
+	/// Returns the atlas index of this table, as read by `from_index`.
+	pub fn to_index(&self) -> toml::Value {
+		let mut textures = toml::value::Table::new();
+		for (name, rect) in self.rects.iter() {
+			textures.insert(name.clone(), toml::Value::Array(vec![
+				toml::Value::Integer(rect.x as i64),
+				toml::Value::Integer(rect.y as i64),
+				toml::Value::Integer(rect.width as i64),
+				toml::Value::Integer(rect.height as i64),
+			]));
+		}
+		
+		let mut index = toml::value::Table::new();
+		index.insert("width".to_string(), toml::Value::Integer(self.width as i64));
+		index.insert("height".to_string(), toml::Value::Integer(self.height as i64));
+		index.insert("textures".to_string(), toml::Value::Table(textures));
+		toml::Value::Table(index)
+	}
+	
+	pub fn insert(&mut self, name: &str, rect: AtlasRect) {
+		self.rects.insert(name.to_string(), rect);
+	}
+	
+	pub fn get_size(&self) -> (u32, u32) {
+		(self.width, self.height)
+	}
+	
+	pub fn get_rect(&self, name: &str) -> Option<AtlasRect> {
+		self.rects.get(name).cloned()
+	}
+	
+	pub fn get_uv(&self, name: &str) -> Option<BlockUv> {
+		self.rects.get(name).map(|rect| BlockUv::new_from_rect(rect, self.width, self.height))
+	}
+	
+	/// Returns the texture coordinates of the named texture,
+	/// or those of the missing-texture if it is not in the atlas.
+	pub fn get_uv_or_missing(&self, name: &str) -> BlockUv {
+		self.get_uv(name)
+			.or_else(|| self.get_uv(MISSING_TEXTURE))
+			.unwrap_or(BlockUv { umin: 0.0, umax: 0.0, vmin: 0.0, vmax: 0.0 })
+	}
+	
+	/// Resolves the face textures of every visible block in the universe.
+	pub fn bake_faces(&self, blockdef: &blockdef::UniverseRef) -> FxHashMap<blockdef::BlockId, [BlockUv; 6]> {
+		let mut faces = FxHashMap::default();
+		let missing = self.get_uv_or_missing(MISSING_TEXTURE);
+		
+		for (id, block) in blockdef.list_blocks() {
+			let attributes = block.get_attributes();
+			if ! attributes.is_visible() {
+				continue;
+			}
+			
+			let mut uvs = [missing; 6];
+			for (uv, face) in uvs.iter_mut().zip(BlockFace::ALL.iter()) {
+				let texture = attributes.get_texture(*face).unwrap_or(MISSING_TEXTURE);
+				*uv = match self.get_uv(texture) {
+					Some(uv) => uv,
+					None => {
+						warn!("Block '{}' uses texture '{}', which is not in the atlas.", block.get_name(), texture);
+						missing
+					}
+				};
+			}
+			
+			faces.insert(*id, uvs);
+		}
+		
+		faces
+	}
+}
@@ -0,0 +1,333 @@
+//! Bakes the individual block textures into a single texture atlas.
+//!
+//! Block textures are PNG files in `textures/blocks/`, named after the texture
+//! (`textures/blocks/bedrock.png` for `bedrock`). The bakery only works on images
+//! in memory, so baking runs without an OpenGL context; uploading the result is
+//! left to the renderer.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use super::super::super::resources;
+use super::super::super::blocks as blockdef;
+use super::atlas::{AtlasRect, TextureAtlas, MISSING_TEXTURE};
+use super::model::BlockModels;
+
+/// The resource directory block textures are loaded from.
+pub const BLOCK_TEXTURE_DIR: &str = "textures/blocks";
+
+/// The largest atlas the bakery will create, in pixels per side.
+pub const MAX_ATLAS_SIZE: u32 = 8192;
+
+#[derive(Debug, Fail)]
+pub enum BakeryError {
+	#[fail(display = "Failed to load texture {}", name)]
+	ResourceLoad { name: String, #[cause] inner: resources::ResError },
+	
+	#[fail(display = "Failed to parse texture {}", name)]
+	ImageParse { name: String, #[cause] inner: image::ImageError },
+	
+	#[fail(display = "Textures do not fit into an atlas of {}x{} pixels", size, size)]
+	TooLarge { size: u32 },
+	
+	#[fail(display = "I/O error")]
+	Io(#[cause] io::Error),
+	
+	#[fail(display = "Failed to write atlas index")]
+	IndexWrite(#[cause] toml::ser::Error),
+}
+
+impl From<io::Error> for BakeryError {
+	fn from(other: io::Error) -> Self {
+		BakeryError::Io(other)
+	}
+}
+
+/// Collects block textures and packs them into an atlas.
+pub struct TextureBakery {
+	textures: Vec<(String, image::RgbaImage)>,
+	max_size: u32,
+}
+
+impl TextureBakery {
+	pub fn new() -> TextureBakery {
+		TextureBakery {
+			textures: vec![],
+			max_size: MAX_ATLAS_SIZE,
+		}
+	}
+	
+	/// Limits the size of the atlas, in pixels per side.
+	pub fn set_max_size(&mut self, max_size: u32) {
+		self.max_size = max_size;
+	}
+	
+	/// Adds a texture, replacing any earlier texture with the same name.
+	pub fn add_texture(&mut self, name: &str, image: image::RgbaImage) {
+		match self.textures.iter_mut().find(|(n, _)| n == name) {
+			Some(entry) => entry.1 = image,
+			None => self.textures.push((name.to_string(), image))
+		}
+	}
+	
+	pub fn contains_texture(&self, name: &str) -> bool {
+		self.textures.iter().any(|(n, _)| n == name)
+	}
+	
+	/// Loads the named texture from `textures/blocks/<name>.png`.
+	pub fn load_texture(&mut self, res: &resources::Resources, name: &str) -> Result<(), BakeryError> {
+		let path = format!("{}/{}.png", BLOCK_TEXTURE_DIR, name);
+		
+		let buffer = res.load_buffer(&path)
+			.map_err(|e| BakeryError::ResourceLoad { name: path.clone(), inner: e })?;
+		
+		let image = image::load_from_memory(&buffer)
+			.map_err(|e| BakeryError::ImageParse { name: path.clone(), inner: e })?;
+		
+		self.add_texture(name, image.to_rgba());
+		Ok(())
+	}
+	
+	/// Loads every texture used by the blocks of the given universe, plus the missing-texture.
+	///
+	/// Textures that fail to load are skipped with a warning,
+	/// so the faces using them end up with the missing-texture.
+	pub fn collect_textures(&mut self, res: &resources::Resources, blockdef: &blockdef::UniverseRef) {
+		if let Err(e) = self.load_texture(res, MISSING_TEXTURE) {
+			warn!("Failed to load the missing-texture, generating one: {}", e);
+			self.add_texture(MISSING_TEXTURE, Self::generate_missing_texture(16));
+		}
+		
+		for (_, block) in blockdef.list_blocks() {
+			let textures = match &block.get_attributes().textures {
+				Some(textures) => textures,
+				None => continue
+			};
+			
+			for texture in textures.iter() {
+				if self.contains_texture(texture) {
+					continue;
+				}
+				
+				if let Err(e) = self.load_texture(res, texture) {
+					warn!("Failed to load texture '{}' of block '{}': {}", texture, block.get_name(), e);
+				}
+			}
+		}
+	}
+	
+	/// Loads every texture the given block models set explicitly.
+	///
+	/// Like `collect_textures`, textures that fail to load are skipped with a warning.
+	pub fn collect_model_textures(&mut self, res: &resources::Resources, models: &BlockModels) {
+		for (name, model) in models.iter() {
+			for texture in model.get_textures() {
+				if self.contains_texture(texture) {
+					continue;
+				}
+				
+				if let Err(e) = self.load_texture(res, texture) {
+					warn!("Failed to load texture '{}' of model '{}': {}", texture, name, e);
+				}
+			}
+		}
+	}
+	
+	/// A magenta and black checkerboard.
+	pub fn generate_missing_texture(size: u32) -> image::RgbaImage {
+		image::ImageBuffer::from_fn(size, size, |x, y| {
+			if (x * 2 / size + y * 2 / size) % 2 == 0 {
+				image::Rgba([255, 0, 255, 255])
+			} else {
+				image::Rgba([0, 0, 0, 255])
+			}
+		})
+	}
+	
+	/// Packs all textures into a single image, along with the table of their locations.
+	///
+	/// The atlas is the smallest square with a power-of-two side the textures fit into.
+	pub fn bake(&self) -> Result<BakedAtlas, BakeryError> {
+		let mut size = self.textures.iter()
+			.map(|(_, image)| image.width().max(image.height()))
+			.max()
+			.unwrap_or(1)
+			.next_power_of_two();
+		
+		let rects = loop {
+			if size > self.max_size {
+				return Err(BakeryError::TooLarge { size: self.max_size });
+			}
+			
+			if let Some(rects) = self.pack(size) {
+				break rects;
+			}
+			
+			size *= 2;
+		};
+		
+		debug!("Baking {} block textures into an atlas of {}x{} pixels...", self.textures.len(), size, size);
+		
+		let mut image = image::RgbaImage::new(size, size);
+		let mut atlas = TextureAtlas::new(size, size);
+		
+		for ((name, texture), rect) in self.textures.iter().zip(rects.iter()) {
+			image::imageops::replace(&mut image, texture, rect.x, rect.y);
+			atlas.insert(name, *rect);
+		}
+		
+		Ok(BakedAtlas {
+			image,
+			atlas
+		})
+	}
+	
+	/// Places the textures in rows ('shelves'), tallest first.
+	///
+	/// Returns the rectangle of every texture, in the order they were added,
+	/// or `None` if they do not fit into a square of the given size.
+	fn pack(&self, size: u32) -> Option<Vec<AtlasRect>> {
+		let mut order: Vec<usize> = (0..self.textures.len()).collect();
+		order.sort_by(|a, b| {
+			let (name_a, a) = &self.textures[*a];
+			let (name_b, b) = &self.textures[*b];
+			b.height().cmp(&a.height())
+				.then(b.width().cmp(&a.width()))
+				.then(name_a.cmp(name_b))
+		});
+		
+		let mut rects = vec![AtlasRect { x: 0, y: 0, width: 0, height: 0 }; self.textures.len()];
+		
+		let mut shelf_x = 0;
+		let mut shelf_y = 0;
+		let mut shelf_height = 0;
+		
+		for index in order {
+			let (width, height) = self.textures[index].1.dimensions();
+			
+			if shelf_x + width > size {
+				shelf_x = 0;
+				shelf_y += shelf_height;
+				shelf_height = 0;
+			}
+			
+			if shelf_x + width > size || shelf_y + height > size {
+				return None;
+			}
+			
+			rects[index] = AtlasRect { x: shelf_x, y: shelf_y, width, height };
+			shelf_x += width;
+			shelf_height = shelf_height.max(height);
+		}
+		
+		Some(rects)
+	}
+}
+
+/// The result of baking: the atlas image and the table of textures within it.
+pub struct BakedAtlas {
+	pub image: image::RgbaImage,
+	pub atlas: TextureAtlas,
+}
+
+impl BakedAtlas {
+	/// Writes the atlas as `atlas.png` and its index as `atlas.toml` into the given directory.
+	pub fn dump(&self, dir: &Path) -> Result<(), BakeryError> {
+		fs::create_dir_all(dir)?;
+		
+		self.image.save(dir.join("atlas.png"))?;
+		
+		let index = toml::to_string(&self.atlas.to_index())
+			.map_err(BakeryError::IndexWrite)?;
+		fs::write(dir.join("atlas.toml"), index)?;
+		
+		info!("Dumped block texture atlas to: {}", dir.to_str().unwrap_or("ERROR"));
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	/// A mix of texture sizes, with the index of the texture in the red channel.
+	fn bakery() -> TextureBakery {
+		let sizes = [(16, 16), (16, 16), (32, 32), (8, 8), (16, 32), (64, 16), (8, 8), (16, 16), (32, 8), (4, 4), (16, 16)];
+		
+		let mut bakery = TextureBakery::new();
+		for (index, (width, height)) in sizes.iter().enumerate() {
+			let image = image::RgbaImage::from_pixel(*width, *height, image::Rgba([index as u8, 0, 0, 255]));
+			bakery.add_texture(&format!("texture{}", index), image);
+		}
+		bakery
+	}
+	
+	fn overlaps(a: &AtlasRect, b: &AtlasRect) -> bool {
+		a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+	}
+	
+	#[test]
+	fn packed_textures_do_not_overlap() {
+		let bakery = bakery();
+		let baked = bakery.bake().unwrap();
+		let (width, height) = baked.atlas.get_size();
+		assert_eq!((width, height), (128, 128));
+		
+		let rects: Vec<AtlasRect> = bakery.textures.iter()
+			.map(|(name, _)| baked.atlas.get_rect(name).unwrap())
+			.collect();
+		
+		for (index, (rect, (_, texture))) in rects.iter().zip(bakery.textures.iter()).enumerate() {
+			assert_eq!((rect.width, rect.height), texture.dimensions());
+			assert!(rect.x + rect.width <= width && rect.y + rect.height <= height, "{:?} is outside", rect);
+			
+			for other in rects[index + 1..].iter() {
+				assert!(!overlaps(rect, other), "{:?} overlaps {:?}", rect, other);
+			}
+			
+			// The corners of every rectangle hold the texture that was placed there.
+			assert_eq!(baked.image.get_pixel(rect.x, rect.y)[0], index as u8);
+			assert_eq!(baked.image.get_pixel(rect.x + rect.width - 1, rect.y + rect.height - 1)[0], index as u8);
+		}
+	}
+	
+	#[test]
+	fn uvs_match_the_packed_rects() {
+		let bakery = bakery();
+		let baked = bakery.bake().unwrap();
+		
+		for (name, _) in bakery.textures.iter() {
+			let rect = baked.atlas.get_rect(name).unwrap();
+			let uv = baked.atlas.get_uv(name).unwrap();
+			
+			assert_eq!(uv.umin, rect.x as f32 / 128.0);
+			assert_eq!(uv.umax, (rect.x + rect.width) as f32 / 128.0);
+			assert_eq!(uv.vmin, rect.y as f32 / 128.0);
+			assert_eq!(uv.vmax, (rect.y + rect.height) as f32 / 128.0);
+			assert!(uv.umin >= 0.0 && uv.umax <= 1.0 && uv.vmin >= 0.0 && uv.vmax <= 1.0);
+		}
+		
+		assert_eq!(baked.atlas.get_uv("unknown"), None);
+	}
+	
+	#[test]
+	fn textures_that_do_not_fit_are_an_error() {
+		let mut bakery = bakery();
+		bakery.set_max_size(64);
+		
+		match bakery.bake() {
+			Err(BakeryError::TooLarge { size }) => assert_eq!(size, 64),
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("textures should not fit")
+		}
+		
+		// A single texture wider than the atlas can not fit either.
+		let mut bakery = TextureBakery::new();
+		bakery.add_texture("wide", image::RgbaImage::new(128, 1));
+		bakery.set_max_size(64);
+		assert!(bakery.bake().is_err());
+		
+		assert!(bakery.pack(64).is_none());
+		assert!(bakery.pack(128).is_some());
+	}
+}
@@ -0,0 +1,830 @@
+//! Turns the blocks of a chunk into lists of vertices, one per render layer.
+//!
+//! Full cubes are meshed face by face, while blocks with a model get the quads of their model,
+//! which are baked once when the mesher is created.
+//!
+//! Meshing does not touch OpenGL at all; uploading the vertices is left to the renderer.
+
+use rustc_hash::FxHashMap;
+use super::super::super::blocks as blockdef;
+use super::super::super::blocks::ChunkSnapshot;
+use super::super::super::blocks::world::CHUNK_SIZE;
+use super::super::super::blocks::{BlockFace, BlockId, BlockState, RenderLayer};
+use super::super::super::blocks::light::{LightChannel, MAX_LIGHT};
+use super::super::settings;
+use super::atlas::{BlockUv, TextureAtlas, MISSING_TEXTURE};
+use super::model::{BlockModel, BlockModels};
+
+/// How the faces of a chunk are turned into quads.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MeshingMode {
+	/// One quad per visible face.
+	Naive,
+	
+	/// Coplanar, adjacent faces with the same texture are merged into larger quads.
+	Greedy,
+}
+
+impl MeshingMode {
+	/// Reads the `meshing` value of the `chunk-rendering` settings, defaulting to `Greedy`.
+	pub fn from_settings(settings: &settings::Settings) -> MeshingMode {
+		let mode = settings.table.get("chunk-rendering")
+			.and_then(|v| v.as_table())
+			.and_then(|t| t.get("meshing"))
+			.and_then(|v| v.as_str());
+		
+		match mode {
+			Some("naive") => MeshingMode::Naive,
+			Some("greedy") | None => MeshingMode::Greedy,
+			Some(other) => {
+				warn!("Unknown meshing mode '{}', using greedy meshing.", other);
+				MeshingMode::Greedy
+			}
+		}
+	}
+}
+
+/// The layout of a face: the axis it faces along, and the axes its texture runs along.
+///
+/// Axes are given as an index (0 = x, 1 = y, 2 = z) and a direction.
+struct FaceLayout {
+	normal: (usize, bool),
+	u: (usize, bool),
+	v: (usize, bool),
+}
+
+impl FaceLayout {
+	fn of(face: BlockFace) -> FaceLayout {
+		let (normal, u, v) = match face {
+			BlockFace::Top    => ((1, true ), (0, true ), (2, false)),
+			BlockFace::Bottom => ((1, false), (0, true ), (2, true )),
+			BlockFace::North  => ((2, false), (0, true ), (1, false)),
+			BlockFace::South  => ((2, true ), (0, true ), (1, true )),
+			BlockFace::West   => ((0, false), (2, false), (1, false)),
+			BlockFace::East   => ((0, true ), (2, false), (1, true )),
+		};
+		FaceLayout { normal, u, v }
+	}
+	
+	/// The offset towards the corner `(su, sv)` of a face, where `(0, 0)` is the corner its texture begins at.
+	fn get_corner_offset(&self, su: usize, sv: usize) -> [isize; 3] {
+		let mut offset = [0; 3];
+		offset[self.u.0] = if (su == 1) == self.u.1 {1} else {-1};
+		offset[self.v.0] = if (sv == 1) == self.v.1 {1} else {-1};
+		offset
+	}
+}
+
+/// Everything needed to emit a visible face; only faces that are equal in all of this can be merged.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct FaceShading {
+	uv: BlockUv,
+	
+	layer: RenderLayer,
+	
+	/// Ambient occlusion of the four corners, from 0 (fully occluded) to 3.
+	ao: [u8; 4],
+	
+	/// Light of the four corners, from 0.0 to 1.0.
+	light: [f32; 4],
+}
+
+/// A face of a block model, baked for a single block.
+#[derive(Clone, Debug)]
+struct ModelQuad {
+	/// Block-local positions of the corners, in the order of `QUAD_CORNERS`.
+	corners: [[f32; 3]; 4],
+	
+	/// Texture coordinates of the corners within their tile, from 0 to 1.
+	uvs: [[f32; 2]; 4],
+	
+	tile: BlockUv,
+	
+	/// The side of the block the face is hidden on by an opaque neighbour, if any;
+	/// such faces are lit by that neighbour instead of the block itself.
+	cull: Option<BlockFace>,
+}
+
+/// The corners of a quad, in the order `FaceShading` stores them: `(su, sv)`.
+const QUAD_CORNERS: [(usize, usize); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+/// The ambient occlusion of a vertex, from the blocks next to it on the two sides and on the corner,
+/// all in the layer in front of the face: 3 if none are opaque, down to 0 if it sits in an inner corner.
+///
+/// Two opaque sides hide the corner block entirely, so it does not matter in that case.
+pub fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+	if side1 && side2 {
+		return 0;
+	}
+	3 - (side1 as u8 + side2 as u8 + corner as u8)
+}
+
+pub struct ChunkMesher {
+	blockdef: blockdef::UniverseRef,
+	
+	/// Texture coordinates of the faces of every visible block.
+	faces: FxHashMap<BlockId, [BlockUv; 6]>,
+	
+	/// The baked model of every block that is not a full cube.
+	models: FxHashMap<BlockId, Vec<ModelQuad>>,
+	
+	mode: MeshingMode,
+}
+
+impl ChunkMesher {
+	
+	pub fn new(blockdef: blockdef::UniverseRef, atlas: &TextureAtlas, models: &BlockModels, mode: MeshingMode) -> ChunkMesher {
+		let mut baked = FxHashMap::default();
+		for (id, block) in blockdef.list_blocks() {
+			if let Some(model) = models.get_block_model(*id) {
+				baked.insert(*id, Self::bake_model(model, block.get_attributes(), atlas));
+			}
+		}
+		
+		ChunkMesher {
+			faces: atlas.bake_faces(&blockdef),
+			models: baked,
+			blockdef,
+			mode,
+		}
+	}
+	
+	/// Turns the boxes of a model into quads, with the textures of the given block.
+	fn bake_model(model: &BlockModel, attributes: &blockdef::BlockAttributes, atlas: &TextureAtlas) -> Vec<ModelQuad> {
+		let mut quads = vec![];
+		
+		for model_box in model.boxes.iter() {
+			let (from, to) = (model_box.from, model_box.to);
+			
+			for face in BlockFace::ALL.iter() {
+				let model_face = match model_box.get_face(*face) {
+					Some(model_face) => model_face,
+					None => continue
+				};
+				
+				// The same corners a full cube would have, shrunk to the box.
+				let layout = FaceLayout::of(*face);
+				let (n, n_positive) = layout.normal;
+				let (u, u_positive) = layout.u;
+				let (v, v_positive) = layout.v;
+				
+				let mut origin = [0.0f32; 3];
+				origin[n] = if n_positive {to[n]} else {from[n]};
+				origin[u] = if u_positive {from[u]} else {to[u]};
+				origin[v] = if v_positive {from[v]} else {to[v]};
+				
+				let (du, dv) = (to[u] - from[u], to[v] - from[v]);
+				
+				let mut corners = [[0.0f32; 3]; 4];
+				for (corner, (su, sv)) in corners.iter_mut().zip(QUAD_CORNERS.iter()) {
+					let (su, sv) = (*su as f32, *sv as f32);
+					*corner = origin;
+					corner[u] += if u_positive {du} else {-du} * su;
+					corner[v] += if v_positive {dv} else {-dv} * sv;
+					
+					if let Some(rotation) = &model_box.rotation {
+						*corner = rotation.apply(*corner);
+					}
+				}
+				
+				// Without explicit coordinates, the box shows the part of the texture a full cube would.
+				let uv = model_face.uv.unwrap_or_else(|| {
+					let u0 = if u_positive {from[u]} else {1.0 - to[u]};
+					let v0 = if v_positive {from[v]} else {1.0 - to[v]};
+					[u0, v0, u0 + du, v0 + dv]
+				});
+				
+				let mut uvs = [[0.0f32; 2]; 4];
+				for (index, uv_corner) in uvs.iter_mut().enumerate() {
+					let (su, sv) = QUAD_CORNERS[(index + model_face.rotation as usize) % 4];
+					let (su, sv) = (su as f32, sv as f32);
+					*uv_corner = [uv[0] + (uv[2] - uv[0]) * su, uv[1] + (uv[3] - uv[1]) * sv];
+				}
+				
+				let texture = model_face.texture.as_ref()
+					.map(|texture| texture.as_str())
+					.or_else(|| attributes.get_texture(*face))
+					.unwrap_or(MISSING_TEXTURE);
+				
+				quads.push(ModelQuad {
+					corners,
+					uvs,
+					tile: atlas.get_uv_or_missing(texture),
+					cull: if model_face.cull {Some(*face)} else {None},
+				});
+			}
+		}
+		
+		quads
+	}
+	
+	pub fn get_mode(&self) -> MeshingMode {
+		self.mode
+	}
+	
+	/// Builds the vertices of the chunk in the given snapshot, two triangles per quad,
+	/// sorted into the layers of their blocks.
+	///
+	/// Faces towards the neighbouring chunks are culled using the border of the snapshot.
+	pub fn mesh(&self, chunk: &ChunkSnapshot) -> ChunkMeshData {
+		let mut mesh = ChunkMeshData::new();
+		
+		match self.mode {
+			MeshingMode::Naive => self.mesh_naive(chunk, &mut mesh),
+			MeshingMode::Greedy => self.mesh_greedy(chunk, &mut mesh),
+		}
+		
+		if ! self.models.is_empty() {
+			self.mesh_models(chunk, &mut mesh);
+		}
+		
+		let offset = [
+			(chunk.pos.x * CHUNK_SIZE as isize) as f32,
+			(chunk.pos.y * CHUNK_SIZE as isize) as f32,
+			(chunk.pos.z * CHUNK_SIZE as isize) as f32,
+		];
+		
+		for vertices in mesh.layers.iter_mut() {
+			for vertex in vertices.iter_mut() {
+				vertex.x += offset[0];
+				vertex.y += offset[1];
+				vertex.z += offset[2];
+			}
+		}
+		
+		mesh
+	}
+	
+	fn mesh_naive(&self, chunk: &ChunkSnapshot, mesh: &mut ChunkMeshData) {
+		let air = self.get_air();
+		
+		for y in 0..CHUNK_SIZE {
+			for z in 0..CHUNK_SIZE {
+				for x in 0..CHUNK_SIZE {
+					let pos = [x, y, z];
+					for face in BlockFace::ALL.iter() {
+						let layout = FaceLayout::of(*face);
+						if let Some(shading) = self.get_face(chunk, air, pos, *face, &layout) {
+							let (u, v) = (layout.u.0, layout.v.0);
+							Self::push_quad(&layout, pos[layout.normal.0], (pos[u], pos[u] + 1), (pos[v], pos[v] + 1), &shading, mesh.get_mut(shading.layer));
+						}
+					}
+				}
+			}
+		}
+	}
+	
+	/// Sweeps every slice of the chunk once per face direction,
+	/// growing each visible face first along `u` and then along `v` as far as texture and shading stay the same.
+	fn mesh_greedy(&self, chunk: &ChunkSnapshot, mesh: &mut ChunkMeshData) {
+		let air = self.get_air();
+		let mut mask: Vec<Option<FaceShading>> = vec![None; CHUNK_SIZE * CHUNK_SIZE];
+		
+		for face in BlockFace::ALL.iter() {
+			let layout = FaceLayout::of(*face);
+			let (n, u, v) = (layout.normal.0, layout.u.0, layout.v.0);
+			
+			for d in 0..CHUNK_SIZE {
+				for b in 0..CHUNK_SIZE {
+					for a in 0..CHUNK_SIZE {
+						let mut pos = [0; 3];
+						pos[n] = d;
+						pos[u] = a;
+						pos[v] = b;
+						mask[a + b * CHUNK_SIZE] = self.get_face(chunk, air, pos, *face, &layout);
+					}
+				}
+				
+				for b in 0..CHUNK_SIZE {
+					let mut a = 0;
+					while a < CHUNK_SIZE {
+						let shading = match mask[a + b * CHUNK_SIZE] {
+							Some(shading) => shading,
+							None => { a += 1; continue }
+						};
+						
+						let mut width = 1;
+						while a + width < CHUNK_SIZE && mask[a + width + b * CHUNK_SIZE] == Some(shading) {
+							width += 1;
+						}
+						
+						let mut height = 1;
+						'grow: while b + height < CHUNK_SIZE {
+							for i in a..a + width {
+								if mask[i + (b + height) * CHUNK_SIZE] != Some(shading) {
+									break 'grow;
+								}
+							}
+							height += 1;
+						}
+						
+						for j in b..b + height {
+							for i in a..a + width {
+								mask[i + j * CHUNK_SIZE] = None;
+							}
+						}
+						
+						Self::push_quad(&layout, d, (a, a + width), (b, b + height), &shading, mesh.get_mut(shading.layer));
+						a += width;
+					}
+				}
+			}
+		}
+	}
+	
+	/// Emits the quads of every block with a model, except for those hidden by opaque neighbours.
+	fn mesh_models(&self, chunk: &ChunkSnapshot, mesh: &mut ChunkMeshData) {
+		let air = self.get_air();
+		
+		for y in 0..CHUNK_SIZE as isize {
+			for z in 0..CHUNK_SIZE as isize {
+				for x in 0..CHUNK_SIZE as isize {
+					let block = chunk.get_block(x, y, z).unwrap_or(air);
+					let quads = match self.models.get(&block.id) {
+						Some(quads) => quads,
+						None => continue
+					};
+					
+					let vertices = mesh.get_mut(self.blockdef.get_attributes(&block).layer);
+					let own_light = self.get_light(chunk, [x, y, z]) as f32 / MAX_LIGHT as f32;
+					
+					for quad in quads.iter() {
+						let light = match quad.cull {
+							Some(face) => {
+								let (ox, oy, oz) = face.get_offset();
+								let front = [x+ox, y+oy, z+oz];
+								if self.is_opaque(chunk, air, front) {
+									continue;
+								}
+								self.get_light(chunk, front) as f32 / MAX_LIGHT as f32
+							},
+							None => own_light
+						};
+						
+						let corner = |index: usize| {
+							let (position, uv) = (quad.corners[index], quad.uvs[index]);
+							ChunkMeshVertex::new(
+								x as f32 + position[0], y as f32 + position[1], z as f32 + position[2],
+								uv[0], uv[1], &quad.tile, 1.0, light
+							)
+						};
+						
+						vertices.reserve(6);
+						for index in [0, 1, 2, 0, 2, 3].iter() {
+							vertices.push(corner(*index));
+						}
+					}
+				}
+			}
+		}
+	}
+	
+	fn get_air(&self) -> BlockState {
+		self.blockdef
+			.get_block_by_name_unchecked("air")
+			.get_default_state()
+	}
+	
+	/// Returns the texture and shading of the given face of the block at `pos`, if that face is visible.
+	///
+	/// A face is hidden behind opaque neighbours,
+	/// and between two transparent blocks of the same state, unless they are cutout blocks,
+	/// whose holes would show that the faces in between are missing.
+	fn get_face(&self, chunk: &ChunkSnapshot, air: BlockState, pos: [usize; 3], face: BlockFace, layout: &FaceLayout) -> Option<FaceShading> {
+		let (x, y, z) = (pos[0] as isize, pos[1] as isize, pos[2] as isize);
+		let block = chunk.get_block(x, y, z).unwrap_or(air);
+		let uvs = self.faces.get(&block.id)?;
+		
+		if self.models.contains_key(&block.id) {
+			return None;
+		}
+		
+		let (ox, oy, oz) = face.get_offset();
+		let front = [x+ox, y+oy, z+oz];
+		let other = chunk.get_block(front[0], front[1], front[2]).unwrap_or(air);
+		let other_opaque = self.blockdef.get_attributes(&other).opaque;
+		let attributes = self.blockdef.get_attributes(&block);
+		
+		if other_opaque || (other == block && !attributes.opaque && attributes.layer != RenderLayer::Cutout) {
+			return None;
+		}
+		
+		let mut shading = FaceShading {
+			uv: uvs[face as usize],
+			layer: attributes.layer,
+			ao: [3; 4],
+			light: [1.0; 4],
+		};
+		
+		// Every corner is shaded by the blocks around it in the layer in front of the face.
+		let (u, v) = (layout.u.0, layout.v.0);
+		for (index, (su, sv)) in QUAD_CORNERS.iter().enumerate() {
+			let offset = layout.get_corner_offset(*su, *sv);
+			
+			let mut side1 = front;
+			side1[u] += offset[u];
+			let mut side2 = front;
+			side2[v] += offset[v];
+			let mut corner = side1;
+			corner[v] += offset[v];
+			
+			let samples = [front, side1, side2, corner];
+			let mut opaque = [false; 4];
+			for (opaque, sample) in opaque.iter_mut().zip(samples.iter()) {
+				*opaque = self.is_opaque(chunk, air, *sample);
+			}
+			
+			shading.ao[index] = vertex_ao(opaque[1], opaque[2], opaque[3]);
+			
+			// Smooth lighting: the average light of the samples light can pass through,
+			// leaving out the corner if the sides hide it.
+			let visible = if opaque[1] && opaque[2] {3} else {4};
+			let mut sum = 0u32;
+			let mut count = 0u32;
+			for (sample, opaque) in samples.iter().zip(opaque.iter()).take(visible) {
+				if !opaque {
+					sum += self.get_light(chunk, *sample) as u32;
+					count += 1;
+				}
+			}
+			
+			shading.light[index] = sum as f32 / (count.max(1) * MAX_LIGHT as u32) as f32;
+		}
+		
+		Some(shading)
+	}
+	
+	fn is_opaque(&self, chunk: &ChunkSnapshot, air: BlockState, pos: [isize; 3]) -> bool {
+		let block = chunk.get_block(pos[0], pos[1], pos[2]).unwrap_or(air);
+		self.blockdef.get_attributes(&block).opaque
+	}
+	
+	/// The brighter of sky and block light at the given position.
+	fn get_light(&self, chunk: &ChunkSnapshot, pos: [isize; 3]) -> u8 {
+		LightChannel::ALL.iter()
+			.filter_map(|channel| chunk.get_light(pos[0], pos[1], pos[2], *channel))
+			.max()
+			.unwrap_or(MAX_LIGHT)
+	}
+	
+	/// Emits the quad covering the ranges `us` and `vs` on the plane of layer `d` of the face.
+	///
+	/// The texture repeats once per block, starting from the corner its layout begins at.
+	fn push_quad(layout: &FaceLayout, d: usize, us: (usize, usize), vs: (usize, usize), shading: &FaceShading, dst: &mut Vec<ChunkMeshVertex>) {
+		let (n, n_positive) = layout.normal;
+		let (u, u_positive) = layout.u;
+		let (v, v_positive) = layout.v;
+		
+		let du = (us.1 - us.0) as f32;
+		let dv = (vs.1 - vs.0) as f32;
+		
+		let mut origin = [0.0f32; 3];
+		origin[n] = (d + if n_positive {1} else {0}) as f32;
+		origin[u] = (if u_positive {us.0} else {us.1}) as f32;
+		origin[v] = (if v_positive {vs.0} else {vs.1}) as f32;
+		
+		let mut step_u = [0.0f32; 3];
+		step_u[u] = if u_positive {du} else {-du};
+		
+		let mut step_v = [0.0f32; 3];
+		step_v[v] = if v_positive {dv} else {-dv};
+		
+		let corner = |index: usize| {
+			let (su, sv) = QUAD_CORNERS[index];
+			let (su, sv) = (su as f32, sv as f32);
+			ChunkMeshVertex::new(
+				origin[0] + step_u[0]*su + step_v[0]*sv,
+				origin[1] + step_u[1]*su + step_v[1]*sv,
+				origin[2] + step_u[2]*su + step_v[2]*sv,
+				du * su, dv * sv, &shading.uv,
+				shading.ao[index] as f32 / 3.0,
+				shading.light[index]
+			)
+		};
+		
+		let quad = [corner(0), corner(1), corner(2), corner(3)];
+		
+		// Split the quad along the diagonal that keeps the occlusion symmetric,
+		// otherwise one of the triangles gets a visibly darker seam.
+		let ao = &shading.ao;
+		let triangles = if ao[0] + ao[2] < ao[1] + ao[3] {
+			[0, 1, 2, 0, 2, 3]
+		} else {
+			[0, 1, 3, 1, 2, 3]
+		};
+		
+		dst.reserve(6);
+		for index in triangles.iter() {
+			dst.push(quad[*index]);
+		}
+	}
+
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The vertices of a meshed chunk, one list per render layer.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkMeshData {
+	/// Indexed like `RenderLayer::ALL`.
+	layers: [Vec<ChunkMeshVertex>; 3],
+}
+
+impl ChunkMeshData {
+	pub fn new() -> ChunkMeshData {
+		ChunkMeshData::default()
+	}
+	
+	pub fn get(&self, layer: RenderLayer) -> &Vec<ChunkMeshVertex> {
+		&self.layers[layer as usize]
+	}
+	
+	pub fn get_mut(&mut self, layer: RenderLayer) -> &mut Vec<ChunkMeshVertex> {
+		&mut self.layers[layer as usize]
+	}
+	
+	/// The number of vertices in all layers.
+	pub fn len(&self) -> usize {
+		self.layers.iter().map(|vertices| vertices.len()).sum()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.layers.iter().all(|vertices| vertices.is_empty())
+	}
+}
+
+/// Sorts the quads of the given vertices back to front, as seen from `camera`,
+/// so blended faces cover the ones behind them.
+///
+/// The vertices must come in groups of six, two triangles per quad, like the mesher emits them.
+pub fn sort_back_to_front(vertices: &mut Vec<ChunkMeshVertex>, camera: cgmath::Vector3<f32>) {
+	let mut quads: Vec<(f32, &[ChunkMeshVertex])> = vertices
+		.chunks(6)
+		.map(|quad| {
+			let count = quad.len() as f32;
+			let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+			for vertex in quad {
+				x += vertex.x;
+				y += vertex.y;
+				z += vertex.z;
+			}
+			
+			let (dx, dy, dz) = (x / count - camera.x, y / count - camera.y, z / count - camera.z);
+			(dx*dx + dy*dy + dz*dz, quad)
+		})
+		.collect();
+	
+	quads.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+	
+	let sorted: Vec<ChunkMeshVertex> = quads.into_iter()
+		.flat_map(|(_, quad)| quad.iter().cloned())
+		.collect();
+	
+	*vertices = sorted;
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct ChunkMeshVertex {
+	// Geometry
+	pub x: f32,
+	pub y: f32,
+	pub z: f32,
+	
+	// Texture, in blocks; repeats once per block.
+	pub u: f32,
+	pub v: f32,
+	
+	// Tile within the atlas the texture repeats in.
+	pub tile_u: f32,
+	pub tile_v: f32,
+	pub tile_w: f32,
+	pub tile_h: f32,
+	
+	// Shading, both from 0.0 (dark) to 1.0.
+	pub ao: f32,
+	pub light: f32,
+}
+
+impl ChunkMeshVertex {
+	pub fn new(x: f32, y: f32, z: f32, u: f32, v: f32, tile: &BlockUv, ao: f32, light: f32) -> Self {
+		Self {
+			x, y, z, u, v, ao, light,
+			tile_u: tile.umin,
+			tile_v: tile.vmin,
+			tile_w: tile.umax - tile.umin,
+			tile_h: tile.vmax - tile.vmin,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::BTreeMap;
+	use super::super::super::super::blocks::universe::define_universe;
+	use super::super::super::super::blocks::world::{Chunk, ChunkStorage};
+	use super::super::bakery::TextureBakery;
+	
+	const CONFIG: &str = r#"
+		[blocks.stone]
+		[blocks.dirt]
+		[blocks.grass]
+		textures = { top = "grass_top", bottom = "dirt", side = "grass_side" }
+	"#;
+	
+	/// A single face of a block, by the position of the block, its normal, and the tile it shows.
+	type UnitFace = ([i32; 3], (usize, bool), [u32; 2]);
+	
+	/// Builds a naive and a greedy mesher for the blocks of `CONFIG`.
+	fn meshers() -> (blockdef::UniverseRef, ChunkMesher, ChunkMesher) {
+		let config: toml::Value = CONFIG.parse().unwrap();
+		let blockdef = define_universe(config.as_table().unwrap());
+		
+		let mut bakery = TextureBakery::new();
+		for name in &[MISSING_TEXTURE, "bedrock", "stone", "dirt", "grass_top", "grass_side"] {
+			bakery.add_texture(name, TextureBakery::generate_missing_texture(16));
+		}
+		let atlas = bakery.bake().unwrap().atlas;
+		
+		let models = BlockModels::new();
+		let naive = ChunkMesher::new(blockdef.clone(), &atlas, &models, MeshingMode::Naive);
+		let greedy = ChunkMesher::new(blockdef.clone(), &atlas, &models, MeshingMode::Greedy);
+		(blockdef, naive, greedy)
+	}
+	
+	/// Meshes the chunk filled in by `fill`, with no chunks around it.
+	fn mesh(blockdef: &blockdef::UniverseRef, mesher: &ChunkMesher, fill: &Fn(isize, isize, isize) -> Option<&'static str>) -> Vec<ChunkMeshVertex> {
+		let mut chunk = Chunk::new_empty(blockdef.clone(), 0, 0, 0);
+		for y in 0..CHUNK_SIZE as isize {
+			for z in 0..CHUNK_SIZE as isize {
+				for x in 0..CHUNK_SIZE as isize {
+					if let Some(name) = fill(x, y, z) {
+						let state = blockdef.get_block_by_name_unchecked(name).get_default_state();
+						chunk.set_block(x, y, z, state);
+					}
+				}
+			}
+		}
+		
+		let mut storage = ChunkStorage::new_empty(blockdef.clone(), &toml::value::Table::new()).unwrap();
+		let pos = chunk.pos;
+		storage.insert_chunk(chunk);
+		
+		mesher.mesh(&storage.get_snapshot(&pos).unwrap()).get(RenderLayer::Opaque).clone()
+	}
+	
+	/// Splits every quad of the mesh into the unit faces it covers, counting how often each is covered.
+	fn unit_faces(vertices: &[ChunkMeshVertex]) -> BTreeMap<UnitFace, usize> {
+		let mut faces = BTreeMap::new();
+		
+		for quad in vertices.chunks(6) {
+			let position = |vertex: &ChunkMeshVertex| [vertex.x, vertex.y, vertex.z];
+			let mut min = position(&quad[0]);
+			let mut max = min;
+			for vertex in quad {
+				let p = position(vertex);
+				for axis in 0..3 {
+					min[axis] = min[axis].min(p[axis]);
+					max[axis] = max[axis].max(p[axis]);
+				}
+			}
+			
+			// The normal of the first triangle tells the facing.
+			let (p0, p1, p2) = (position(&quad[0]), position(&quad[1]), position(&quad[2]));
+			let e1 = [p1[0] - p0[0], p1[1] - p0[1], p1[2] - p0[2]];
+			let e2 = [p2[0] - p0[0], p2[1] - p0[1], p2[2] - p0[2]];
+			let cross = [e1[1]*e2[2] - e1[2]*e2[1], e1[2]*e2[0] - e1[0]*e2[2], e1[0]*e2[1] - e1[1]*e2[0]];
+			
+			let n = (0..3).find(|axis| min[*axis] == max[*axis]).expect("Quad is not axis-aligned.");
+			let positive = cross[n] > 0.0;
+			let tile = [quad[0].tile_u.to_bits(), quad[0].tile_v.to_bits()];
+			
+			let (u, v) = ((n + 1) % 3, (n + 2) % 3);
+			for a in min[u] as i32..max[u] as i32 {
+				for b in min[v] as i32..max[v] as i32 {
+					let mut block = [0; 3];
+					block[n] = min[n] as i32 - if positive {1} else {0};
+					block[u] = a;
+					block[v] = b;
+					*faces.entry((block, (n, positive), tile)).or_insert(0) += 1;
+				}
+			}
+		}
+		
+		faces
+	}
+	
+	fn area(faces: &BTreeMap<UnitFace, usize>) -> usize {
+		faces.values().sum()
+	}
+	
+	fn check_same_surface(fill: &Fn(isize, isize, isize) -> Option<&'static str>) -> (usize, usize) {
+		let (blockdef, naive, greedy) = meshers();
+		let naive = mesh(&blockdef, &naive, fill);
+		let greedy = mesh(&blockdef, &greedy, fill);
+		
+		let naive_faces = unit_faces(&naive);
+		let greedy_faces = unit_faces(&greedy);
+		
+		assert_eq!(area(&naive_faces), naive.len() / 6);
+		assert_eq!(area(&greedy_faces), area(&naive_faces));
+		assert!(greedy_faces.values().all(|count| *count == 1), "Greedy quads overlap.");
+		assert_eq!(greedy_faces, naive_faces);
+		
+		(naive.len() / 6, greedy.len() / 6)
+	}
+	
+	#[test]
+	fn translucent_quads_are_sorted_back_to_front() {
+		let tile = BlockUv { umin: 0.0, umax: 1.0, vmin: 0.0, vmax: 1.0 };
+		
+		// Four panes facing `z`, one block apart, told apart by their light.
+		let mut vertices = vec![];
+		for pane in [2, 0, 3, 1].iter() {
+			let z = *pane as f32;
+			for &(x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter() {
+				vertices.push(ChunkMeshVertex::new(x, y, z, x, y, &tile, 1.0, z));
+			}
+		}
+		
+		let order = |vertices: &Vec<ChunkMeshVertex>| -> Vec<f32> {
+			vertices.chunks(6).map(|quad| {
+				// Quads stay whole.
+				assert!(quad.iter().all(|vertex| vertex.light == quad[0].light));
+				quad[0].light
+			}).collect()
+		};
+		
+		sort_back_to_front(&mut vertices, cgmath::Vector3::new(0.5, 0.5, -5.0));
+		assert_eq!(order(&vertices), vec![3.0, 2.0, 1.0, 0.0]);
+		
+		sort_back_to_front(&mut vertices, cgmath::Vector3::new(0.5, 0.5, 10.0));
+		assert_eq!(order(&vertices), vec![0.0, 1.0, 2.0, 3.0]);
+		
+		// From in between, the panes on either side are interleaved by distance.
+		sort_back_to_front(&mut vertices, cgmath::Vector3::new(0.5, 0.5, 1.4));
+		assert_eq!(order(&vertices), vec![3.0, 0.0, 2.0, 1.0]);
+		assert_eq!(vertices.len(), 24);
+	}
+	
+	#[test]
+	fn vertex_ao_levels() {
+		// (side1, side2, corner) => level
+		let table = [
+			((false, false, false), 3),
+			((true , false, false), 2),
+			((false, true , false), 2),
+			((false, false, true ), 2),
+			((true , false, true ), 1),
+			((false, true , true ), 1),
+			((true , true , false), 0),
+			((true , true , true ), 0),
+		];
+		
+		for ((side1, side2, corner), level) in table.iter() {
+			assert_eq!(vertex_ao(*side1, *side2, *corner), *level, "sides {} {}, corner {}", side1, side2, corner);
+		}
+	}
+	
+	#[test]
+	fn single_block() {
+		let (naive, greedy) = check_same_surface(&|x, y, z| {
+			if (x, y, z) == (3, 4, 5) {Some("stone")} else {None}
+		});
+		assert_eq!((naive, greedy), (6, 6));
+	}
+	
+	#[test]
+	fn slab() {
+		let (naive, greedy) = check_same_surface(&|_, y, _| {
+			if y == 1 {Some("stone")} else {None}
+		});
+		assert_eq!(naive, 16 * 16 * 2 + 16 * 4);
+		
+		// The light under the slab is uneven, which keeps some of the faces there apart.
+		assert!(greedy < naive / 3);
+	}
+	
+	#[test]
+	fn checkerboard() {
+		let (naive, greedy) = check_same_surface(&|x, y, z| {
+			if (x + y + z) % 2 == 0 {Some("stone")} else {None}
+		});
+		assert_eq!(naive, 16 * 16 * 16 / 2 * 6);
+		assert_eq!(greedy, naive);
+	}
+	
+	#[test]
+	fn mixed_textures() {
+		let (naive, greedy) = check_same_surface(&|x, y, z| {
+			match (y, (x / 3 + z / 5) % 3) {
+				(y, _) if y > (x + z) % 4 => None,
+				(_, 0) => Some("stone"),
+				(_, 1) => Some("dirt"),
+				_ => Some("grass"),
+			}
+		});
+		assert!(greedy < naive);
+	}
+}
@@ -0,0 +1,571 @@
+//! Block models: shapes other than the full cube, built from boxes.
+//!
+//! A block uses a model when it names one in the `[blocks]` config (`model = "slab"`).
+//! Models are JSON files in `models/`, named after the model (`models/slab.json` for `slab`):
+//!
+//! ```json
+//! {
+//!     "boxes": [
+//!         {
+//!             "from": [0.0, 0.0, 0.0],
+//!             "to": [1.0, 0.5, 1.0],
+//!             "rotation": { "axis": "y", "angle": 45.0, "origin": [0.5, 0.5, 0.5] },
+//!             "faces": {
+//!                 "all": {},
+//!                 "top": { "texture": "slab_top", "uv": [0.0, 0.0, 1.0, 1.0], "rotation": 90, "cull": true }
+//!             }
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Positions are in block-local space, where a full block spans `0..1` on every axis;
+//! boxes may reach up to one block beyond that, and may be flat along one axis.
+//!
+//! Only the faces listed in `faces` are rendered, all six if it is left out.
+//! Its keys are those of the `textures` attribute of blocks: `all`, `side`, `top`, `bottom`,
+//! `north`, `south`, `west` and `east`, the more specific keys overriding the more general ones.
+//! Every key of a face is optional:
+//!
+//! - `texture` defaults to the texture of the block on that face.
+//! - `uv` is `[umin, vmin, umax, vmax]` within the texture, from 0 to 1; by default the box
+//!   takes the part of the texture a full cube would show at its position.
+//! - `rotation` turns the texture on the face in steps of 90 degrees.
+//! - `cull` hides the face behind an opaque neighbour on that side; by default,
+//!   faces of unrotated boxes that lie on the side of the block are culled.
+//!
+//! `rotation` of a box turns it around the given axis and origin, by any angle in degrees;
+//! two flat, crossed boxes turned by 45 degrees make a plant.
+
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+use super::super::super::resources;
+use super::super::super::blocks as blockdef;
+use super::super::super::blocks::BlockFace;
+
+/// The resource directory block models are loaded from.
+pub const BLOCK_MODEL_DIR: &str = "models";
+
+/// How far boxes may reach beyond the block, in blocks.
+const MAX_OVERHANG: f32 = 1.0;
+
+#[derive(Debug, Fail)]
+pub enum ModelError {
+	#[fail(display = "Failed to load model {}", name)]
+	ResourceLoad { name: String, #[cause] inner: resources::ResError },
+	
+	#[fail(display = "Failed to parse model {}", name)]
+	JsonParse { name: String, #[cause] inner: serde_json::Error },
+	
+	#[fail(display = "Invalid model {}: {}", name, reason)]
+	Invalid { name: String, reason: String },
+}
+
+/// An axis of block-local space.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ModelAxis {
+	X,
+	Y,
+	Z,
+}
+
+/// A rotation of a box around an axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoxRotation {
+	pub axis: ModelAxis,
+	
+	/// Counter-clockwise when looking along the axis towards the origin, in degrees.
+	pub angle: f32,
+	
+	pub origin: [f32; 3],
+}
+
+impl BoxRotation {
+	/// Rotates a point in block-local space.
+	pub fn apply(&self, point: [f32; 3]) -> [f32; 3] {
+		let (sin, cos) = self.angle.to_radians().sin_cos();
+		let p = [point[0] - self.origin[0], point[1] - self.origin[1], point[2] - self.origin[2]];
+		
+		let r = match self.axis {
+			ModelAxis::X => [p[0], p[1]*cos - p[2]*sin, p[1]*sin + p[2]*cos],
+			ModelAxis::Y => [p[0]*cos + p[2]*sin, p[1], -p[0]*sin + p[2]*cos],
+			ModelAxis::Z => [p[0]*cos - p[1]*sin, p[0]*sin + p[1]*cos, p[2]],
+		};
+		
+		[r[0] + self.origin[0], r[1] + self.origin[1], r[2] + self.origin[2]]
+	}
+}
+
+/// A single face of a box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelFace {
+	/// The texture of the face; `None` for the texture of the block on this face.
+	pub texture: Option<String>,
+	
+	/// `[umin, vmin, umax, vmax]` within the texture; `None` for the part a full cube would show.
+	pub uv: Option<[f32; 4]>,
+	
+	/// Quarter turns of the texture on the face.
+	pub rotation: u8,
+	
+	/// Whether the face is hidden behind an opaque neighbour on its side.
+	pub cull: bool,
+}
+
+/// An axis-aligned box of a model, possibly rotated as a whole.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelBox {
+	pub from: [f32; 3],
+	pub to: [f32; 3],
+	pub rotation: Option<BoxRotation>,
+	
+	/// The rendered faces, indexed like `BlockFace::ALL`.
+	pub faces: [Option<ModelFace>; 6],
+}
+
+impl ModelBox {
+	pub fn get_face(&self, face: BlockFace) -> Option<&ModelFace> {
+		self.faces[face as usize].as_ref()
+	}
+}
+
+/// A block model, as loaded from its JSON file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockModel {
+	pub boxes: Vec<ModelBox>,
+}
+
+impl BlockModel {
+	/// Loads and validates the named model from `models/<name>.json`.
+	pub fn load(res: &resources::Resources, name: &str) -> Result<BlockModel, ModelError> {
+		let buffer = res.load_buffer(&format!("{}/{}.json", BLOCK_MODEL_DIR, name))
+			.map_err(|e| ModelError::ResourceLoad { name: name.to_string(), inner: e })?;
+		
+		BlockModel::from_json(name, &buffer)
+	}
+	
+	/// Parses and validates a model from the contents of its JSON file.
+	pub fn from_json(name: &str, json: &[u8]) -> Result<BlockModel, ModelError> {
+		let value: Value = serde_json::from_slice(json)
+			.map_err(|e| ModelError::JsonParse { name: name.to_string(), inner: e })?;
+		
+		BlockModel::from_value(&value)
+			.map_err(|reason| ModelError::Invalid { name: name.to_string(), reason })
+	}
+	
+	fn from_value(value: &Value) -> Result<BlockModel, String> {
+		let model = value.as_object().ok_or("the model must be an object")?;
+		check_keys(model, &["boxes"], "the model")?;
+		
+		let boxes = model.get("boxes")
+			.and_then(|b| b.as_array())
+			.ok_or("'boxes' must be an array")?;
+		
+		if boxes.is_empty() {
+			return Err("'boxes' must not be empty".to_string());
+		}
+		
+		let boxes = boxes.iter()
+			.enumerate()
+			.map(|(index, b)| parse_box(b).map_err(|e| format!("box {}: {}", index, e)))
+			.collect::<Result<Vec<ModelBox>, String>>()?;
+		
+		Ok(BlockModel { boxes })
+	}
+	
+	/// Returns the names of all textures the model sets explicitly.
+	pub fn get_textures(&self) -> Vec<&str> {
+		let mut textures: Vec<&str> = self.boxes.iter()
+			.flat_map(|b| b.faces.iter())
+			.filter_map(|face| face.as_ref().and_then(|face| face.texture.as_ref()))
+			.map(|texture| texture.as_str())
+			.collect();
+		
+		textures.sort();
+		textures.dedup();
+		textures
+	}
+}
+
+fn parse_box(value: &Value) -> Result<ModelBox, String> {
+	let value = value.as_object().ok_or("must be an object")?;
+	check_keys(value, &["from", "to", "rotation", "faces"], "the box")?;
+	
+	let from = parse_vector(value.get("from"), "from")?;
+	let to = parse_vector(value.get("to"), "to")?;
+	
+	for axis in 0..3 {
+		if from[axis] > to[axis] {
+			return Err("'from' must not be greater than 'to'".to_string());
+		}
+	}
+	
+	let rotation = match value.get("rotation") {
+		Some(rotation) => Some(parse_rotation(rotation)?),
+		None => None
+	};
+	
+	let mut faces: [Option<ModelFace>; 6] = Default::default();
+	match value.get("faces") {
+		Some(entries) => {
+			let entries = entries.as_object().ok_or("'faces' must be an object")?;
+			check_keys(entries, &["all", "side", "top", "bottom", "north", "south", "west", "east"], "'faces'")?;
+			
+			for (index, face) in BlockFace::ALL.iter().enumerate() {
+				let entry = entries.get(face.get_name())
+					.or_else(|| if face.is_side() { entries.get("side") } else { None })
+					.or_else(|| entries.get("all"));
+				
+				if let Some(entry) = entry {
+					let default_cull = rotation.is_none() && is_on_side(&from, &to, *face);
+					faces[index] = Some(parse_face(entry, default_cull)
+						.map_err(|e| format!("face '{}': {}", face.get_name(), e))?);
+				}
+			}
+		},
+		None => {
+			for (index, face) in BlockFace::ALL.iter().enumerate() {
+				faces[index] = Some(ModelFace {
+					texture: None,
+					uv: None,
+					rotation: 0,
+					cull: rotation.is_none() && is_on_side(&from, &to, *face),
+				});
+			}
+		}
+	}
+	
+	Ok(ModelBox { from, to, rotation, faces })
+}
+
+fn parse_face(value: &Value, default_cull: bool) -> Result<ModelFace, String> {
+	let value = value.as_object().ok_or("must be an object")?;
+	check_keys(value, &["texture", "uv", "rotation", "cull"], "the face")?;
+	
+	let texture = match value.get("texture") {
+		Some(texture) => Some(texture.as_str().ok_or("'texture' must be a string")?.to_string()),
+		None => None
+	};
+	
+	let uv = match value.get("uv") {
+		Some(uv) => {
+			let uv = parse_numbers(uv, 4).map_err(|_| "'uv' must be an array of four numbers")?;
+			if uv.iter().any(|x| *x < 0.0 || *x > 1.0) {
+				return Err("'uv' values must be from 0 to 1".to_string());
+			}
+			Some([uv[0], uv[1], uv[2], uv[3]])
+		},
+		None => None
+	};
+	
+	let rotation = match value.get("rotation") {
+		Some(rotation) => {
+			let degrees = rotation.as_i64()
+				.filter(|r| [0, 90, 180, 270].contains(r))
+				.ok_or("'rotation' of a face must be 0, 90, 180 or 270")?;
+			(degrees / 90) as u8
+		},
+		None => 0
+	};
+	
+	let cull = match value.get("cull") {
+		Some(cull) => cull.as_bool().ok_or("'cull' must be a boolean")?,
+		None => default_cull
+	};
+	
+	Ok(ModelFace { texture, uv, rotation, cull })
+}
+
+fn parse_rotation(value: &Value) -> Result<BoxRotation, String> {
+	let value = value.as_object().ok_or("'rotation' must be an object")?;
+	check_keys(value, &["axis", "angle", "origin"], "'rotation'")?;
+	
+	let axis = match value.get("axis").and_then(|a| a.as_str()) {
+		Some("x") => ModelAxis::X,
+		Some("y") => ModelAxis::Y,
+		Some("z") => ModelAxis::Z,
+		_ => return Err("'axis' of a rotation must be \"x\", \"y\" or \"z\"".to_string())
+	};
+	
+	let angle = value.get("angle")
+		.and_then(|a| a.as_f64())
+		.ok_or("'angle' of a rotation must be a number")? as f32;
+	
+	let origin = match value.get("origin") {
+		Some(_) => parse_vector(value.get("origin"), "origin")?,
+		None => [0.5, 0.5, 0.5]
+	};
+	
+	Ok(BoxRotation { axis, angle, origin })
+}
+
+/// Parses a position within the block, or at most `MAX_OVERHANG` outside of it.
+fn parse_vector(value: Option<&Value>, key: &str) -> Result<[f32; 3], String> {
+	let value = value.ok_or_else(|| format!("'{}' is missing", key))?;
+	let values = parse_numbers(value, 3)
+		.map_err(|_| format!("'{}' must be an array of three numbers", key))?;
+	
+	if values.iter().any(|x| *x < -MAX_OVERHANG || *x > 1.0 + MAX_OVERHANG) {
+		return Err(format!("'{}' must be from {} to {}", key, -MAX_OVERHANG, 1.0 + MAX_OVERHANG));
+	}
+	
+	Ok([values[0], values[1], values[2]])
+}
+
+fn parse_numbers(value: &Value, count: usize) -> Result<Vec<f32>, ()> {
+	let values = value.as_array()
+		.filter(|values| values.len() == count)
+		.ok_or(())?;
+	
+	values.iter()
+		.map(|x| x.as_f64().map(|x| x as f32).ok_or(()))
+		.collect()
+}
+
+/// Fails on keys that are not in `known`, which are most likely typos.
+fn check_keys(object: &serde_json::Map<String, Value>, known: &[&str], what: &str) -> Result<(), String> {
+	match object.keys().find(|key| !known.contains(&key.as_str())) {
+		Some(key) => Err(format!("unknown key '{}' in {}", key, what)),
+		None => Ok(())
+	}
+}
+
+/// Whether the given face of the box lies on the side of the block it faces.
+fn is_on_side(from: &[f32; 3], to: &[f32; 3], face: BlockFace) -> bool {
+	match face {
+		BlockFace::Top    => to[1] >= 1.0,
+		BlockFace::Bottom => from[1] <= 0.0,
+		BlockFace::North  => from[2] <= 0.0,
+		BlockFace::South  => to[2] >= 1.0,
+		BlockFace::West   => from[0] <= 0.0,
+		BlockFace::East   => to[0] >= 1.0,
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The models of all blocks in a universe.
+pub struct BlockModels {
+	/// Models by name.
+	models: FxHashMap<String, BlockModel>,
+	
+	/// The name of the model of every block that has one.
+	blocks: FxHashMap<blockdef::BlockId, String>,
+}
+
+impl BlockModels {
+	/// No models at all; every block is a cube.
+	pub fn new() -> BlockModels {
+		BlockModels {
+			models: FxHashMap::default(),
+			blocks: FxHashMap::default(),
+		}
+	}
+	
+	/// Loads the models of all blocks in the universe from `models/`, each model only once.
+	pub fn load(res: &resources::Resources, blockdef: &blockdef::UniverseRef) -> Result<BlockModels, ModelError> {
+		BlockModels::load_with(blockdef, |name| BlockModel::load(res, name))
+	}
+	
+	/// Collects the models of all blocks in the universe, getting each model from `loader` only once.
+	pub fn load_with<F>(blockdef: &blockdef::UniverseRef, mut loader: F) -> Result<BlockModels, ModelError>
+		where F: FnMut(&str) -> Result<BlockModel, ModelError>
+	{
+		let mut models = BlockModels::new();
+		
+		for (id, block) in blockdef.list_blocks() {
+			let name = match &block.get_attributes().model {
+				Some(name) => name,
+				None => continue
+			};
+			
+			if ! models.models.contains_key(name) {
+				debug!("Loading block model '{}'...", name);
+				models.models.insert(name.clone(), loader(name)?);
+			}
+			
+			models.blocks.insert(*id, name.clone());
+		}
+		
+		Ok(models)
+	}
+	
+	pub fn get(&self, name: &str) -> Option<&BlockModel> {
+		self.models.get(name)
+	}
+	
+	/// Returns the model of the given block, if it is not a cube.
+	pub fn get_block_model(&self, block: blockdef::BlockId) -> Option<&BlockModel> {
+		self.blocks.get(&block).and_then(|name| self.models.get(name))
+	}
+	
+	/// Iterates over all models and their names.
+	pub fn iter(&self) -> impl Iterator<Item = (&String, &BlockModel)> {
+		self.models.iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::super::super::blocks::universe::define_universe;
+	
+	fn parse(json: &str) -> Result<BlockModel, ModelError> {
+		BlockModel::from_json("test", json.as_bytes())
+	}
+	
+	/// Parses a model with a single box of the given keys.
+	fn parse_box(keys: &str) -> Result<ModelBox, ModelError> {
+		parse(&format!(r#"{{ "boxes": [{{ {} }}] }}"#, keys)).map(|mut model| model.boxes.remove(0))
+	}
+	
+	fn is_invalid<T>(result: Result<T, ModelError>) -> bool {
+		match result {
+			Err(ModelError::Invalid { .. }) => true,
+			_ => false
+		}
+	}
+	
+	const CUBE: &str = r#""from": [0, 0, 0], "to": [1, 1, 1]"#;
+	
+	#[test]
+	fn invalid_models_are_rejected() {
+		assert!(is_invalid(parse(r#"{ "boxes": [] }"#)));
+		assert!(is_invalid(parse(r#"{ "boxes": [], "box": [] }"#)));
+		
+		for keys in [
+			// Unknown keys, anywhere.
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "size": 1"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "up": {} }"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "tint": 1 } }"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "rotation": { "axis": "y", "angle": 45, "center": [0, 0, 0] }"#,
+			
+			// Boxes turned inside out.
+			r#""from": [0, 0.5, 0], "to": [1, 0.25, 1]"#,
+			
+			// Vectors too far out of the block, or not vectors.
+			r#""from": [-1.5, 0, 0], "to": [1, 1, 1]"#,
+			r#""from": [0, 0, 0], "to": [1, 2.5, 1]"#,
+			r#""from": [0, 0, 0], "to": [1, 1]"#,
+			r#""from": [0, 0, 0], "to": [1, 1, "1"]"#,
+			r#""to": [1, 1, 1]"#,
+			
+			// Texture coordinates out of the texture.
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "uv": [0, 0, 1, 1.5] } }"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "uv": [-0.5, 0, 1, 1] } }"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "uv": [0, 0, 1] } }"#,
+			
+			// Textures turned by anything but quarter turns.
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "rotation": 45 } }"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "rotation": 360 } }"#,
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "faces": { "all": { "rotation": 90.5 } }"#,
+			
+			// Rotations around no axis.
+			r#""from": [0, 0, 0], "to": [1, 1, 1], "rotation": { "axis": "w", "angle": 45 }"#,
+		].iter() {
+			assert!(is_invalid(parse_box(keys)), "{}", keys);
+		}
+		
+		// Not JSON at all.
+		match parse("{ boxes") {
+			Err(ModelError::JsonParse { .. }) => (),
+			_ => panic!("expected a parse error")
+		}
+	}
+	
+	#[test]
+	fn specific_faces_override_general_ones() {
+		let model = parse_box(&format!(r#"{}, "faces": {{
+			"all": {{ "texture": "all" }},
+			"side": {{ "texture": "side" }},
+			"north": {{ "texture": "north" }}
+		}}"#, CUBE)).unwrap();
+		
+		let texture = |face| model.get_face(face).unwrap().texture.as_ref().unwrap().as_str();
+		assert_eq!(texture(BlockFace::Top), "all");
+		assert_eq!(texture(BlockFace::Bottom), "all");
+		assert_eq!(texture(BlockFace::North), "north");
+		assert_eq!(texture(BlockFace::South), "side");
+		assert_eq!(texture(BlockFace::West), "side");
+		assert_eq!(texture(BlockFace::East), "side");
+		
+		// Faces that are not listed are not rendered.
+		let model = parse_box(&format!(r#"{}, "faces": {{ "top": {{}} }}"#, CUBE)).unwrap();
+		assert!(model.get_face(BlockFace::Top).is_some());
+		assert!(model.get_face(BlockFace::North).is_none());
+		assert!(model.get_face(BlockFace::Bottom).is_none());
+	}
+	
+	#[test]
+	fn faces_on_the_sides_of_unrotated_boxes_are_culled() {
+		let culled = |model: &ModelBox| -> Vec<bool> {
+			BlockFace::ALL.iter().map(|face| model.get_face(*face).unwrap().cull).collect()
+		};
+		
+		// A slab only reaches the top of the block with its top face...
+		let slab = parse_box(r#""from": [0, 0, 0], "to": [1, 0.5, 1]"#).unwrap();
+		for face in BlockFace::ALL.iter() {
+			assert_eq!(slab.get_face(*face).unwrap().cull, *face != BlockFace::Top, "{:?}", face);
+		}
+		
+		// ...the same if the faces are listed...
+		let listed = parse_box(r#""from": [0, 0, 0], "to": [1, 0.5, 1], "faces": { "all": {} }"#).unwrap();
+		assert_eq!(culled(&listed), culled(&slab));
+		
+		// ...unless they say otherwise.
+		let explicit = parse_box(r#""from": [0, 0, 0], "to": [1, 0.5, 1], "faces": { "all": { "cull": false }, "top": { "cull": true } }"#).unwrap();
+		for face in BlockFace::ALL.iter() {
+			assert_eq!(explicit.get_face(*face).unwrap().cull, *face == BlockFace::Top, "{:?}", face);
+		}
+		
+		// Rotated boxes are never culled by default, wherever they are.
+		let rotated = parse_box(&format!(r#"{}, "rotation": {{ "axis": "x", "angle": 90 }}"#, CUBE)).unwrap();
+		assert_eq!(culled(&rotated), vec![false; 6]);
+	}
+	
+	#[test]
+	fn models_are_loaded_once() {
+		let config: toml::Value = r#"
+			[blocks.stone]
+			[blocks.oak_slab]
+			model = "slab"
+			[blocks.stone_slab]
+			model = "slab"
+			[blocks.grass]
+			model = "cross"
+		"#.parse().unwrap();
+		let blockdef = define_universe(config.as_table().unwrap());
+		
+		let mut loaded: Vec<String> = vec![];
+		let models = BlockModels::load_with(&blockdef, |name| {
+			loaded.push(name.to_string());
+			parse(&format!(r#"{{ "boxes": [{{ {} }}] }}"#, CUBE))
+		}).unwrap();
+		
+		loaded.sort();
+		assert_eq!(loaded, vec!["cross", "slab"]);
+		
+		let model = |name| models.get_block_model(blockdef.get_block_by_name_unchecked(name).get_id());
+		assert!(model("oak_slab").is_some());
+		assert_eq!(model("oak_slab"), model("stone_slab"));
+		assert!(model("grass").is_some());
+		assert!(model("stone").is_none());
+		
+		// A model that fails to load fails all of them.
+		assert!(BlockModels::load_with(&blockdef, |name| parse(name).map_err(|_| ModelError::Invalid {
+			name: name.to_string(),
+			reason: "missing".to_string()
+		})).is_err());
+	}
+	
+	#[test]
+	fn shipped_models_are_valid() {
+		let slab = BlockModel::from_json("slab", include_bytes!("../../../assets/models/slab.json")).unwrap();
+		assert_eq!(slab.boxes.len(), 1);
+		assert_eq!(slab.boxes[0].to, [1.0, 0.5, 1.0]);
+		
+		let cross = BlockModel::from_json("cross", include_bytes!("../../../assets/models/cross.json")).unwrap();
+		assert_eq!(cross.boxes.len(), 2);
+		assert!(cross.boxes.iter().all(|b| b.rotation.is_some()));
+	}
+}
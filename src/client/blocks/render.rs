@@ -1,25 +1,33 @@
+use std::path::Path;
 use rustc_hash::FxHashMap;
 use super::super::super::resources;
 use super::super::super::blocks as blockdef;
-use super::super::super::util::current_time_nanos;
 use super::super::render;
+use super::super::render::frustum::Frustum;
 use super::super::scene;
 
-use super::Chunk;
-use super::ChunkCoord;
-use super::CHUNK_SIZE;
+use super::super::super::blocks::world::ChunkCoord;
+use super::super::super::blocks::world::CHUNK_SIZE;
+use super::super::super::blocks::RenderLayer;
+use super::super::settings;
+use super::bakery::TextureBakery;
+use super::model::BlockModels;
+use super::mesher::{self, ChunkMesher, ChunkMeshData, ChunkMeshVertex, MeshingMode};
+use super::worker::MeshWorkerPool;
+use cgmath::InnerSpace;
 
 pub struct ShaderBlocks {
 	pub shader: render::utility::Program,
 	pub texatlas: render::utility::Texture,
 	pub uniform_matrix: i32,
 	pub uniform_atlas: i32,
+	pub uniform_alpha_cutoff: i32,
 }
 
 impl ShaderBlocks {
-	pub fn new(res: &resources::Resources) -> Result<ShaderBlocks, render::utility::Error> {
-		debug!("Loading blocks texture...");
-		let texatlas = render::utility::Texture::from_res(&res, "textures/atlas.png", &||{
+	pub fn new(res: &resources::Resources, atlas: &image::RgbaImage) -> Result<ShaderBlocks, render::utility::Error> {
+		debug!("Uploading blocks texture...");
+		let texatlas = render::utility::Texture::from_image("Block Atlas", atlas, &||{
 			unsafe {
 				// wrapping
 				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
@@ -35,45 +43,91 @@ impl ShaderBlocks {
 					gl::TexParameterf(gl::TEXTURE_2D, 0x84FE, aniso);
 				}
 			}
-		})?;
+		});
 		
 		debug!("Loading blocks shader...");
 		let shader = render::utility::Program::from_res(&res, "shaders/blocks")?;
 		
 		let uniform_matrix = shader.uniform_location("transform");
 		let uniform_atlas = shader.uniform_location("atlas");
+		let uniform_alpha_cutoff = shader.uniform_location("alpha_cutoff");
 		
 		Ok(ShaderBlocks {shader, texatlas,
 			uniform_matrix,
 			uniform_atlas,
+			uniform_alpha_cutoff,
 		})
 	}
 }
 
+/// Default number of threads meshing chunks in the background.
+const DEFAULT_MESH_THREADS: usize = 2;
+
+/// Default number of chunk meshes uploaded per frame.
+const DEFAULT_UPLOADS_PER_FRAME: usize = 4;
+
+/// How far the camera has to move, in blocks, before the translucent faces of a chunk are sorted again.
+const RESORT_DISTANCE: f32 = 1.0;
+
 pub struct ChunkRenderManager {
 	#[allow(dead_code)] // Not needed... yet.
 	blockdef: blockdef::UniverseRef,
 	
-	chunks: FxHashMap<ChunkCoord, (u128, ChunkMeshState)>,
+	chunks: FxHashMap<ChunkCoord, ChunkRenderState>,
 	material: ShaderBlocks,
-	mesher: ChunkMesher,
+	workers: MeshWorkerPool,
+	uploads_per_frame: usize,
 }
 
 impl ChunkRenderManager {
-	pub fn new(res: &resources::Resources, blockdef: blockdef::UniverseRef) -> Result<ChunkRenderManager, render::utility::Error> {
-		let material = ShaderBlocks::new(res)?;
+	/// Creates the renderer, loading the models of all blocks and baking their textures into an atlas.
+	///
+	/// Reads `meshing`, `mesh-threads` and `uploads-per-frame` from the `chunk-rendering` settings.
+	/// If `dump_dir` is given, the baked atlas is written there for debugging.
+	pub fn new(res: &resources::Resources, blockdef: blockdef::UniverseRef, settings: &settings::Settings, dump_dir: Option<&Path>) -> Result<ChunkRenderManager, render::utility::Error> {
+		let models = BlockModels::load(res, &blockdef)
+			.map_err(|e| render::utility::Error::BlockModels { inner: e })?;
+		
+		debug!("Baking blocks texture...");
+		let mut bakery = TextureBakery::new();
+		bakery.collect_textures(res, &blockdef);
+		bakery.collect_model_textures(res, &models);
+		let baked = bakery.bake()
+			.map_err(|e| render::utility::Error::AtlasBake { inner: e })?;
+		
+		if let Some(dump_dir) = dump_dir {
+			if let Err(e) = baked.dump(dump_dir) {
+				warn!("Failed to dump block texture atlas: {}", e);
+			}
+		}
+		
+		let material = ShaderBlocks::new(res, &baked.image)?;
+		
+		let get_count = |key: &str, default: usize| {
+			settings.table.get("chunk-rendering")
+				.and_then(|v| v.get(key))
+				.and_then(|v| v.as_integer())
+				.filter(|v| *v > 0)
+				.map(|v| v as usize)
+				.unwrap_or(default)
+		};
+		
+		let mesher = ChunkMesher::new(blockdef.clone(), &baked.atlas, &models, MeshingMode::from_settings(settings));
 		
 		Ok(ChunkRenderManager {
 			blockdef: blockdef.clone(),
 			chunks: FxHashMap::default(),
 			material,
-			mesher: ChunkMesher::new(blockdef.clone()),
+			workers: MeshWorkerPool::new(mesher, get_count("mesh-threads", DEFAULT_MESH_THREADS)),
+			uploads_per_frame: get_count("uploads-per-frame", DEFAULT_UPLOADS_PER_FRAME),
 		})
 	}
 	
-	pub fn render(&mut self, scene: &scene::Scene, transform: cgmath::Matrix4<f32>) {
+	pub fn render(&mut self, scene: &scene::Scene, transform: cgmath::Matrix4<f32>, camera: cgmath::Vector3<f32>) {
 		render::utility::gl_push_debug("chunks");
 		
+		self.update(scene, camera);
+		
 		self.material.shader.set_used();
 		self.material.shader.uniform_matrix4(self.material.uniform_matrix, transform);
 		self.material.shader.uniform_sampler(self.material.uniform_atlas, 0);
@@ -82,228 +136,243 @@ impl ChunkRenderManager {
 			gl::BindTexture(gl::TEXTURE_2D, self.material.texatlas.id);
 		}
 		
-		let mut max_uploads_per_frame: usize = 1;
-		for chunk in scene.chunks.chunks.iter() {
-			let cpos = &chunk.pos;
-			
-			if self.chunks.contains_key(cpos) {
-				let (time, mesh) = self.chunks.get_mut(cpos).unwrap();
-				
-				if chunk.last_update > *time {
-					*time = chunk.last_update;
-					*mesh = self.mesher.mesh(&chunk);
-				}
-				
-				if let ChunkMeshState::Meshed(mesh) = mesh{
-					mesh.draw();
-				}
-				
-			} else {
-				if max_uploads_per_frame > 0 {
-					max_uploads_per_frame -= 1;
-					let mesh = self.mesher.mesh(&chunk);
-					self.chunks.insert(cpos.clone(), (current_time_nanos(), mesh));
-				}
-			}
+		// Only draw the chunks in view, sorted front to back.
+		let frustum = Frustum::from_matrix(&transform);
+		let size = CHUNK_SIZE as f32;
+		
+		let mut visible: Vec<(f32, &mut ChunkMesh)> = self.chunks.iter_mut()
+			.filter_map(|(cpos, state)| match &mut state.mesh {
+				ChunkMeshState::Meshed(mesh) => Some((cpos.as_vec() * size, mesh)),
+				_ => None
+			})
+			.filter(|(min, _)| frustum.intersects_box(*min, *min + cgmath::Vector3::new(size, size, size)))
+			.map(|(min, mesh)| {
+				let center = min + cgmath::Vector3::new(0.5, 0.5, 0.5) * size;
+				((center - camera).magnitude2(), mesh)
+			})
+			.collect();
+		
+		visible.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+		
+		// Opaque and cutout faces front to back, so hidden fragments fail the depth test early...
+		unsafe {
+			gl::Disable(gl::BLEND);
+		}
+		
+		self.material.shader.uniform_scalar(self.material.uniform_alpha_cutoff, 0.0);
+		for (_, mesh) in visible.iter() {
+			mesh.draw(RenderLayer::Opaque);
+		}
+		
+		self.material.shader.uniform_scalar(self.material.uniform_alpha_cutoff, 0.5);
+		for (_, mesh) in visible.iter() {
+			mesh.draw(RenderLayer::Cutout);
+		}
+		
+		// ...and translucent faces back to front, blended over everything behind them,
+		// without hiding each other in the depth buffer.
+		unsafe {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+			gl::DepthMask(gl::FALSE);
+		}
+		
+		self.material.shader.uniform_scalar(self.material.uniform_alpha_cutoff, 0.0);
+		for (_, mesh) in visible.iter_mut().rev() {
+			mesh.sort_translucent(camera);
+			mesh.draw(RenderLayer::Translucent);
 		}
 		
 		unsafe {
+			gl::DepthMask(gl::TRUE);
+			gl::Disable(gl::BLEND);
 			gl::BindTexture(gl::TEXTURE_2D, 0);
 		}
 		
 		render::utility::gl_pop_debug();
 	}
 	
-}
+	/// Submits dirty chunks for meshing, and uploads as many finished meshes as the budget allows.
+	fn update(&mut self, scene: &scene::Scene, camera: cgmath::Vector3<f32>) {
+		// Forget chunks that are gone...
+		let workers = &self.workers;
+		self.chunks.retain(|cpos, _| {
+			let keep = scene.chunks.contains_chunk(cpos);
+			if !keep {
+				workers.cancel(cpos);
+			}
+			keep
+		});
+		
+		// ...queue the ones that changed since they were last submitted...
+		for chunk in scene.chunks.iter() {
+			let state = self.chunks.entry(chunk.pos).or_insert_with(ChunkRenderState::new);
+			
+			if chunk.last_update <= state.meshed || chunk.last_update <= state.queued {
+				continue;
+			}
+			
+			if let Some(snapshot) = scene.chunks.get_snapshot(&chunk.pos) {
+				let center = (chunk.pos.as_vec() + cgmath::Vector3::new(0.5, 0.5, 0.5)) * CHUNK_SIZE as f32;
+				let distance = (center - camera).magnitude2();
+				
+				state.queued = snapshot.last_update;
+				self.workers.submit(snapshot, distance);
+			}
+		}
+		
+		// ...and upload what has been meshed in the meantime.
+		let mut uploads = 0;
+		while uploads < self.uploads_per_frame {
+			let result = match self.workers.poll() {
+				Some(result) => result,
+				None => break
+			};
+			
+			// Results of chunks that were removed, or that are older than the current mesh, are dropped.
+			if let Some(state) = self.chunks.get_mut(&result.pos) {
+				if result.revision > state.meshed {
+					state.meshed = result.revision;
+					state.mesh = upload(&result.pos, result.mesh, camera);
+					uploads += 1;
+				}
+			}
+		}
+	}
 
-struct ChunkMesher {
-	blockdef: blockdef::UniverseRef,
 }
 
-impl ChunkMesher {
+/// The render-side bookkeeping of a single chunk.
+struct ChunkRenderState {
+	/// The `last_update` of the chunk the current mesh was built from.
+	meshed: u128,
+	
+	/// The newest `last_update` of the chunk that was submitted for meshing.
+	queued: u128,
 	
-	fn new(blockdef: blockdef::UniverseRef) -> ChunkMesher {
-		ChunkMesher {
-			blockdef
+	mesh: ChunkMeshState,
+}
+
+impl ChunkRenderState {
+	fn new() -> ChunkRenderState {
+		ChunkRenderState {
+			meshed: 0,
+			queued: 0,
+			mesh: ChunkMeshState::Empty,
 		}
 	}
+}
+
+/// Uploads the vertices of a chunk into a new mesh,
+/// with its translucent faces sorted for the given camera position.
+fn upload(pos: &ChunkCoord, mut mesh_data: ChunkMeshData, camera: cgmath::Vector3<f32>) -> ChunkMeshState {
+	// Don't upload empty meshes.
+	if mesh_data.is_empty() {
+		return ChunkMeshState::Empty
+	}
 	
-	fn mesh(&mut self, chunk: &Chunk) -> ChunkMeshState {
-		let mut vertices: Vec<ChunkMeshVertex> = vec![];
-		
-		let cpos = chunk.pos;
-		
-		const N: f32 = 0.0;
-		const S: f32 = 1.0;
-		
-		let air = self.blockdef
-			.get_block_by_name_unchecked("air")
-			.get_default_state();
-		
-		for y in 0..CHUNK_SIZE {
-			for z in 0..CHUNK_SIZE {
-				for x in 0..CHUNK_SIZE {
-					let x = x as isize;
-					let y = y as isize;
-					let z = z as isize;
-					let block = chunk.get_block(x, y, z).unwrap_or(air);
-					
-					if block == air {
-						continue;
-					}
-					
-					let cbp = vertices.len();
-					
-					// This line is the dumbest thing in the whole project...
-					let uv = BlockUv::new_from_pos(block.id.get_raw_id() as u8 - 1, 0);
-					// TODO: Implement the static block-bakery.
-					
-					if chunk.get_block(x,y+1,z).unwrap_or(air) == air {
-						Self::quad_to_tris(&[ // top
-							(N, S, S, uv.umin, uv.vmin).into(),
-							(S, S, S, uv.umax, uv.vmin).into(),
-							(S, S, N, uv.umax, uv.vmax).into(),
-							(N, S, N, uv.umin, uv.vmax).into(),
-						], &mut vertices);
-					}
-					
-					if chunk.get_block(x,y-1,z).unwrap_or(air) == air {
-						Self::quad_to_tris(&[ // bottom
-							(N, N, N, uv.umin, uv.vmin).into(),
-							(S, N, N, uv.umax, uv.vmin).into(),
-							(S, N, S, uv.umax, uv.vmax).into(),
-							(N, N, S, uv.umin, uv.vmax).into(),
-						], &mut vertices);
-					}
-					
-					if chunk.get_block(x,y,z-1).unwrap_or(air) == air {
-						Self::quad_to_tris(&[ // front
-							(N, S, N, uv.umin, uv.vmin).into(), // a
-							(S, S, N, uv.umax, uv.vmin).into(), // b
-							(S, N, N, uv.umax, uv.vmax).into(), // c
-							(N, N, N, uv.umin, uv.vmax).into(), // d
-						], &mut vertices);
-					}
-					
-					if chunk.get_block(x,y,z+1).unwrap_or(air) == air {
-						Self::quad_to_tris(&[ // back
-							(N, N, S, uv.umin, uv.vmin).into(), // d
-							(S, N, S, uv.umax, uv.vmin).into(), // c
-							(S, S, S, uv.umax, uv.vmax).into(), // b
-							(N, S, S, uv.umin, uv.vmax).into(), // a
-						], &mut vertices);
-					}
-					
-					if chunk.get_block(x-1,y,z).unwrap_or(air) == air {
-						Self::quad_to_tris(&[ // left
-							(N, S, S, uv.umin, uv.vmin).into(), // a
-							(N, S, N, uv.umax, uv.vmin).into(), // b
-							(N, N, N, uv.umax, uv.vmax).into(), // c
-							(N, N, S, uv.umin, uv.vmax).into(), // d
-						], &mut vertices);
-					}
-					
-					if chunk.get_block(x+1,y,z).unwrap_or(air) == air {
-						Self::quad_to_tris(&[ // right
-							(S, N, S, uv.umin, uv.vmin).into(), // d
-							(S, N, N, uv.umax, uv.vmin).into(), // c
-							(S, S, N, uv.umax, uv.vmax).into(), // b
-							(S, S, S, uv.umin, uv.vmax).into(), // a
-						], &mut vertices);
-					}
-					
-					for vertex in &mut vertices[cbp..] {
-						vertex.x += (x + cpos.x*CHUNK_SIZE as isize) as f32;
-						vertex.y += (y + cpos.y*CHUNK_SIZE as isize) as f32;
-						vertex.z += (z + cpos.z*CHUNK_SIZE as isize) as f32;
-					}
-				}
-			}
-		}
-		
-		return Self::upload(chunk, &vertices);
+	mesher::sort_back_to_front(mesh_data.get_mut(RenderLayer::Translucent), camera);
+	
+	let label = format!("Chunk({}, {}, {})", pos.x, pos.y, pos.z);
+	
+	let mut layers = [None, None, None];
+	for (index, layer) in RenderLayer::ALL.iter().enumerate() {
+		layers[index] = upload_layer(&format!("{} {}", label, layer.get_name()), mesh_data.get(*layer));
 	}
 	
-	fn upload(chunk: &Chunk, mesh_data: &Vec<ChunkMeshVertex>) -> ChunkMeshState {
-		// Don't upload empty meshes.
-		if mesh_data.len() == 0 {
-			return ChunkMeshState::Empty
-		}
-		
-		let vertex_count = mesh_data.len();
-		
-		let mut vbo: gl::types::GLuint = 0;
-		unsafe {
-			gl::GenBuffers(1, &mut vbo);
-			gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-			gl::BufferData(
-				gl::ARRAY_BUFFER,
-				(vertex_count * std::mem::size_of::<ChunkMeshVertex>()) as gl::types::GLsizeiptr,
-				mesh_data.as_ptr() as *const gl::types::GLvoid,
-				gl::STATIC_DRAW
-			);
-			gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-		}
+	ChunkMeshState::Meshed(ChunkMesh {
+		layers,
+		translucent: std::mem::replace(mesh_data.get_mut(RenderLayer::Translucent), vec![]),
+		sorted_for: camera,
+	})
+}
+
+/// Uploads the vertices of one layer of a chunk, if there are any.
+fn upload_layer(label: &str, mesh_data: &Vec<ChunkMeshVertex>) -> Option<LayerMesh> {
+	if mesh_data.is_empty() {
+		return None
+	}
+	
+	let vertex_count = mesh_data.len();
+	
+	let mut vbo: gl::types::GLuint = 0;
+	unsafe {
+		gl::GenBuffers(1, &mut vbo);
+		gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+		gl::BufferData(
+			gl::ARRAY_BUFFER,
+			(vertex_count * std::mem::size_of::<ChunkMeshVertex>()) as gl::types::GLsizeiptr,
+			mesh_data.as_ptr() as *const gl::types::GLvoid,
+			gl::STATIC_DRAW
+		);
+		gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+	}
+	
+	let mut vao: gl::types::GLuint = 0;
+	unsafe {
+		gl::GenVertexArrays(1, &mut vao);
+		gl::BindVertexArray(vao);
+		gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
 		
-		let mut vao: gl::types::GLuint = 0;
-		unsafe {
-			gl::GenVertexArrays(1, &mut vao);
-			gl::BindVertexArray(vao);
-			gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-			
-			gl::EnableVertexAttribArray(0);
-			gl::VertexAttribPointer(
-				0, // attribute location
-				3, // sub-element count
-				gl::FLOAT, // sub-element type
-				gl::FALSE, // sub-element normalization
-				(5 * std::mem::size_of::<f32>()) as gl::types::GLsizei,
-				(0 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid
-			);
-			
-			gl::EnableVertexAttribArray(1);
-			gl::VertexAttribPointer(
-				1, // attribute location
-				2, // sub-element count
-				gl::FLOAT, // sub-element type
-				gl::FALSE, // sub-element normalization
-				(5 * std::mem::size_of::<f32>()) as gl::types::GLsizei,
-				(3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid
-			);
-			
-			gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-			gl::BindVertexArray(0);
-		}
+		gl::EnableVertexAttribArray(0);
+		gl::VertexAttribPointer(
+			0, // attribute location
+			3, // sub-element count
+			gl::FLOAT, // sub-element type
+			gl::FALSE, // sub-element normalization
+			std::mem::size_of::<ChunkMeshVertex>() as gl::types::GLsizei,
+			(0 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid
+		);
 		
-		let label = format!("Chunk({}, {}, {})", chunk.pos.x, chunk.pos.y, chunk.pos.z);
+		gl::EnableVertexAttribArray(1);
+		gl::VertexAttribPointer(
+			1, // attribute location
+			2, // sub-element count
+			gl::FLOAT, // sub-element type
+			gl::FALSE, // sub-element normalization
+			std::mem::size_of::<ChunkMeshVertex>() as gl::types::GLsizei,
+			(3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid
+		);
 		
-		render::utility::gl_label_object(
-			gl::VERTEX_ARRAY, vao,
-			&format!("{} Descriptor", label)
+		gl::EnableVertexAttribArray(2);
+		gl::VertexAttribPointer(
+			2, // attribute location
+			4, // sub-element count
+			gl::FLOAT, // sub-element type
+			gl::FALSE, // sub-element normalization
+			std::mem::size_of::<ChunkMeshVertex>() as gl::types::GLsizei,
+			(5 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid
 		);
 		
-		render::utility::gl_label_object(
-			gl::BUFFER, vbo,
-			&format!("{} Geometry", label)
+		gl::EnableVertexAttribArray(3);
+		gl::VertexAttribPointer(
+			3, // attribute location
+			2, // sub-element count
+			gl::FLOAT, // sub-element type
+			gl::FALSE, // sub-element normalization
+			std::mem::size_of::<ChunkMeshVertex>() as gl::types::GLsizei,
+			(9 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid
 		);
 		
-		return ChunkMeshState::Meshed(ChunkMesh {
-			descriptor: vao,
-			vertex_buf: vbo,
-			count: vertex_count as i32
-		})
+		gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+		gl::BindVertexArray(0);
 	}
 	
-	fn quad_to_tris(src: &[ChunkMeshVertex; 4], dst: &mut Vec<ChunkMeshVertex>) {
-		dst.reserve(6);
-		dst.push(src[0]);
-		dst.push(src[1]);
-		dst.push(src[3]);
-		dst.push(src[1]);
-		dst.push(src[2]);
-		dst.push(src[3]);
-	}
+	render::utility::gl_label_object(
+		gl::VERTEX_ARRAY, vao,
+		&format!("{} Descriptor", label)
+	);
+	
+	render::utility::gl_label_object(
+		gl::BUFFER, vbo,
+		&format!("{} Geometry", label)
+	);
 	
+	Some(LayerMesh {
+		descriptor: vao,
+		vertex_buf: vbo,
+		count: vertex_count as i32
+	})
 }
 
 /// The graphical state of a chunk.
@@ -315,24 +384,81 @@ enum ChunkMeshState {
 	Meshed(ChunkMesh),
 }
 
-/// The graphical representation of a chunk.
-/// Really just a bag of OpenGL Object-Handles.
+/// The graphical representation of a chunk, one mesh per render layer.
 struct ChunkMesh {
+	/// Indexed like `RenderLayer::ALL`; `None` for empty layers.
+	layers: [Option<LayerMesh>; 3],
+	
+	/// A copy of the translucent vertices, to sort them again when the camera moves.
+	translucent: Vec<ChunkMeshVertex>,
+	
+	/// The camera position the translucent vertices were last sorted for.
+	sorted_for: cgmath::Vector3<f32>,
+}
+
+impl ChunkMesh {
+	pub fn draw(&self, layer: RenderLayer) {
+		if let Some(mesh) = &self.layers[layer as usize] {
+			mesh.draw();
+		}
+	}
+	
+	/// Sorts the translucent faces back to front again, if the camera moved far enough since the last time.
+	pub fn sort_translucent(&mut self, camera: cgmath::Vector3<f32>) {
+		let mesh = match &self.layers[RenderLayer::Translucent as usize] {
+			Some(mesh) => mesh,
+			None => return
+		};
+		
+		if !needs_resort(self.sorted_for, camera) {
+			return;
+		}
+		
+		mesher::sort_back_to_front(&mut self.translucent, camera);
+		mesh.update(&self.translucent);
+		self.sorted_for = camera;
+	}
+}
+
+/// Whether translucent faces sorted for one camera position have to be sorted again for another.
+fn needs_resort(sorted_for: cgmath::Vector3<f32>, camera: cgmath::Vector3<f32>) -> bool {
+	(camera - sorted_for).magnitude2() >= RESORT_DISTANCE * RESORT_DISTANCE
+}
+
+/// The mesh of a single layer of a chunk.
+/// Really just a bag of OpenGL Object-Handles.
+struct LayerMesh {
 	descriptor: gl::types::GLuint,
 	vertex_buf: gl::types::GLuint,
 	count: i32,
 }
 
-impl ChunkMesh {
+impl LayerMesh {
 	pub fn draw(&self) {
 		unsafe {
 			gl::BindVertexArray(self.descriptor);
 			gl::DrawArrays(gl::TRIANGLES, 0, self.count);
 		}
 	}
+	
+	/// Replaces the vertices of the mesh with the same number of other vertices.
+	pub fn update(&self, mesh_data: &Vec<ChunkMeshVertex>) {
+		debug_assert_eq!(mesh_data.len(), self.count as usize);
+		
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buf);
+			gl::BufferSubData(
+				gl::ARRAY_BUFFER,
+				0,
+				(mesh_data.len() * std::mem::size_of::<ChunkMeshVertex>()) as gl::types::GLsizeiptr,
+				mesh_data.as_ptr() as *const gl::types::GLvoid
+			);
+			gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+		}
+	}
 }
 
-impl Drop for ChunkMesh {
+impl Drop for LayerMesh {
 	fn drop(&mut self) {
 		unsafe {
 			let tmp = [self.vertex_buf];
@@ -344,50 +470,20 @@ impl Drop for ChunkMesh {
 	}
 }
 
-#[derive(Copy, Clone, Debug)]
-#[repr(C, packed)]
-struct ChunkMeshVertex {
-	// Geometry
-	pub x: f32,
-	pub y: f32,
-	pub z: f32,
+#[cfg(test)]
+mod tests {
+	use super::*;
 	
-	// Texture
-	pub u: f32,
-	pub v: f32,
-}
-
-impl ChunkMeshVertex {
-	pub fn new(x: f32, y: f32, z: f32, u: f32, v: f32) -> Self {
-		Self {
-			x, y, z, u, v
-		}
-	}
-}
-
-impl From<(f32, f32, f32, f32, f32)> for ChunkMeshVertex {
-	fn from(other: (f32, f32, f32, f32, f32)) -> Self {
-		Self::new(other.0, other.1, other.2, other.3, other.4)
+	#[test]
+	fn translucent_faces_are_sorted_again_after_a_block() {
+		let sorted_for = cgmath::Vector3::new(10.0, 20.0, 30.0);
+		
+		assert!(!needs_resort(sorted_for, sorted_for));
+		assert!(!needs_resort(sorted_for, sorted_for + cgmath::Vector3::new(0.5, 0.5, 0.5)));
+		assert!(!needs_resort(sorted_for, sorted_for + cgmath::Vector3::new(0.0, -0.99, 0.0)));
+		
+		assert!(needs_resort(sorted_for, sorted_for + cgmath::Vector3::new(1.0, 0.0, 0.0)));
+		assert!(needs_resort(sorted_for, sorted_for + cgmath::Vector3::new(0.6, 0.6, 0.6)));
+		assert!(needs_resort(sorted_for, sorted_for - cgmath::Vector3::new(0.0, 0.0, 5.0)));
 	}
 }
-
-struct BlockUv {
-	umin: f32,
-	umax: f32,
-	vmin: f32,
-	vmax: f32,
-}
-
-impl BlockUv {
-	fn new_from_pos(x: u8, y: u8) -> Self {
-		let x = (x as f32) / 16.0;
-		let y = (y as f32) / 16.0;
-		let s = 1.0 / 16.0;
-		Self {
-			umin: x,
-			umax: x+s,
-			vmin: y,
-			vmax: y+s,
-		}
-	}
-}
\ No newline at end of file
@@ -0,0 +1,374 @@
+//! A pool of threads that mesh chunks in the background.
+//!
+//! The render thread submits snapshots of dirty chunks, which are meshed nearest-first;
+//! finished vertex data is collected again on the render thread, which does the uploading.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex, Condvar};
+use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
+use std::thread;
+use rustc_hash::FxHashMap;
+use super::super::super::blocks::{ChunkCoord, ChunkSnapshot};
+use super::mesher::{ChunkMesher, ChunkMeshData};
+
+/// A chunk waiting to be meshed.
+struct MeshJob {
+	/// Squared distance of the chunk to the camera, at the time it was submitted.
+	distance: f32,
+	snapshot: ChunkSnapshot,
+}
+
+impl PartialEq for MeshJob {
+	fn eq(&self, other: &MeshJob) -> bool {
+		self.distance == other.distance
+	}
+}
+
+impl Eq for MeshJob {}
+
+impl PartialOrd for MeshJob {
+	fn partial_cmp(&self, other: &MeshJob) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for MeshJob {
+	/// Nearer chunks are 'greater', so they come out of the heap first.
+	fn cmp(&self, other: &MeshJob) -> Ordering {
+		other.distance.partial_cmp(&self.distance).unwrap_or(Ordering::Equal)
+	}
+}
+
+/// The vertices of a meshed chunk.
+pub struct MeshResult {
+	pub pos: ChunkCoord,
+	
+	/// The `last_update` of the chunk the mesh was built from.
+	pub revision: u128,
+	
+	pub mesh: ChunkMeshData,
+}
+
+/// State shared between the render thread and the workers.
+struct MeshQueue {
+	jobs: BinaryHeap<MeshJob>,
+	
+	/// The newest revision submitted for every chunk;
+	/// jobs for older revisions are skipped, as they would be thrown away anyway.
+	latest: FxHashMap<ChunkCoord, u128>,
+	
+	shutdown: bool,
+}
+
+impl MeshQueue {
+	/// Queues a chunk, superseding the jobs of older revisions of it.
+	fn push(&mut self, snapshot: ChunkSnapshot, distance: f32) {
+		let latest = self.latest.entry(snapshot.pos).or_insert(0);
+		*latest = (*latest).max(snapshot.last_update);
+		
+		self.jobs.push(MeshJob {
+			distance,
+			snapshot
+		});
+	}
+	
+	/// Takes the nearest job that has not been superseded by a newer revision of its chunk.
+	fn pop(&mut self) -> Option<MeshJob> {
+		while let Some(job) = self.jobs.pop() {
+			let latest = self.latest.get(&job.snapshot.pos).cloned().unwrap_or(0);
+			if job.snapshot.last_update >= latest {
+				return Some(job);
+			}
+		}
+		None
+	}
+	
+	/// Drops all jobs of the given chunk.
+	fn cancel(&mut self, pos: &ChunkCoord) {
+		if self.latest.remove(pos).is_some() {
+			let jobs = std::mem::replace(&mut self.jobs, BinaryHeap::new());
+			self.jobs = jobs.into_iter()
+				.filter(|job| job.snapshot.pos != *pos)
+				.collect();
+		}
+	}
+	
+	/// Returns `false` for results of chunks that were cancelled.
+	///
+	/// Results of superseded revisions are still wanted, as they are newer than the mesh on screen.
+	fn is_wanted(&self, result: &MeshResult) -> bool {
+		self.latest.contains_key(&result.pos)
+	}
+}
+
+pub struct MeshWorkerPool {
+	queue: Arc<(Mutex<MeshQueue>, Condvar)>,
+	results: Receiver<MeshResult>,
+	workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl MeshWorkerPool {
+	/// Starts the given number of worker threads (at least one), all sharing the same mesher.
+	pub fn new(mesher: ChunkMesher, threads: usize) -> MeshWorkerPool {
+		let mesher = Arc::new(mesher);
+		let queue = Arc::new((Mutex::new(MeshQueue {
+			jobs: BinaryHeap::new(),
+			latest: FxHashMap::default(),
+			shutdown: false,
+		}), Condvar::new()));
+		
+		let (sender, results) = channel();
+		
+		let workers = (0..threads.max(1)).map(|index| {
+			let mesher = mesher.clone();
+			let queue = queue.clone();
+			let sender = sender.clone();
+			
+			thread::Builder::new()
+				.name(format!("chunk-mesher-{}", index))
+				.spawn(move || Self::work(&mesher, &queue, &sender))
+				.expect("Failed to spawn chunk meshing thread.")
+		}).collect();
+		
+		debug!("Started {} chunk meshing threads.", threads.max(1));
+		
+		MeshWorkerPool {
+			queue,
+			results,
+			workers,
+		}
+	}
+	
+	fn work(mesher: &ChunkMesher, queue: &(Mutex<MeshQueue>, Condvar), results: &Sender<MeshResult>) {
+		let (lock, condvar) = queue;
+		
+		loop {
+			let job = {
+				let mut queue = lock.lock().expect("Chunk meshing queue is poisoned.");
+				loop {
+					if queue.shutdown {
+						return;
+					}
+					
+					match queue.pop() {
+						Some(job) => break job,
+						None => queue = condvar.wait(queue).expect("Chunk meshing queue is poisoned.")
+					}
+				}
+			};
+			
+			let mesh = mesher.mesh(&job.snapshot);
+			
+			let result = MeshResult {
+				pos: job.snapshot.pos,
+				revision: job.snapshot.last_update,
+				mesh,
+			};
+			
+			if results.send(result).is_err() {
+				return; // the pool is gone
+			}
+		}
+	}
+	
+	/// Queues a chunk for meshing; nearer chunks are meshed first.
+	pub fn submit(&self, snapshot: ChunkSnapshot, distance: f32) {
+		let (lock, condvar) = &*self.queue;
+		let mut queue = lock.lock().expect("Chunk meshing queue is poisoned.");
+		queue.push(snapshot, distance);
+		condvar.notify_one();
+	}
+	
+	/// Forgets about a chunk, dropping any of its jobs that have not started yet,
+	/// and the results of the ones that have.
+	pub fn cancel(&self, pos: &ChunkCoord) {
+		let (lock, _) = &*self.queue;
+		lock.lock().expect("Chunk meshing queue is poisoned.").cancel(pos);
+	}
+	
+	/// Returns the next finished mesh, if there is one.
+	///
+	/// Meshes of chunks that were cancelled in the meantime are skipped.
+	pub fn poll(&self) -> Option<MeshResult> {
+		loop {
+			let result = match self.results.try_recv() {
+				Ok(result) => result,
+				Err(TryRecvError::Empty) => return None,
+				Err(TryRecvError::Disconnected) => return None,
+			};
+			
+			let (lock, _) = &*self.queue;
+			if lock.lock().map(|queue| queue.is_wanted(&result)).unwrap_or(false) {
+				return Some(result);
+			}
+		}
+	}
+	
+	/// The number of chunks waiting to be meshed.
+	pub fn get_queue_len(&self) -> usize {
+		let (lock, _) = &*self.queue;
+		lock.lock().map(|queue| queue.jobs.len()).unwrap_or(0)
+	}
+}
+
+impl Drop for MeshWorkerPool {
+	fn drop(&mut self) {
+		{
+			let (lock, condvar) = &*self.queue;
+			if let Ok(mut queue) = lock.lock() {
+				queue.shutdown = true;
+				queue.jobs.clear();
+			}
+			condvar.notify_all();
+		}
+		
+		for worker in self.workers.drain(..) {
+			worker.join().ok();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::{Duration, Instant};
+	use super::super::super::super::blocks::UniverseRef;
+	use super::super::super::super::blocks::universe::define_universe;
+	use super::super::super::super::blocks::world::{Chunk, ChunkStorage};
+	use super::super::atlas::MISSING_TEXTURE;
+	use super::super::bakery::TextureBakery;
+	use super::super::mesher::MeshingMode;
+	use super::super::model::BlockModels;
+	
+	fn universe() -> UniverseRef {
+		let config: toml::Value = "[blocks.stone]".parse().unwrap();
+		define_universe(config.as_table().unwrap())
+	}
+	
+	fn pool(blockdef: &UniverseRef, threads: usize) -> MeshWorkerPool {
+		let mut bakery = TextureBakery::new();
+		for name in &[MISSING_TEXTURE, "bedrock", "stone"] {
+			bakery.add_texture(name, TextureBakery::generate_missing_texture(16));
+		}
+		let atlas = bakery.bake().unwrap().atlas;
+		
+		let mesher = ChunkMesher::new(blockdef.clone(), &atlas, &BlockModels::new(), MeshingMode::Greedy);
+		MeshWorkerPool::new(mesher, threads)
+	}
+	
+	/// A snapshot of an empty chunk at the given position, claiming to be of the given revision.
+	fn snapshot(blockdef: &UniverseRef, x: isize, revision: u128) -> ChunkSnapshot {
+		let mut storage = ChunkStorage::new_empty(blockdef.clone(), &toml::value::Table::new()).unwrap();
+		let chunk = Chunk::new_empty(blockdef.clone(), x, 0, 0);
+		let pos = chunk.pos;
+		storage.insert_chunk(chunk);
+		
+		let mut snapshot = storage.get_snapshot(&pos).unwrap();
+		snapshot.last_update = revision;
+		snapshot
+	}
+	
+	fn queue() -> MeshQueue {
+		MeshQueue {
+			jobs: BinaryHeap::new(),
+			latest: FxHashMap::default(),
+			shutdown: false,
+		}
+	}
+	
+	/// Takes all jobs from the queue, as `(x, revision)`.
+	fn drain(queue: &mut MeshQueue) -> Vec<(isize, u128)> {
+		std::iter::from_fn(|| queue.pop())
+			.map(|job| (job.snapshot.pos.x, job.snapshot.last_update))
+			.collect()
+	}
+	
+	/// Polls the pool until a result comes in, failing after a few seconds.
+	fn wait_for_result(pool: &MeshWorkerPool) -> MeshResult {
+		let start = Instant::now();
+		loop {
+			if let Some(result) = pool.poll() {
+				return result;
+			}
+			assert!(start.elapsed() < Duration::from_secs(10), "no mesh arrived");
+			thread::sleep(Duration::from_millis(1));
+		}
+	}
+	
+	#[test]
+	fn nearest_chunks_are_meshed_first() {
+		let blockdef = universe();
+		let mut queue = queue();
+		queue.push(snapshot(&blockdef, 0, 1), 25.0);
+		queue.push(snapshot(&blockdef, 1, 1), 1.0);
+		queue.push(snapshot(&blockdef, 2, 1), 9.0);
+		queue.push(snapshot(&blockdef, 3, 1), 4.0);
+		
+		assert_eq!(drain(&mut queue), vec![(1, 1), (3, 1), (2, 1), (0, 1)]);
+	}
+	
+	#[test]
+	fn newer_revisions_replace_queued_ones() {
+		let blockdef = universe();
+		let mut queue = queue();
+		queue.push(snapshot(&blockdef, 0, 1), 1.0);
+		queue.push(snapshot(&blockdef, 1, 1), 4.0);
+		queue.push(snapshot(&blockdef, 0, 2), 9.0);
+		
+		// The old revision is skipped, even though it was nearer.
+		assert_eq!(drain(&mut queue), vec![(1, 1), (0, 2)]);
+	}
+	
+	#[test]
+	fn cancelled_chunks_are_dropped() {
+		let blockdef = universe();
+		let mut queue = queue();
+		queue.push(snapshot(&blockdef, 0, 1), 1.0);
+		queue.push(snapshot(&blockdef, 1, 1), 4.0);
+		queue.push(snapshot(&blockdef, 0, 2), 9.0);
+		queue.cancel(&ChunkCoord::new_from_chunk(0, 0, 0));
+		
+		assert_eq!(queue.jobs.len(), 1);
+		assert_eq!(drain(&mut queue), vec![(1, 1)]);
+	}
+	
+	#[test]
+	fn results_of_cancelled_chunks_are_dropped() {
+		let blockdef = universe();
+		let pool = pool(&blockdef, 1);
+		
+		pool.submit(snapshot(&blockdef, 0, 7), 1.0);
+		let result = wait_for_result(&pool);
+		assert_eq!((result.pos.x, result.revision), (0, 7));
+		
+		// Cancelled once the single worker has taken the job...
+		pool.submit(snapshot(&blockdef, 1, 1), 1.0);
+		let start = Instant::now();
+		while pool.get_queue_len() > 0 {
+			assert!(start.elapsed() < Duration::from_secs(10), "the job was never taken");
+			thread::sleep(Duration::from_millis(1));
+		}
+		pool.cancel(&ChunkCoord::new_from_chunk(1, 0, 0));
+		
+		// ...so its result is sent before that of the next chunk, and has to be skipped.
+		pool.submit(snapshot(&blockdef, 2, 1), 1.0);
+		let result = wait_for_result(&pool);
+		assert_eq!((result.pos.x, result.revision), (2, 1));
+		assert!(pool.poll().is_none());
+	}
+	
+	#[test]
+	fn dropping_the_pool_stops_the_workers() {
+		let blockdef = universe();
+		let pool = pool(&blockdef, 3);
+		for x in 0..16 {
+			pool.submit(snapshot(&blockdef, x, 1), x as f32);
+		}
+		
+		// Every worker holds on to the queue until its thread ends.
+		let queue = pool.queue.clone();
+		drop(pool);
+		assert_eq!(Arc::strong_count(&queue), 1);
+	}
+}
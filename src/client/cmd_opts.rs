@@ -11,6 +11,7 @@ pub struct CmdOptions {
 	pub height: u32,
 	pub gl_debug: bool,
 	pub gl_multisamples: u32,
+	pub dump_atlas: Option<String>,
 }
 
 pub fn parse() -> Result<CmdOptions, failure::Error> {
@@ -67,6 +68,14 @@ pub fn parse() -> Result<CmdOptions, failure::Error> {
 			.help("Enables OpenGL debugging.")
 		)
 		
+		.arg(Arg::with_name("dump_atlas")
+			.help("Writes the baked block texture atlas and its index into the given directory.")
+			.long("dump_atlas")
+			.value_name("DUMP_DIR")
+			.takes_value(true)
+			.require_equals(true)
+		)
+		
 		.arg(Arg::with_name("PATH")
 			.help("Where to navigate to when the client-lens is created.")
 			.index(1)
@@ -90,5 +99,8 @@ pub fn parse() -> Result<CmdOptions, failure::Error> {
 		gl_multisamples: matches.value_of("GL_SAMPLES")
 			.unwrap_or("0").parse::<u32>()?
 		,
+		dump_atlas: matches.value_of("dump_atlas")
+			.map(|dir| dir.to_string())
+		,
 	})
 }
@@ -1,6 +1,7 @@
 //! Module for managing the game-window and associated events (mostly GLFW).
 
 use super::super::router;
+use super::super::router::comp::ComponentHandle;
 use super::cmd_opts;
 use super::scene;
 
@@ -9,6 +10,7 @@ use std::sync::mpsc::Receiver;
 use std::cell::RefMut;
 use std::ops::DerefMut;
 use crate::blocks::BlockCoord;
+use crate::blocks::BlockRaycast;
 
 pub struct GlfwContextComponent {
 	pub glfw: glfw::Glfw,
@@ -109,10 +111,14 @@ impl GlfwContextComponent {
 		})
 	}
 	
-	pub fn process_events(&mut self, router: &mut RefMut<router::Router>) {
+	/// Handles the window events that came in since the last call.
+	///
+	/// The context is only borrowed while it is needed, so the events fired from here reach every component.
+	pub fn process_events(gfx: &ComponentHandle<GlfwContextComponent>, router: &mut RefMut<router::Router>) {
 		let router = router.deref_mut();
+		let events: Vec<_> = glfw::flush_messages(&gfx.borrow().events).collect();
 		
-		for(_, event) in glfw::flush_messages(&mut self.events) {
+		for (_, event) in events {
 			match event {
 				glfw::WindowEvent::FramebufferSize(width, height) => {
 					trace!("Resizing viewport to {}x{}", width, height);
@@ -121,76 +127,91 @@ impl GlfwContextComponent {
 				
 				glfw::WindowEvent::Key(Key::M, _, Action::Release, _) => {
 					let new_state = GlfwContextComponent::toggle_cursor_mode(
-						&mut self.window,
+						&mut gfx.borrow_mut().window,
 						None // toggle
 					);
 					
-					if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
-						scene.camera.active = new_state == glfw::CursorMode::Disabled
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						scene.borrow_mut().camera.active = new_state == glfw::CursorMode::Disabled
 					}
 				},
 				
 				glfw::WindowEvent::Key(Key::C, _, Action::Release, _) => {
-					if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						let mut scene = scene.borrow_mut();
 						scene.camera.crane = !scene.camera.crane;
 					}
 				},
 				
+				glfw::WindowEvent::Key(Key::F, _, Action::Release, _) => {
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						let mut scene = scene.borrow_mut();
+						scene.player.mode = scene.player.mode.next();
+						info!("Switched to {}-mode.", scene.player.mode.get_name());
+					}
+				},
+				
 				glfw::WindowEvent::Key(Key::F5, _, Action::Release, _) => {
 					info!("User pressed R, reloading settings...");
-					if let Ok(settings) = router.nodes.get_mut_node_component_downcast::<super::settings::Settings>(0) {
-						if let Ok(_) = settings.load() {
+					if let Ok(settings) = router.nodes.get_node_component_downcast::<super::settings::Settings>(0) {
+						// The settings must not be borrowed anymore when the event reaches the components.
+						let loaded = settings.borrow_mut().load();
+						if let Ok(_) = loaded {
 							router.fire_event_at_lens("client", &mut super::settings::SettingsReloadEvent::new(settings));
 						}
 					}
 				},
 				
 				glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+					let mut gfx = gfx.borrow_mut();
 					let current = super::super::util::current_time_nanos();
 					
-					if (current - self.last_esc) < 500000000 {
+					if (current - gfx.last_esc) < 500000000 {
 						info!("User pressed ESC twice, shutting down...");
-						self.window.set_should_close(true)
+						gfx.window.set_should_close(true)
 					} else {
 						info!("User pressed ESC once...");
-						self.last_esc = current;
+						gfx.last_esc = current;
 					}
 				},
 				
 				glfw::WindowEvent::Key(Key::Num1, _, Action::Press, _) => {
-					if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						let mut scene = scene.borrow_mut();
 						scene.camera.block = Some(scene.blockdef.get_block_by_name_unchecked("bedrock").get_default_state());
 					}
 				},
 				
 				glfw::WindowEvent::Key(Key::Num2, _, Action::Press, _) => {
-					if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						let mut scene = scene.borrow_mut();
 						scene.camera.block = Some(scene.blockdef.get_block_by_name_unchecked("bedrock2").get_default_state());
 					}
 				},
 				
 				glfw::WindowEvent::Key(Key::Num3, _, Action::Press, _) => {
-					if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						let mut scene = scene.borrow_mut();
 						scene.camera.block = Some(scene.blockdef.get_block_by_name_unchecked("bedrock3").get_default_state());
 					}
 				},
 				
 				glfw::WindowEvent::MouseButton(button, Action::Press, _) => {
-					if self.window.get_cursor_mode() != glfw::CursorMode::Disabled {
+					if gfx.borrow().window.get_cursor_mode() != glfw::CursorMode::Disabled {
 						continue;
 					}
 					
-					let scene = match router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
+					let scene = match router.nodes.get_node_component_downcast::<scene::Scene>(0) {
 						Ok(scene) => scene,
 						Err(_) => return
 					};
+					let mut scene = scene.borrow_mut();
 					
 					let src = scene.camera.get_position(1.0);
 					let dir = scene.camera.get_look_dir(1.0);
 					let len = 16.0;
 					
-					use super::blocks;
-					let mut rc = blocks::BlockRaycast::new_from_src_dir_len(src, dir, len);
+					let mut rc = BlockRaycast::new_from_src_dir_len(src, dir, len);
 					
 					let air = scene.blockdef
 						.get_block_by_name_unchecked("air")
@@ -213,7 +234,7 @@ impl GlfwContextComponent {
 							_ => continue
 						};
 						
-						if self.window.get_key(Key::X) != Action::Press {
+						if gfx.borrow().window.get_key(Key::X) != Action::Press {
 							scene.chunks.set_block(&pos, block);
 						}
 						else {
@@ -235,13 +256,14 @@ impl GlfwContextComponent {
 				},
 				
 				glfw::WindowEvent::CursorPos(x, y) => {
-					self.cursor.update(x, y);
+					let (mov_x, mov_y) = {
+						let mut gfx = gfx.borrow_mut();
+						gfx.cursor.update(x, y);
+						(gfx.cursor.mov_x, gfx.cursor.mov_y)
+					};
 					
-					if let Ok(scene) = router.nodes.get_mut_node_component_downcast::<scene::Scene>(0) {
-						scene.camera.update_rotation(
-							self.cursor.mov_x,
-							self.cursor.mov_y
-						);
+					if let Ok(scene) = router.nodes.get_node_component_downcast::<scene::Scene>(0) {
+						scene.borrow_mut().camera.update_rotation(mov_x, mov_y);
 					}
 				},
 				_ => ()
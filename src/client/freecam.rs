@@ -1,9 +1,9 @@
-//! This module defines a free-flying camera for debugging purposes.
+//! This module defines the camera, which looks through the eyes of the player.
+//!
+//! The movement itself is done by the [`PlayerController`](../player/struct.PlayerController.html).
 
 // TODO: Integrate the debug-camera with the ECS once it comes up...
-// TODO: The camera will need to be abstracted into a PlayerController...
 
-use super::glfw::{Key, Action};
 use super::settings;
 use super::super::blocks;
 
@@ -21,7 +21,6 @@ pub struct Camera {
 	velocity: cgmath::Vector3<f32>,
 	rotation: cgmath::Vector2<f32>,
 	position_last: cgmath::Vector3<f32>,
-	velocity_last: cgmath::Vector3<f32>,
 	rotation_last: cgmath::Vector2<f32>,
 	pub target: Option<blocks::BlockCoord>,
 	pub block: Option<blocks::BlockState>,
@@ -31,7 +30,6 @@ pub struct Camera {
 	fov_vel_effect: bool,
 	mouse_sensivity: f32,
 	invert_mouse: bool,
-	move_speed: f32,
 	pub crane: bool,
 }
 
@@ -44,7 +42,6 @@ impl Camera {
 			velocity: cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0 },
 			rotation: cgmath::Vector2 { x: 0.0, y: 0.0 },
 			position_last: cgmath::Vector3 { x: 0.0, y: 1.8, z: 0.0 },
-			velocity_last: cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0 },
 			rotation_last: cgmath::Vector2 { x: 0.0, y: 90.0 },
 			target: None,
 			block: None,
@@ -54,7 +51,6 @@ impl Camera {
 			fov_vel_effect: false,
 			mouse_sensivity: 0.25,
 			invert_mouse: false,
-			move_speed: 2.0 / 30.0,
 			crane: true
 		}
 	}
@@ -80,10 +76,6 @@ impl Camera {
 			self.invert_mouse = v.as_bool().expect("Value 'mouse-inverted' is not a bool.");
 		}
 		
-		if let Some(v) = controls.get("movement-speed") {
-			self.move_speed = v.as_float().expect("Value 'movement-speed' is not a float.") as f32 / 30.0;
-		}
-		
 		if let Some(v) = controls.get("crane") {
 			self.crane = v.as_bool().expect("Value 'crane' is not a bool.") as bool;
 		}
@@ -189,69 +181,14 @@ impl Camera {
 		self.rotation.y = wrap(self.rotation.y, 360.0);
 	}
 	
-	/// Updates the camera position by querying key-states and changing the velocity accordingly.
-	pub fn update_movement(&mut self, window: &glfw::Window) {
+	/// Moves the camera to the given position, e.g. the eyes of the player.
+	///
+	/// The velocity is the distance the camera is expected to move until the next tick,
+	/// which is used to predict its position in between ticks.
+	pub fn follow(&mut self, position: cgmath::Vector3<f32>, velocity: cgmath::Vector3<f32>) {
 		self.position_last.clone_from(&self.position);
-		self.velocity_last.clone_from(&self.velocity);
-		
-		if !self.active {
-			return;
-		}
-		
-		let mut move_speed = self.move_speed;
-		
-		// --- Apply speed multiplier?
-		if window.get_key(Key::LeftShift) == Action::Press {
-			move_speed *= 5.0;
-		}
-		
-		// --- Construct velocity vector...
-		let yaw = cgmath::Deg(self.rotation.y);
-		let mut mat = Matrix4::from_angle_y(yaw);
-		
-		// Fetch the input statuses and convert them to 0/1...
-		let forwards = (window.get_key(Key::W) == Action::Press) as i8;
-		let backwards = (window.get_key(Key::S) == Action::Press) as i8;
-		let strafe_left = (window.get_key(Key::A) == Action::Press) as i8;
-		let strafe_right = (window.get_key(Key::D) == Action::Press) as i8;
-		
-		let mut direction = Vector3::new(0.0, 0.0, 0.0);
-		
-		// ...then build a direction vector from them.
-		// - If neither are active, the result is 0.
-		// - If only 'forwards'  is active, the result is +1.
-		// - If only 'backwards' is active, the result is -1.
-		// - If both are active, cancelling each other out, the result is 0.
-		direction.z += (forwards - backwards) as f32;
-		direction.x += (strafe_right - strafe_left) as f32;
-		
-		// crane or drone mode for y axis
-		if self.crane {
-			// CRANE: The camera pitch does not affect planar movement.
-			let up = (window.get_key(Key::Space) == Action::Press) as i8;
-			let down = (window.get_key(Key::LeftControl) == Action::Press) as i8;
-			direction.y += (up - down) as f32;
-		}
-		else {
-			// DRONE: The camera pitch tilts the plane of movement.
-			let pitch = cgmath::Deg(self.rotation.x);
-			mat = mat * Matrix4::from_angle_x(pitch);
-		}
-		
-		// Ensure that the vector has a magnitude of 1 (equal in all directions)
-		direction.normalize();
-		
-		// Transform the new velocity vector into world-space...
-		let direction = Matrix4::transform_vector(&mat, direction);
-		
-		// ...and add it to the existing velocity vector.
-		self.velocity += direction * move_speed;
-		
-		// Apply velocity
-		self.position += self.velocity;
-		
-		// Friction
-		self.velocity *= 0.75;
+		self.position = position;
+		self.velocity = velocity;
 	}
 }
 
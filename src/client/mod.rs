@@ -9,6 +9,7 @@ pub mod context;
 pub mod scene;
 pub mod render;
 pub mod freecam;
+pub mod player;
 pub mod blocks;
 
 pub use render::geometry;
@@ -17,7 +18,10 @@ pub use render::geometry;
 
 
 /// Generic `Event` representing a tick being computed.
-pub struct TickEvent {}
+pub struct TickEvent {
+	/// The fixed length of a tick, in seconds.
+	pub delta: f64,
+}
 impl super::router::event::Event for TickEvent {
 	fn is_passive(&self) -> bool {false}
 }
@@ -0,0 +1,658 @@
+//! The player: a box that walks, jumps and flies through the block-world.
+//!
+//! The physics are advanced in fixed ticks and only depend on the world, the input and the
+//! length of a tick, so the same inputs always lead to the same movement; nothing in here
+//! touches OpenGL, and only `PlayerInput::from_window` needs a window.
+//!
+//! Configured by the `[player]` table of the settings:
+//!
+//! ```toml
+//! [player]
+//! mode = "walk"      # or "fly", or "noclip"
+//! walk-speed = 4.3   # blocks per second
+//! fly-speed = 6.0
+//! jump-height = 1.25 # blocks
+//! ```
+
+use super::glfw::{Key, Action};
+use super::freecam;
+use super::settings;
+use super::super::blocks::{BlockCoord, ChunkStorage};
+use super::super::blocks::collision::{self, Aabb};
+use cgmath::{InnerSpace, Matrix4, Transform, Vector2, Vector3};
+
+/// The width of the player on both horizontal axes, in blocks.
+pub const PLAYER_WIDTH: f32 = 0.6;
+
+/// The height of the player, in blocks.
+pub const PLAYER_HEIGHT: f32 = 1.8;
+
+/// The height of the eyes above the feet of the player, in blocks.
+pub const EYE_HEIGHT: f32 = 1.62;
+
+/// How the player moves.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MovementMode {
+	/// Walking on the ground, pulled down by gravity.
+	Walk,
+	
+	/// Flying freely, but still colliding with blocks.
+	Fly,
+	
+	/// Flying freely through everything.
+	Noclip,
+}
+
+impl MovementMode {
+	pub const ALL: [MovementMode; 3] = [
+		MovementMode::Walk,
+		MovementMode::Fly,
+		MovementMode::Noclip,
+	];
+	
+	pub fn get_name(&self) -> &'static str {
+		match self {
+			MovementMode::Walk   => "walk",
+			MovementMode::Fly    => "fly",
+			MovementMode::Noclip => "noclip",
+		}
+	}
+	
+	pub fn from_name(name: &str) -> Option<MovementMode> {
+		MovementMode::ALL.iter().cloned().find(|mode| mode.get_name() == name)
+	}
+	
+	/// The mode after this one, wrapping around.
+	pub fn next(&self) -> MovementMode {
+		match self {
+			MovementMode::Walk   => MovementMode::Fly,
+			MovementMode::Fly    => MovementMode::Noclip,
+			MovementMode::Noclip => MovementMode::Walk,
+		}
+	}
+}
+
+/// The constants of player movement, in blocks and seconds.
+#[derive(Clone, Debug)]
+pub struct PlayerPhysics {
+	pub walk_speed: f32,
+	pub fly_speed: f32,
+	
+	/// Multiplies the walking speed while sprinting.
+	pub walk_sprint_factor: f32,
+	
+	/// Multiplies the flying speed while sprinting.
+	pub fly_sprint_factor: f32,
+	
+	pub gravity: f32,
+	pub jump_speed: f32,
+	
+	/// The fastest the player can fall.
+	pub terminal_speed: f32,
+	
+	/// How quickly the velocity follows the input, per second; higher is snappier.
+	pub ground_control: f32,
+	pub air_control: f32,
+	pub fly_control: f32,
+	
+	/// The highest ledge the player walks onto without jumping.
+	pub step_height: f32,
+}
+
+impl PlayerPhysics {
+	pub fn new() -> PlayerPhysics {
+		PlayerPhysics {
+			walk_speed: 4.3,
+			fly_speed: 6.0,
+			walk_sprint_factor: 1.5,
+			fly_sprint_factor: 5.0,
+			gravity: 32.0,
+			jump_speed: Self::get_jump_speed(32.0, 1.25),
+			terminal_speed: 60.0,
+			ground_control: 20.0,
+			air_control: 2.0,
+			fly_control: 8.6,
+			step_height: 0.6,
+		}
+	}
+	
+	/// The upwards speed needed to jump to the given height against the given gravity.
+	pub fn get_jump_speed(gravity: f32, height: f32) -> f32 {
+		(2.0 * gravity * height).sqrt()
+	}
+}
+
+/// What the player wants to do during a tick.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlayerInput {
+	/// The direction to move in, in world space, no longer than 1.
+	/// Walking ignores the vertical part.
+	pub direction: Vector3<f32>,
+	
+	pub jump: bool,
+	pub sprint: bool,
+}
+
+impl PlayerInput {
+	/// Standing still.
+	pub fn none() -> PlayerInput {
+		PlayerInput {
+			direction: Vector3::new(0.0, 0.0, 0.0),
+			jump: false,
+			sprint: false,
+		}
+	}
+	
+	/// Reads the movement keys, relative to where the camera is looking.
+	///
+	/// In crane-mode, space and control move straight up and down.
+	pub fn from_window(window: &glfw::Window, camera: &freecam::Camera, mode: MovementMode) -> PlayerInput {
+		if !camera.active {
+			return PlayerInput::none();
+		}
+		
+		let pressed = |key: Key| (window.get_key(key) == Action::Press) as i8;
+		
+		let mut keys = Vector3::new(
+			(pressed(Key::D) - pressed(Key::A)) as f32,
+			0.0,
+			(pressed(Key::W) - pressed(Key::S)) as f32,
+		);
+		
+		if camera.crane {
+			keys.y = (pressed(Key::Space) - pressed(Key::LeftControl)) as f32;
+		}
+		
+		let tilt = !camera.crane && mode != MovementMode::Walk;
+		
+		PlayerInput {
+			direction: Self::get_direction(keys, camera.get_rotation(0.0), tilt),
+			jump: pressed(Key::Space) != 0,
+			sprint: pressed(Key::LeftShift) != 0,
+		}
+	}
+	
+	/// Turns the pressed movement keys (`x` right, `y` up, `z` forward) into a direction in world space,
+	/// for a camera with the given pitch (`x`) and yaw (`y`), in degrees.
+	///
+	/// Only with `tilt` does the pitch tilt the plane of movement; walking ignores it,
+	/// so looking up or down does not slow the player down.
+	pub fn get_direction(keys: Vector3<f32>, rotation: Vector2<f32>, tilt: bool) -> Vector3<f32> {
+		let mut mat = Matrix4::from_angle_y(cgmath::Deg(rotation.y));
+		if tilt {
+			mat = mat * Matrix4::from_angle_x(cgmath::Deg(rotation.x));
+		}
+		
+		if keys.magnitude2() > 0.0 {
+			mat.transform_vector(keys.normalize())
+		} else {
+			keys
+		}
+	}
+}
+
+/// The position and movement of the player.
+#[derive(Clone, Debug)]
+pub struct PlayerController {
+	pub mode: MovementMode,
+	pub physics: PlayerPhysics,
+	
+	/// The center of the bottom of the player.
+	position: Vector3<f32>,
+	
+	/// In blocks per second.
+	velocity: Vector3<f32>,
+	
+	on_ground: bool,
+}
+
+impl PlayerController {
+	/// Creates a walking player standing at the given position.
+	pub fn new(position: Vector3<f32>) -> PlayerController {
+		PlayerController {
+			mode: MovementMode::Walk,
+			physics: PlayerPhysics::new(),
+			position,
+			velocity: Vector3::new(0.0, 0.0, 0.0),
+			on_ground: false,
+		}
+	}
+	
+	pub fn apply_settings(&mut self, settings: &settings::Settings) {
+		let player = match settings.table.get("player").and_then(|v| v.as_table()) {
+			Some(player) => player,
+			None => return
+		};
+		
+		let get_number = |key: &str| player.get(key).and_then(|v| match v {
+			toml::Value::Float(x) => Some(*x as f32),
+			toml::Value::Integer(x) => Some(*x as f32),
+			_ => {warn!("Value '{}' of the player settings is not a number.", key); None}
+		});
+		
+		if let Some(mode) = player.get("mode").and_then(|v| v.as_str()) {
+			match MovementMode::from_name(mode) {
+				Some(mode) => self.mode = mode,
+				None => warn!("Unknown movement mode '{}'.", mode)
+			}
+		}
+		
+		if let Some(speed) = get_number("walk-speed") {
+			self.physics.walk_speed = speed;
+		}
+		
+		if let Some(speed) = get_number("fly-speed") {
+			self.physics.fly_speed = speed;
+		}
+		
+		if let Some(height) = get_number("jump-height") {
+			self.physics.jump_speed = PlayerPhysics::get_jump_speed(self.physics.gravity, height.max(0.0));
+		}
+	}
+	
+	pub fn get_position(&self) -> Vector3<f32> {
+		self.position
+	}
+	
+	/// Moves the player to the given position, stopping it.
+	pub fn set_position(&mut self, position: Vector3<f32>) {
+		self.position = position;
+		self.velocity = Vector3::new(0.0, 0.0, 0.0);
+		self.on_ground = false;
+	}
+	
+	pub fn get_eye_position(&self) -> Vector3<f32> {
+		self.position + Vector3::new(0.0, EYE_HEIGHT, 0.0)
+	}
+	
+	/// The velocity of the player, in blocks per second.
+	pub fn get_velocity(&self) -> Vector3<f32> {
+		self.velocity
+	}
+	
+	/// Returns `true` if the player is walking and standing on something.
+	pub fn is_on_ground(&self) -> bool {
+		self.on_ground
+	}
+	
+	/// The box the player occupies.
+	pub fn get_bounds(&self) -> Aabb {
+		let half = PLAYER_WIDTH / 2.0;
+		Aabb::new(
+			self.position - Vector3::new(half, 0.0, half),
+			self.position + Vector3::new(half, PLAYER_HEIGHT, half),
+		)
+	}
+	
+	/// Advances the player by one tick of the given length, in seconds.
+	///
+	/// Unless in noclip-mode, the player does not move while the chunk it is in is not loaded,
+	/// and is pushed out of any blocks it is stuck in, e.g. because one was placed inside of it.
+	pub fn tick(&mut self, world: &ChunkStorage, input: &PlayerInput, delta: f32) {
+		if self.mode != MovementMode::Noclip {
+			if world.get_block(&to_block(self.position)).is_none() {
+				self.velocity = Vector3::new(0.0, 0.0, 0.0);
+				return;
+			}
+			
+			let push = collision::resolve_penetration(world, &self.get_bounds(), PLAYER_HEIGHT)
+				.unwrap_or(Vector3::new(0.0, 1.0, 0.0));
+			
+			if push.magnitude2() > 0.0 {
+				self.position += push;
+				self.velocity = Vector3::new(0.0, 0.0, 0.0);
+				self.on_ground = false;
+				return;
+			}
+		}
+		
+		match self.mode {
+			MovementMode::Walk => self.tick_walk(world, input, delta),
+			MovementMode::Fly => self.tick_fly(Some(world), input, delta),
+			MovementMode::Noclip => self.tick_fly(None, input, delta),
+		}
+	}
+	
+	fn tick_walk(&mut self, world: &ChunkStorage, input: &PlayerInput, delta: f32) {
+		let physics = &self.physics;
+		
+		let mut wish = Vector3::new(input.direction.x, 0.0, input.direction.z);
+		if wish.magnitude2() > 1.0 {
+			wish = wish.normalize();
+		}
+		
+		let speed = physics.walk_speed * if input.sprint {physics.walk_sprint_factor} else {1.0};
+		let control = if self.on_ground {physics.ground_control} else {physics.air_control};
+		let blend = 1.0 - (-control * delta).exp();
+		
+		self.velocity.x += (wish.x * speed - self.velocity.x) * blend;
+		self.velocity.z += (wish.z * speed - self.velocity.z) * blend;
+		
+		if input.jump && self.on_ground {
+			self.velocity.y = physics.jump_speed;
+		}
+		
+		self.velocity.y = (self.velocity.y - physics.gravity * delta).max(-physics.terminal_speed);
+		
+		let motion = self.velocity * delta;
+		let blocked = self.move_colliding(world, motion, self.on_ground);
+		
+		self.on_ground = motion.y < 0.0 && blocked[1];
+		self.stop_blocked(blocked);
+	}
+	
+	fn tick_fly(&mut self, world: Option<&ChunkStorage>, input: &PlayerInput, delta: f32) {
+		let physics = &self.physics;
+		
+		let speed = physics.fly_speed * if input.sprint {physics.fly_sprint_factor} else {1.0};
+		let blend = 1.0 - (-physics.fly_control * delta).exp();
+		
+		self.velocity += (input.direction * speed - self.velocity) * blend;
+		self.on_ground = false;
+		
+		let motion = self.velocity * delta;
+		match world {
+			Some(world) => {
+				let blocked = self.move_colliding(world, motion, false);
+				self.stop_blocked(blocked);
+			},
+			None => self.position += motion
+		}
+	}
+	
+	/// Stops the movement along every axis the player ran into something.
+	fn stop_blocked(&mut self, blocked: [bool; 3]) {
+		for axis in 0..3 {
+			if blocked[axis] {
+				self.velocity[axis] = 0.0;
+			}
+		}
+	}
+	
+	/// Moves the player as far as the given motion goes without entering a solid block,
+	/// returning along which axes it was stopped short.
+	///
+	/// The motion is swept along one axis after the other, vertical first. If `step` is set and
+	/// a horizontal move is blocked, moving over the obstacle is tried as well, up to the step height.
+	fn move_colliding(&mut self, world: &ChunkStorage, motion: Vector3<f32>, step: bool) -> [bool; 3] {
+		let start = self.get_bounds();
+		
+		let lift = if step {self.physics.step_height} else {0.0};
+		let colliders: Vec<Aabb> = collision::get_boxes(world, &start.expand(motion).expand(Vector3::new(0.0, lift, 0.0)))
+			.into_iter()
+			.map(|(_, bounds)| bounds)
+			.collect();
+		
+		let slide = collision::slide_among(&colliders, &start, motion);
+		let mut bounds = slide.bounds;
+		let mut blocked = slide.blocked;
+		
+		if step && (blocked[0] || blocked[2]) {
+			let mut stepped = start;
+			let mut stepped_blocked = [false; 3];
+			
+			let up = stepped.clip(&colliders, 1, lift);
+			stepped = stepped.offset_axis(1, up);
+			
+			for axis in [0, 2].iter() {
+				let distance = stepped.clip(&colliders, *axis, motion[*axis]);
+				stepped_blocked[*axis] = distance != motion[*axis];
+				stepped = stepped.offset_axis(*axis, distance);
+			}
+			
+			// Back down onto whatever was stepped onto, at least as far as the player was falling.
+			let fall = -up + motion.y.min(0.0);
+			let down = stepped.clip(&colliders, 1, fall);
+			stepped_blocked[1] = down != fall;
+			stepped = stepped.offset_axis(1, down);
+			
+			let horizontal = |b: &Aabb| {
+				let (x, z) = (b.min.x - start.min.x, b.min.z - start.min.z);
+				x*x + z*z
+			};
+			
+			if horizontal(&stepped) > horizontal(&bounds) {
+				bounds = stepped;
+				blocked = stepped_blocked;
+			}
+		}
+		
+		self.position += bounds.min - start.min;
+		blocked
+	}
+}
+
+fn to_block(position: Vector3<f32>) -> BlockCoord {
+	BlockCoord::new(position.x.floor() as isize, position.y.floor() as isize, position.z.floor() as isize)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::super::blocks::{BlockState, Chunk, UniverseRef};
+	use super::super::super::blocks::universe::define_universe;
+	
+	/// The length of a tick, as used by the client.
+	const DELTA: f32 = 1.0 / 60.0;
+	
+	const CONFIG: &str = r#"
+		[blocks.stone]
+		[blocks.slab]
+		collision = [0.0, 0.0, 0.0, 1.0, 0.5, 1.0]
+	"#;
+	
+	fn state(blockdef: &UniverseRef, name: &str) -> BlockState {
+		blockdef.get_block_by_name_unchecked(name).get_default_state()
+	}
+	
+	/// A world of 4x3x4 chunks around the origin, with a floor of stone
+	/// whose top is at `y = 0`, and the given blocks set on top of it.
+	fn world(blocks: &[(isize, isize, isize, &str)]) -> ChunkStorage {
+		let config: toml::Value = CONFIG.parse().unwrap();
+		let blockdef = define_universe(config.as_table().unwrap());
+		
+		let mut world = ChunkStorage::new_empty(blockdef.clone(), &toml::value::Table::new()).unwrap();
+		for y in (-1..2).rev() {
+			for z in -2..2 {
+				for x in -2..2 {
+					world.insert_chunk(Chunk::new_empty(blockdef.clone(), x, y, z));
+				}
+			}
+		}
+		
+		let stone = state(&blockdef, "stone");
+		for z in -20..20 {
+			for x in -20..20 {
+				world.set_block(&BlockCoord::new(x, -1, z), stone);
+			}
+		}
+		
+		for (x, y, z, name) in blocks {
+			world.set_block(&BlockCoord::new(*x, *y, *z), state(&blockdef, name));
+		}
+		
+		world
+	}
+	
+	fn walking(x: f32, z: f32) -> PlayerInput {
+		PlayerInput {
+			direction: Vector3::new(x, 0.0, z),
+			.. PlayerInput::none()
+		}
+	}
+	
+	fn run(player: &mut PlayerController, world: &ChunkStorage, input: &PlayerInput, ticks: usize) {
+		for _ in 0..ticks {
+			player.tick(world, input, DELTA);
+		}
+	}
+	
+	#[test]
+	fn falls_and_lands() {
+		let world = world(&[]);
+		let mut player = PlayerController::new(Vector3::new(0.5, 5.0, 0.5));
+		
+		player.tick(&world, &PlayerInput::none(), DELTA);
+		assert!(player.get_velocity().y < 0.0);
+		assert!(player.get_position().y < 5.0);
+		assert!(!player.is_on_ground());
+		
+		run(&mut player, &world, &PlayerInput::none(), 120);
+		assert!(player.is_on_ground());
+		assert_eq!(player.get_position().y, 0.0);
+		assert_eq!(player.get_velocity().y, 0.0);
+	}
+	
+	#[test]
+	fn jumps_as_high_as_configured() {
+		let world = world(&[]);
+		
+		for height in [1.25, 2.0].iter() {
+			let mut player = PlayerController::new(Vector3::new(0.5, 0.0, 0.5));
+			player.physics.jump_speed = PlayerPhysics::get_jump_speed(player.physics.gravity, *height);
+			run(&mut player, &world, &PlayerInput::none(), 2);
+			assert!(player.is_on_ground());
+			
+			let jump = PlayerInput { jump: true, .. PlayerInput::none() };
+			player.tick(&world, &jump, DELTA);
+			assert!(!player.is_on_ground());
+			
+			let mut top: f32 = 0.0;
+			for _ in 0..120 {
+				player.tick(&world, &PlayerInput::none(), DELTA);
+				top = top.max(player.get_position().y);
+			}
+			
+			// Stepping the motion in ticks loses at most one tick of rising.
+			assert!(top <= *height && top > *height - player.physics.jump_speed * DELTA, "{} of {}", top, height);
+			assert!(player.is_on_ground());
+		}
+	}
+	
+	#[test]
+	fn steps_onto_ledges() {
+		// A ledge of slabs ahead on `z`, and a ledge of full blocks ahead on `x`.
+		let mut blocks = vec![];
+		for a in -2..3 {
+			for b in 3..10 {
+				blocks.push((a, 0, b, "slab"));
+				blocks.push((b, 0, a, "stone"));
+			}
+		}
+		let world = world(&blocks);
+		
+		// A slab is lower than the step height...
+		let mut player = PlayerController::new(Vector3::new(0.5, 0.0, 0.5));
+		run(&mut player, &world, &walking(0.0, 1.0), 90);
+		assert!(player.get_position().z > 3.0, "{:?}", player.get_position());
+		assert_eq!(player.get_position().y, 0.5);
+		assert!(player.is_on_ground());
+		
+		// ...a full block is not.
+		let mut player = PlayerController::new(Vector3::new(0.5, 0.0, 0.5));
+		run(&mut player, &world, &walking(1.0, 0.0), 90);
+		assert_eq!(player.get_position().x, 3.0 - PLAYER_WIDTH / 2.0);
+		assert_eq!(player.get_position().y, 0.0);
+		
+		// With a higher step height, the player walks onto a ledge of a full block.
+		let mut player = PlayerController::new(Vector3::new(0.5, 0.0, 0.5));
+		player.physics.step_height = 1.0;
+		run(&mut player, &world, &walking(1.0, 0.0), 90);
+		assert!(player.get_position().x > 3.0, "{:?}", player.get_position());
+		assert_eq!(player.get_position().y, 1.0);
+	}
+	
+	#[test]
+	fn slides_along_walls() {
+		let blocks: Vec<(isize, isize, isize, &str)> = (-10..10)
+			.flat_map(|z| vec![(3, 0, z, "stone"), (3, 1, z, "stone")])
+			.collect();
+		let world = world(&blocks);
+		
+		// Walking diagonally into the wall keeps the part of the motion along it.
+		let mut player = PlayerController::new(Vector3::new(0.5, 0.0, 0.5));
+		run(&mut player, &world, &walking(0.6, 0.8), 60);
+		
+		let position = player.get_position();
+		assert_eq!(position.x, 3.0 - PLAYER_WIDTH / 2.0);
+		assert_eq!(position.y, 0.0);
+		assert!(position.z > 2.5, "{:?}", position);
+		assert_eq!(player.get_velocity().x, 0.0);
+		assert!(player.get_velocity().z > 0.0);
+	}
+	
+	#[test]
+	fn looking_down_does_not_slow_walking() {
+		let forward = Vector3::new(0.0, 0.0, 1.0);
+		let level = PlayerInput::get_direction(forward, Vector2::new(0.0, 30.0), false);
+		let pitched = PlayerInput::get_direction(forward, Vector2::new(-60.0, 30.0), false);
+		assert!((pitched - level).magnitude() < 1e-5);
+		assert_eq!(pitched.y, 0.0);
+		
+		// Flying goes where the camera is looking.
+		let flying = PlayerInput::get_direction(forward, Vector2::new(-60.0, 30.0), true);
+		assert!((flying.y.abs() - 60f32.to_radians().sin()).abs() < 1e-5);
+		assert!((flying.magnitude() - 1.0).abs() < 1e-5);
+		
+		let world = world(&[]);
+		let mut speeds = vec![];
+		for direction in [level, pitched].iter() {
+			let mut player = PlayerController::new(Vector3::new(0.5, 0.0, 0.5));
+			run(&mut player, &world, &PlayerInput { direction: *direction, .. PlayerInput::none() }, 60);
+			
+			let velocity = player.get_velocity();
+			speeds.push(Vector3::new(velocity.x, 0.0, velocity.z).magnitude());
+		}
+		
+		assert!((speeds[0] - PlayerPhysics::new().walk_speed).abs() < 0.01);
+		assert!((speeds[1] - speeds[0]).abs() < 1e-5);
+	}
+	
+	#[test]
+	fn noclip_ignores_blocks() {
+		let world = world(&[]);
+		let down = PlayerInput { direction: Vector3::new(0.0, -1.0, 0.0), .. PlayerInput::none() };
+		
+		let mut player = PlayerController::new(Vector3::new(0.5, 3.0, 0.5));
+		player.mode = MovementMode::Fly;
+		run(&mut player, &world, &down, 120);
+		assert_eq!(player.get_position().y, 0.0);
+		
+		player.mode = MovementMode::Noclip;
+		run(&mut player, &world, &down, 60);
+		assert!(player.get_position().y < -2.0, "{:?}", player.get_position());
+		
+		// Even outside of the loaded chunks.
+		player.set_position(Vector3::new(1000.5, 0.0, 0.5));
+		run(&mut player, &world, &down, 10);
+		assert!(player.get_position().y < 0.0);
+	}
+	
+	#[test]
+	fn same_inputs_give_the_same_positions() {
+		let world = world(&[(2, 0, 2, "slab"), (4, 0, 1, "stone"), (1, 0, 4, "stone"), (4, 1, 4, "stone")]);
+		
+		let inputs: Vec<PlayerInput> = (0..300)
+			.map(|tick| PlayerInput {
+				direction: Vector3::new((tick as f32 * 0.05).cos(), 0.0, (tick as f32 * 0.05).sin()),
+				jump: tick % 45 == 0,
+				sprint: tick % 100 < 50,
+			})
+			.collect();
+		
+		let positions = || {
+			let mut player = PlayerController::new(Vector3::new(0.5, 2.0, 0.5));
+			inputs.iter()
+				.map(|input| {
+					player.tick(&world, input, DELTA);
+					player.get_position()
+				})
+				.collect::<Vec<_>>()
+		};
+		
+		let (a, b) = (positions(), positions());
+		for (a, b) in a.iter().zip(b.iter()) {
+			assert_eq!((a.x.to_bits(), a.y.to_bits(), a.z.to_bits()), (b.x.to_bits(), b.y.to_bits(), b.z.to_bits()));
+		}
+	}
+}
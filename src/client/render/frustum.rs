@@ -0,0 +1,153 @@
+//! The view-frustum of a camera, for skipping things that are off-screen.
+//!
+//! This is plain maths on the CPU; nothing in here touches OpenGL.
+
+use cgmath::{InnerSpace, Matrix4, Vector3, Vector4};
+
+/// The six planes bounding everything a camera can see.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+	/// Left, right, bottom, top, near and far; the normals (`xyz`) point inwards.
+	planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+	/// Extracts the frustum from a combined projection- and view-matrix (`projection * view`).
+	///
+	/// The planes are in the space the view-matrix transforms from, i.e. world space.
+	pub fn from_matrix(matrix: &Matrix4<f32>) -> Frustum {
+		// The matrices are column-major, so the rows have to be picked out.
+		let row = |i: usize| Vector4::new(matrix.x[i], matrix.y[i], matrix.z[i], matrix.w[i]);
+		let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+		
+		let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2];
+		
+		for plane in planes.iter_mut() {
+			let length = plane.truncate().magnitude();
+			if length > 0.0 {
+				*plane /= length;
+			}
+		}
+		
+		Frustum { planes }
+	}
+	
+	/// Returns `true` if the point is inside of the frustum.
+	pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+		self.planes.iter().all(|plane| plane.truncate().dot(point) + plane.w >= 0.0)
+	}
+	
+	/// Returns `true` if the axis-aligned box from `min` to `max` might be visible.
+	///
+	/// This is conservative: some boxes just outside of the corners of the frustum pass,
+	/// but a box that is visible is never rejected.
+	pub fn intersects_box(&self, min: Vector3<f32>, max: Vector3<f32>) -> bool {
+		self.planes.iter().all(|plane| {
+			// The corner of the box that is furthest along the normal of the plane.
+			let corner = Vector3::new(
+				if plane.x >= 0.0 { max.x } else { min.x },
+				if plane.y >= 0.0 { max.y } else { min.y },
+				if plane.z >= 0.0 { max.z } else { min.z },
+			);
+			plane.truncate().dot(corner) + plane.w >= 0.0
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cgmath::{Deg, PerspectiveFov, Point3, Rad};
+	
+	/// A camera at `eye` looking at `target`, with a field of view of 90 degrees both ways.
+	fn frustum_at(eye: Point3<f32>, target: Point3<f32>) -> Frustum {
+		let projection: Matrix4<f32> = PerspectiveFov {
+			fovy: Rad::from(Deg(90.0)),
+			aspect: 1.0,
+			near: 0.1,
+			far: 100.0
+		}.into();
+		
+		let view = Matrix4::look_at(eye, target, Vector3::unit_y());
+		Frustum::from_matrix(&(projection * view))
+	}
+	
+	/// A camera at the origin looking down `-z`;
+	/// at a distance `d` the frustum spans from `-d` to `d` on `x` and `y`.
+	fn frustum() -> Frustum {
+		frustum_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0))
+	}
+	
+	/// A cube with a side of 4, centered on the given point.
+	fn cube(frustum: &Frustum, x: f32, y: f32, z: f32) -> bool {
+		frustum.intersects_box(Vector3::new(x - 2.0, y - 2.0, z - 2.0), Vector3::new(x + 2.0, y + 2.0, z + 2.0))
+	}
+	
+	#[test]
+	fn contains_point() {
+		let frustum = frustum();
+		
+		assert!(frustum.contains_point(Vector3::new(0.0, 0.0, -10.0)));
+		assert!(frustum.contains_point(Vector3::new(9.0, -9.0, -10.0)));
+		assert!(frustum.contains_point(Vector3::new(0.0, 0.0, -99.0)));
+		
+		// Beside, above and below, in front of the near plane and past the far plane.
+		assert!(!frustum.contains_point(Vector3::new(11.0, 0.0, -10.0)));
+		assert!(!frustum.contains_point(Vector3::new(-11.0, 0.0, -10.0)));
+		assert!(!frustum.contains_point(Vector3::new(0.0, 11.0, -10.0)));
+		assert!(!frustum.contains_point(Vector3::new(0.0, -11.0, -10.0)));
+		assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, -0.05)));
+		assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, -101.0)));
+		
+		// Behind the camera.
+		assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 10.0)));
+		assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 0.0)));
+	}
+	
+	#[test]
+	fn boxes_inside_and_outside() {
+		let frustum = frustum();
+		
+		assert!(cube(&frustum, 0.0, 0.0, -20.0));
+		assert!(cube(&frustum, 15.0, 15.0, -20.0));
+		
+		assert!(!cube(&frustum, 30.0, 0.0, -20.0));
+		assert!(!cube(&frustum, -30.0, 0.0, -20.0));
+		assert!(!cube(&frustum, 0.0, 30.0, -20.0));
+		assert!(!cube(&frustum, 0.0, -30.0, -20.0));
+		assert!(!cube(&frustum, 0.0, 0.0, -110.0));
+	}
+	
+	#[test]
+	fn boxes_straddling_the_edges() {
+		let frustum = frustum();
+		
+		// Only a corner of these reaches into the frustum.
+		assert!(cube(&frustum, 21.0, 0.0, -20.0));
+		assert!(cube(&frustum, -21.0, 0.0, -20.0));
+		assert!(cube(&frustum, 0.0, 21.0, -20.0));
+		assert!(cube(&frustum, 0.0, -21.0, -20.0));
+		assert!(cube(&frustum, 0.0, 0.0, -101.0));
+		
+		// A box around the camera straddles the near plane.
+		assert!(cube(&frustum, 0.0, 0.0, 0.0));
+		assert!(cube(&frustum, 0.0, 0.0, 1.0));
+	}
+	
+	#[test]
+	fn boxes_behind_the_camera() {
+		let frustum = frustum();
+		
+		assert!(!cube(&frustum, 0.0, 0.0, 20.0));
+		assert!(!cube(&frustum, 0.0, 0.0, 3.0));
+		assert!(!cube(&frustum, 10.0, 10.0, 10.0));
+		
+		// The planes are in world space, so this holds for a camera elsewhere too.
+		let frustum = frustum_at(Point3::new(100.0, 0.0, 0.0), Point3::new(101.0, 0.0, 0.0));
+		
+		assert!(cube(&frustum, 120.0, 0.0, 0.0));
+		assert!(!cube(&frustum, 80.0, 0.0, 0.0));
+		assert!(frustum.contains_point(Vector3::new(110.0, 0.0, 0.0)));
+		assert!(!frustum.contains_point(Vector3::new(90.0, 0.0, 0.0)));
+	}
+}
@@ -11,3 +11,4 @@ pub mod materials;
 pub mod crosshair;
 pub mod text;
 pub mod grid;
+pub mod frustum;
@@ -3,6 +3,8 @@
 // TODO: Once CDML is implemented, rewrite loading to be more... dynamic.
 
 use super::super::super::resources;
+use super::super::blocks::bakery::BakeryError;
+use super::super::blocks::model::ModelError;
 use std::ffi::{CString, CStr};
 use super::cgmath::prelude::*;
 
@@ -25,6 +27,12 @@ pub enum Error {
 	
 	#[fail(display = "Failed to parse value {}", name)]
 	ValueParse { name: String },
+	
+	#[fail(display = "Failed to bake texture atlas")]
+	AtlasBake { #[cause] inner: BakeryError },
+	
+	#[fail(display = "Failed to load block models")]
+	BlockModels { #[cause] inner: ModelError },
 }
 
 impl Error {
@@ -329,8 +337,11 @@ impl Texture {
 		let image = image::load_from_memory(&buffer)
 			.map_err(|e| Error::ImageParse { name: name.into(), inner: e })?;
 		
-		let image = image.to_rgba();
-		
+		Ok(Self::from_image(name, &image.to_rgba(), gl_setup))
+	}
+	
+	/// Uploads an image that is already in memory, labeling the texture with the given name.
+	pub fn from_image(name: &str, image: &image::RgbaImage, gl_setup: &Fn() -> ()) -> Texture {
 		let image_size = image.dimensions();
 		let image_width = image_size.0;
 		let image_height = image_size.1;
@@ -369,13 +380,13 @@ impl Texture {
 			name
 		);
 		
-		Ok(Texture{
+		Texture{
 			id: handle,
 			width: image_width,
 			height: image_height,
 			tx: 1.0 / image_width as f32,
 			ty: 1.0 / image_height as f32
-		})
+		}
 	}
 	
 	pub fn get_uv_rect(&self, x: u32, y:u32, w: u32, h: u32) -> [f32;4] {
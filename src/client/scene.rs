@@ -5,25 +5,49 @@ use super::super::router;
 use super::render;
 use super::geometry;
 use super::freecam;
+use super::player;
 use super::blocks;
+use super::cmd_opts;
+use super::settings;
 use super::super::blocks as blockdef;
+use std::path::{Path, PathBuf};
 
 pub struct Scene {
 	pub camera: freecam::Camera,
+	pub player: player::PlayerController,
 	meshes: Vec<geometry::SimpleMesh>,
 	pub blockdef: blockdef::UniverseRef,
-	pub chunks: blocks::ChunkStorage,
+	pub chunks: blockdef::ChunkStorage,
+	pub streamer: blockdef::ChunkStreamer,
+	save_dir: Option<PathBuf>,
 }
 
 impl Scene {
 	pub fn new() -> Scene {
 		let config = Scene::load_config().expect("Failed to load scene config.");
 		
-		let blockdef = blockdef::universe::define_universe(&config);
-		let chunks = blocks::ChunkStorage::new(blockdef.clone(), &config);
+		let save_dir = Scene::get_save_dir(&config);
+		
+		// Keep the block-ids of the save, if there is one.
+		let mapping = save_dir.as_ref()
+			.map(|save_dir| save_dir.join(blockdef::universe::ID_MAPPING_FILE))
+			.filter(|path| path.is_file())
+			.and_then(|path| match blockdef::universe::read_id_mapping(&path) {
+				Ok(mapping) => Some(mapping),
+				Err(e) => {error!("Failed to read block id-mapping: {}", e); None}
+			});
+		
+		let blockdef = blockdef::universe::define_universe_with_ids(&config, mapping.as_ref())
+			.expect("Failed to define universe.");
+		
+		// Chunks are streamed in around the camera, from the save or the generator.
+		let chunks = blockdef::ChunkStorage::new_empty(blockdef.clone(), &config)
+			.expect("Failed to set up world generator.");
+		let streamer = blockdef::ChunkStreamer::new(&config, save_dir.clone());
 		
 		Scene {
 			camera: freecam::Camera::new(),
+			player: player::PlayerController::new(cgmath::Vector3::new(0.0, 0.0, -3.0)),
 			meshes: vec![
 				// geometry::geometry_test(),
 				// geometry::geometry_cube(1.0),
@@ -31,6 +55,47 @@ impl Scene {
 			],
 			blockdef,
 			chunks,
+			streamer,
+			save_dir,
+		}
+	}
+	
+	/// Resolves the `save`-directory of the scene config relative to the executable.
+	fn get_save_dir(config: &toml::value::Table) -> Option<PathBuf> {
+		let save = config.get("save")?.as_str()?;
+		let exe_file_name = ::std::env::current_exe().ok()?;
+		let exe_path = exe_file_name.parent()?;
+		Some(exe_path.join(save))
+	}
+	
+	/// Moves the player by one tick of the given length, and puts the camera at its eyes.
+	pub fn update_player(&mut self, window: &glfw::Window, delta: f32) {
+		let input = player::PlayerInput::from_window(window, &self.camera, self.player.mode);
+		self.player.tick(&self.chunks, &input, delta);
+		self.camera.follow(self.player.get_eye_position(), self.player.get_velocity() * delta);
+	}
+	
+	/// Loads and unloads chunks around the camera.
+	pub fn update_chunks(&mut self) {
+		let observers = [self.camera.get_position(1.0)];
+		self.streamer.update(&mut self.chunks, &observers);
+	}
+	
+	/// Writes the modified chunks of the scene into its save-directory, if it has one.
+	pub fn save(&mut self) {
+		let save_dir = match &self.save_dir {
+			Some(save_dir) => save_dir,
+			None => return
+		};
+		
+		if let Err(e) = self.chunks.save_regions(save_dir) {
+			error!("Failed to save scene: {}", e);
+			return;
+		}
+		
+		let mapping_file = save_dir.join(blockdef::universe::ID_MAPPING_FILE);
+		if let Err(e) = self.blockdef.write_id_mapping(&mapping_file) {
+			error!("Failed to save block id-mapping: {}", e);
 		}
 	}
 	
@@ -58,8 +123,7 @@ impl Scene {
 		let dir = self.camera.get_look_dir(1.0);
 		let len = 16.0;
 		
-		use super::blocks;
-		let mut rc = blocks::BlockRaycast::new_from_src_dir_len(src, dir, len);
+		let mut rc = blockdef::BlockRaycast::new_from_src_dir_len(src, dir, len);
 		
 		let target = match self.chunks.raycast(&mut rc) {
 			Some((_, curr_pos, _)) => {
@@ -70,7 +134,7 @@ impl Scene {
 		
 		self.camera.target = target;
 	}
-	
+
 }
 
 impl router::comp::Component for Scene {
@@ -87,7 +151,9 @@ impl router::comp::Component for Scene {
 	fn on_event(&mut self, event: &mut router::event::Wrapper) {
 		
 		if let Some(event) = event.downcast::<super::settings::SettingsReloadEvent>() {
-			self.camera.apply_settings(event.settings);
+			let settings = event.settings.borrow();
+			self.camera.apply_settings(&settings);
+			self.player.apply_settings(&settings);
 		}
 		
 	}
@@ -103,12 +169,17 @@ pub struct SceneRenderer {
 }
 
 impl SceneRenderer {
-	pub fn new(res: &resources::Resources, scene: &Scene) -> Result<SceneRenderer, render::utility::Error> {
+	pub fn new(res: &resources::Resources, scene: &Scene, settings: &settings::Settings, opts: &cmd_opts::CmdOptions) -> Result<SceneRenderer, render::utility::Error> {
 		let grid = render::grid::Grid::new(res)?;
 		let sky_renderer = SkyRenderer::new(res)?;
 		let shader_random = render::materials::ShaderRandom::new(res)?;
 		let crosshair_3d = render::crosshair::CrosshairRenderer3D::new(res)?;
-		let chunk_rmng = blocks::ChunkRenderManager::new(res, scene.blockdef.clone())?;
+		let chunk_rmng = blocks::ChunkRenderManager::new(
+			res,
+			scene.blockdef.clone(),
+			settings,
+			opts.dump_atlas.as_ref().map(|dir| Path::new(dir))
+		)?;
 		
 		Ok(SceneRenderer {
 			frame_id: 0,
@@ -179,7 +250,7 @@ pub fn render(render_state: &mut SceneRenderer, scene: &Scene, size: (i32, i32),
 	}
 	
 	// Render chunks!
-	render_state.chunk_rmng.render(scene, camera_matrix);
+	render_state.chunk_rmng.render(scene, camera_matrix, camera_position);
 	
 	if let Some(target) = &scene.camera.target {
 		render_state.crosshair_3d.draw(camera_matrix, target);
@@ -67,12 +67,13 @@ impl router::comp::Component for Settings {
 
 
 
+/// Fired after the settings have been reloaded.
 pub struct SettingsReloadEvent {
-	pub settings: &'static Settings
+	pub settings: router::comp::ComponentHandle<Settings>
 }
 
 impl SettingsReloadEvent {
-	pub fn new(settings: &'static Settings) -> SettingsReloadEvent {
+	pub fn new(settings: router::comp::ComponentHandle<Settings>) -> SettingsReloadEvent {
 		SettingsReloadEvent {settings}
 	}
 }
@@ -1,12 +1,13 @@
 use super::rustc_hash::FxHashMap;
-use core::borrow::{Borrow, BorrowMut};
 use std::any::TypeId;
-use std::mem::transmute;
+use std::cell::{Ref, RefCell, RefMut};
+use std::marker::PhantomData;
+use std::rc::Rc;
 
 /// Container for all components bound to nodes.
 pub struct Components {
 	/// Collection of collections of components.
-	pub comps: FxHashMap<usize, FxHashMap<TypeId, Box<Component>>>,
+	pub comps: FxHashMap<usize, FxHashMap<TypeId, ComponentCell>>,
 }
 
 impl Components {
@@ -25,158 +26,221 @@ pub enum ComponentAccessError {
 	
 	#[fail(display = "Failed to load resource")]
 	CompNotFound,
+	
+	#[fail(display = "Component '{}' is already borrowed", type_name)]
+	CompBorrowed {type_name: &'static str},
+}
+
+/// A component shared between its node and everyone holding a handle to it.
+///
+/// The component is borrowed at runtime, like a `RefCell`: borrowing it mutably while it is
+/// borrowed anywhere else fails with `ComponentAccessError::CompBorrowed`, instead of aliasing.
+#[derive(Clone)]
+pub struct ComponentCell {
+	type_id: TypeId,
+	type_name: &'static str,
+	cell: Rc<RefCell<Box<Component>>>,
+}
+
+impl ComponentCell {
+	/// Wraps the given component into a new cell.
+	pub fn new(component: Box<Component>) -> ComponentCell {
+		ComponentCell {
+			type_id: mopa::Any::get_type_id(&*component),
+			type_name: component.get_type_name(),
+			cell: Rc::new(RefCell::new(component)),
+		}
+	}
+	
+	pub fn get_type_id(&self) -> TypeId {
+		self.type_id
+	}
+	
+	/// Returns the type name of the component, without having to borrow it.
+	pub fn get_type_name(&self) -> &'static str {
+		self.type_name
+	}
+	
+	/// Borrow the component, unless it is mutably borrowed already.
+	pub fn try_borrow(&self) -> Result<Ref<Component>, ComponentAccessError> {
+		self.cell.try_borrow()
+			.map(|component| Ref::map(component, |component| &**component))
+			.map_err(|_| ComponentAccessError::CompBorrowed {type_name: self.type_name})
+	}
+	
+	/// Mutably borrow the component, unless it is borrowed already.
+	pub fn try_borrow_mut(&self) -> Result<RefMut<Component>, ComponentAccessError> {
+		self.cell.try_borrow_mut()
+			.map(|component| RefMut::map(component, |component| &mut **component))
+			.map_err(|_| ComponentAccessError::CompBorrowed {type_name: self.type_name})
+	}
+	
+	/// Borrow the component.
+	///
+	/// # Panics
+	///
+	/// If the component is mutably borrowed already.
+	pub fn borrow(&self) -> Ref<Component> {
+		self.try_borrow().unwrap_or_else(|e| panic!("{}", e))
+	}
+	
+	/// Mutably borrow the component.
+	///
+	/// # Panics
+	///
+	/// If the component is borrowed already.
+	pub fn borrow_mut(&self) -> RefMut<Component> {
+		self.try_borrow_mut().unwrap_or_else(|e| panic!("{}", e))
+	}
+	
+	/// Returns a typed handle to the component, if it is of the given type.
+	pub fn downcast<C: Component>(&self) -> Option<ComponentHandle<C>> {
+		if self.type_id != TypeId::of::<C>() {
+			return None;
+		}
+		
+		Some(ComponentHandle {
+			cell: self.clone(),
+			marker: PhantomData,
+		})
+	}
+}
+
+/// A handle to a component of a known type, which can be kept around while the router owns the component.
+pub struct ComponentHandle<C: Component> {
+	cell: ComponentCell,
+	marker: PhantomData<C>,
+}
+
+impl<C: Component> ComponentHandle<C> {
+	/// Returns the untyped cell of the component.
+	pub fn get_cell(&self) -> &ComponentCell {
+		&self.cell
+	}
+	
+	/// Borrow the component, unless it is mutably borrowed already.
+	pub fn try_borrow(&self) -> Result<Ref<C>, ComponentAccessError> {
+		self.cell.try_borrow().map(|component| Ref::map(component, |component| {
+			component.downcast_ref::<C>().expect("Component handle has the wrong type.")
+		}))
+	}
+	
+	/// Mutably borrow the component, unless it is borrowed already.
+	pub fn try_borrow_mut(&self) -> Result<RefMut<C>, ComponentAccessError> {
+		self.cell.try_borrow_mut().map(|component| RefMut::map(component, |component| {
+			component.downcast_mut::<C>().expect("Component handle has the wrong type.")
+		}))
+	}
+	
+	/// Borrow the component.
+	///
+	/// # Panics
+	///
+	/// If the component is mutably borrowed already.
+	pub fn borrow(&self) -> Ref<C> {
+		self.try_borrow().unwrap_or_else(|e| panic!("{}", e))
+	}
+	
+	/// Mutably borrow the component.
+	///
+	/// # Panics
+	///
+	/// If the component is borrowed already.
+	pub fn borrow_mut(&self) -> RefMut<C> {
+		self.try_borrow_mut().unwrap_or_else(|e| panic!("{}", e))
+	}
+}
+
+impl<C: Component> Clone for ComponentHandle<C> {
+	fn clone(&self) -> Self {
+		ComponentHandle {
+			cell: self.cell.clone(),
+			marker: PhantomData,
+		}
+	}
 }
 
 impl super::node::Nodes {
 	/// Set a component of a specific type for the given node.
 	pub fn set_node_component(&mut self, node_id: usize, component: Box<Component>) -> Result<(), ComponentAccessError> {
-		let component_type_id = component.get_type_id();
-		
 		if ! self.nodes.contains_key(&node_id) {
 			return Err(ComponentAccessError::NodeNotFound{node_id});
 		}
 		
-		trace!("Adding component [{} #{}] to node #{}...",
+		let component = ComponentCell::new(component);
+		let component_type_id = component.get_type_id();
+		
+		trace!("Adding component [{} {:?}] to node #{}...",
 			component.get_type_name(),
-			unsafe {transmute::<TypeId, u64>(component_type_id)},
+			component_type_id,
 			node_id
 		);
 		
-		// --- If the node has no components...
-		if ! self.comps.comps.contains_key(&node_id) {
-			let mut new_components = FxHashMap::default();
-			new_components.insert(component_type_id, component);
-			self.comps.comps.insert(node_id, new_components);
-			
-			// Let the component initialize things.
-			( * self.comps.comps
-				.get_mut(&node_id)
-				.unwrap()
-				.get_mut(&component_type_id)
-				.unwrap()
-			).on_attachment(node_id);
-			return Ok(());
-		}
-		
-		let components = self.comps.comps.get_mut(&node_id).unwrap();
-		if let Some(mut old) = components.insert(component_type_id, component) {
-			old.on_detachment(node_id);
+		// Detach the old component first, so a failed borrow leaves it in place.
+		let components = self.comps.comps.entry(node_id).or_insert_with(FxHashMap::default);
+		if let Some(old) = components.get(&component_type_id) {
+			old.try_borrow_mut()?.on_detachment(node_id);
 		}
+		components.insert(component_type_id, component.clone());
 		
 		// Let the component initialize things.
-		( * components
-			.get_mut(&component_type_id)
-			.unwrap()
-		).on_attachment(node_id);
+		component.borrow_mut().on_attachment(node_id);
 		
 		return Ok(());
 	}
 	
-	/// Borrow a component of the given type from the given node, or any of its parents.
-	pub fn get_node_component(&self, node_id: usize, component_type: TypeId) -> Result<&'static Component, ComponentAccessError> {
-		return match {self.comps.comps.contains_key(&node_id)} {
-			false => match {self.get_node_parent_id(node_id)} {
-				Some(next_id) => return self.get_node_component(next_id, component_type),
-				None => Err(ComponentAccessError::CompNotFound)
-			},
-			true => {
-				return match self.comps.comps.get(&node_id).unwrap().get(&component_type).map(|boxed| {
-					unsafe {
-						// WARNING: This is terribly, terribly, unsafe, given that it breaks the borrowchecker.
-						transmute::<&Component, &'static Component>(boxed.borrow())
-					}
-				}) {
-					Some(x) => Ok(x),
-					None => Err(ComponentAccessError::CompNotFound)
-				};
-			}
-		};
-	}
-	
-	/// Mutably borrow a component of the given type from the given node, or any of its parents.
-	pub fn get_mut_node_component(&mut self, node_id: usize, component_type: TypeId) -> Result<&'static mut Component, ComponentAccessError> {
-		return match {self.comps.comps.contains_key(&node_id)} {
-			false => match {self.get_node_parent_id(node_id)} {
-				Some(next_id) => return self.get_mut_node_component(next_id, component_type),
-				None => Err(ComponentAccessError::CompNotFound)
-			},
-			true => {
-				return match self.comps.comps.get_mut(&node_id).unwrap().get_mut(&component_type).map(|boxed| {
-					unsafe {
-						// WARNING: This is terribly, terribly, unsafe, given that it breaks the borrowchecker.
-						transmute::<&mut Component, &'static mut Component>(boxed.borrow_mut())
-					}
-				}) {
-					Some(x) => Ok(x),
-					None => Err(ComponentAccessError::CompNotFound)
-				};
-			}
+	/// Detach and drop all components of the given node, in the order of their type names.
+	///
+	/// # Panics
+	///
+	/// If one of the components is borrowed elsewhere.
+	pub fn remove_node_components(&mut self, node_id: usize) {
+		let components = match self.comps.comps.remove(&node_id) {
+			Some(components) => components,
+			None => return
 		};
+		
+		let mut components: Vec<ComponentCell> = components.into_iter()
+			.map(|(_, component)| component)
+			.collect();
+		
+		components.sort_by_key(|component| component.get_type_name());
+		
+		for component in components {
+			trace!("Removing component [{}] from node #{}...", component.get_type_name(), node_id);
+			component.borrow_mut().on_detachment(node_id);
+		}
 	}
 	
-	/// Borrow a component of the given type from the given node, or any of its parents.
-	pub fn get_node_component_downcast<C: Component>(&self, node_id: usize) -> Result<&'static C, ComponentAccessError> {
-		return match {self.comps.comps.contains_key(&node_id)} {
-			false => match {self.get_node_parent_id(node_id)} {
-				Some(next_id) => return self.get_node_component_downcast::<C>(next_id),
-				None => Err(ComponentAccessError::CompNotFound)
-			},
-			true => {
-				let component_type_id = TypeId::of::<C>();
-				return match self.comps.comps.get(&node_id).unwrap().get(&component_type_id).map(|boxed| {
-					let boxed = boxed.downcast_ref::<C>();
-					match boxed {
-						Some(boxed) => unsafe {
-							// WARNING: This is terribly, terribly, unsafe, given that it breaks the borrowchecker.
-							Some(transmute::<&C, &'static C>(boxed.borrow()))
-						},
-						None => None
-					}
-				}) {
-					Some(x) => match x {
-						Some(x) => Ok(x),
-						None => Err(ComponentAccessError::CompNotFound)
-					},
-					None => Err(ComponentAccessError::CompNotFound)
-				};
+	/// Get the component of the given type from the given node, or the nearest of its parents that has one.
+	pub fn get_node_component(&self, node_id: usize, component_type: TypeId) -> Result<ComponentCell, ComponentAccessError> {
+		if ! self.nodes.contains_key(&node_id) {
+			return Err(ComponentAccessError::NodeNotFound{node_id});
+		}
+		
+		let mut current = Some(node_id);
+		while let Some(node_id) = current {
+			let component = self.comps.comps.get(&node_id)
+				.and_then(|components| components.get(&component_type));
+			
+			if let Some(component) = component {
+				return Ok(component.clone());
 			}
-		};
+			
+			current = self.get_node_parent_id(node_id);
+		}
+		
+		Err(ComponentAccessError::CompNotFound)
 	}
 	
-	/// Mutably borrow a component of the given type from the given node, or any of its parents.
-	pub fn get_mut_node_component_downcast<C: Component>(&mut self, node_id: usize) -> Result<&'static mut C, ComponentAccessError> {
-		return match {self.comps.comps.contains_key(&node_id)} {
-			false => match {self.get_node_parent_id(node_id)} {
-				Some(next_id) => return self.get_mut_node_component_downcast::<C>(next_id),
-				None => Err(ComponentAccessError::CompNotFound)
-			},
-			true => {
-				let component_type_id = TypeId::of::<C>();
-				return match self.comps.comps.get_mut(&node_id).unwrap().get_mut(&component_type_id).map(|boxed| {
-					let boxed = boxed.downcast_mut::<C>();
-					match boxed {
-						Some(boxed) => unsafe {
-							// WARNING: This is terribly, terribly, unsafe, given that it breaks the borrowchecker.
-							Some(transmute::<&mut C, &'static mut C>(boxed.borrow_mut()))
-						},
-						None => None
-					}
-				}) {
-					Some(x) => match x {
-						Some(x) => Ok(x),
-						None => Err(ComponentAccessError::CompNotFound)
-					},
-					None => Err(ComponentAccessError::CompNotFound)
-				};
-			}
-		};
+	/// Get a handle to the component of the given type from the given node, or the nearest of its parents that has one.
+	pub fn get_node_component_downcast<C: Component>(&self, node_id: usize) -> Result<ComponentHandle<C>, ComponentAccessError> {
+		self.get_node_component(node_id, TypeId::of::<C>())?
+			.downcast::<C>()
+			.ok_or(ComponentAccessError::CompNotFound)
 	}
 }
 
-
-
-
-
 /// A component is a bundle of user-logic and -state attached to a node,
 /// that can be loaded and unloaded depending on the residence of lenses.
 pub trait Component: mopa::Any {
@@ -186,8 +250,8 @@ pub trait Component: mopa::Any {
 	/// This function is called when the component is attached to a node.
 	fn on_attachment(&mut self, node_id: usize);
 	
-	/// This function is called when the component is detached from a node.
-	/// Should not happen unless a component is replaced.
+	/// This function is called when the component is detached from a node,
+	/// either because it is replaced or because the node is removed.
 	fn on_detachment(&mut self, node_id: usize);
 	
 	fn on_load(&mut self);
@@ -1,16 +1,18 @@
 use super::node;
 use super::comp;
 use super::lens;
+use super::event;
 use std::any::TypeId;
 
 pub struct Context<'a> {
 	pub lens: &'a lens::Lens,
 	pub nodes: &'a mut node::Nodes,
+	pub queue: &'a mut event::Queue,
 }
 
 impl<'a> Context<'a> {
-	pub fn new<'b>(lens: &'b lens::Lens, nodes: &'b mut node::Nodes) -> Context<'b> {
-		Context {lens, nodes}
+	pub fn new<'b>(lens: &'b lens::Lens, nodes: &'b mut node::Nodes, queue: &'b mut event::Queue) -> Context<'b> {
+		Context {lens, nodes, queue}
 	}
 	
 	pub fn get_lens_name(&self) -> &str {
@@ -53,37 +55,27 @@ impl<'a> Context<'a> {
 		self.lens.path_str.as_str()
 	}
 	
-	pub fn get_component(&self, component_type_id: TypeId) -> Result<&'a comp::Component, comp::ComponentAccessError> {
+	/// Get the component of the given type from the lensed node, or the nearest of its parents that has one.
+	pub fn get_component(&self, component_type_id: TypeId) -> Result<comp::ComponentCell, comp::ComponentAccessError> {
 		match self.get_lensed_node_id() {
 			Some(node_id) => self.nodes.get_node_component(node_id, component_type_id),
 			None => Err(comp::ComponentAccessError::NodeNotFound{node_id:0})
 		}
 	}
 	
-	pub fn get_mut_component(&mut self, component_type_id: TypeId) -> Result<&'a mut comp::Component, comp::ComponentAccessError> {
-		match self.get_lensed_node_id() {
-			Some(node_id) => self.nodes.get_mut_node_component(node_id, component_type_id),
-			None => Err(comp::ComponentAccessError::NodeNotFound{node_id:0})
-		}
-	}
-	
-	pub fn get_component_downcast<C: comp::Component>(&self) -> Result<&'a C, comp::ComponentAccessError> {
+	/// Get a handle to the component of the given type from the lensed node, or the nearest of its parents that has one.
+	pub fn get_component_downcast<C: comp::Component>(&self) -> Result<comp::ComponentHandle<C>, comp::ComponentAccessError> {
 		match self.get_lensed_node_id() {
 			Some(node_id) => self.nodes.get_node_component_downcast::<C>(node_id),
 			None => Err(comp::ComponentAccessError::NodeNotFound{node_id:0})
 		}
 	}
 	
-	pub fn get_mut_component_downcast<C: comp::Component>(&mut self) -> Result<&'a mut C, comp::ComponentAccessError> {
-		match self.get_lensed_node_id() {
-			Some(node_id) => self.nodes.get_mut_node_component_downcast::<C>(node_id),
-			None => Err(comp::ComponentAccessError::NodeNotFound{node_id:0})
-		}
-	}
-	
-	pub fn fire_event(&mut self) {
-		// TODO: Implement event handling for the context.
-		// Possibly by splitting it out from the router itself?
-		panic!("Not yet implemented.");
+	/// Queues an event for the given target.
+	///
+	/// The event is dispatched by the next `Router::update`, after the current event is done;
+	/// events are dispatched in the order they were fired. Relative node paths start at the lensed node.
+	pub fn fire_event<E: event::Event>(&mut self, target: event::Target, event: E) {
+		self.queue.push(target, &self.lens.path, Box::new(event));
 	}
 }
\ No newline at end of file
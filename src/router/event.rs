@@ -1,8 +1,9 @@
 use super::lens;
 use super::context;
+use std::collections::VecDeque;
 
 /// A event that can be sent trough the router towards various destinations.
-/// At the moment the only possible destination is a Lens.
+/// See `Target` for the destinations of queued events.
 pub trait Event: mopa::Any {
 	///	If an event is passive, it can be fired at its destination
 	///	regardless of what state the lens is in.
@@ -12,7 +13,89 @@ pub trait Event: mopa::Any {
 // This is 100% necessary until `std::` provides Any for object-traits.
 mopafy!(Event);
 
+/// The destination of a queued event.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+	/// The lens with the given name.
+	Lens(String),
+	
+	/// The node at the given path; see `Router::fire_event_at_node`.
+	Node(String),
+	
+	/// Every node in the subtree at the given path; see `Router::fire_event_at_subtree`.
+	Subtree(String),
+	
+	/// Every lens inside of the subtree at the given path; see `Router::fire_event_at_lenses_in`.
+	LensesIn(String),
+	
+	/// Every lens in the router.
+	Broadcast,
+}
+
+// Paths are resolved like the path of a moving lens. Relative paths start at the node
+// of the lens that fired the event, or at the root node.
+
+/// An event waiting to be dispatched.
+pub struct QueuedEvent {
+	pub target: Target,
+	
+	/// The path of the lens that fired the event; empty if it was not fired by a lens.
+	pub origin: Vec<usize>,
+	
+	pub event: Box<Event>,
+}
+
+/// Events waiting to be dispatched by `Router::update`, in the order they were fired.
+pub struct Queue {
+	events: VecDeque<QueuedEvent>,
+}
+
+impl Queue {
+	/// Creates a new empty queue.
+	pub fn new() -> Queue {
+		Queue {
+			events: VecDeque::new(),
+		}
+	}
+	
+	/// Puts an event at the end of the queue.
+	pub fn push(&mut self, target: Target, origin: &[usize], event: Box<Event>) {
+		self.events.push_back(QueuedEvent {
+			target,
+			origin: origin.to_vec(),
+			event,
+		});
+	}
+	
+	/// Removes all events from the queue, oldest first.
+	pub fn take(&mut self) -> VecDeque<QueuedEvent> {
+		std::mem::replace(&mut self.events, VecDeque::new())
+	}
+	
+	/// Cuts the origins of the queued events short before the first of the given nodes,
+	/// so that relative paths do not start at nodes that were removed.
+	pub fn leave_nodes(&mut self, node_ids: &[usize]) {
+		for queued in self.events.iter_mut() {
+			if let Some(position) = queued.origin.iter().position(|id| node_ids.contains(id)) {
+				queued.origin.truncate(position);
+			}
+		}
+	}
+	
+	pub fn len(&self) -> usize {
+		self.events.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.events.is_empty()
+	}
+}
+
 /// Represents the phase (or state) of an event as it's being processed.
+///
+/// An event fired at a lens flows like a DOM event: down the path of the lens from the root
+/// (propagation), to the handler of the lens (action), and back up to the root (bubbling).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Phase {
 	/// The event is being wrapped in a `EventWrapper`.
 	Creation,
@@ -29,13 +112,15 @@ pub enum Phase {
 
 /// Wraps an event as it is processed by the [Router].
 pub struct Wrapper<'a> {
-	#[allow(dead_code)]
 	/// The event being processed.
 	pub event: &'a mut Event,
 	
 	// --- State for the event
 	phase: Phase,
 	
+	/// The node whose components are handling the event, if any.
+	node_id: Option<usize>,
+	
 	/// Can the event flow towards its destination?
 	can_propagate: bool,
 	
@@ -47,6 +132,41 @@ pub struct Wrapper<'a> {
 }
 
 impl<'a> Wrapper<'a> {
+	/// Wraps an event that may travel in every direction.
+	pub fn new(event: &'a mut Event) -> Wrapper<'a> {
+		Wrapper {
+			event,
+			phase: Phase::Creation,
+			node_id: None,
+			can_propagate: true,
+			can_default: true,
+			can_bubble: true,
+		}
+	}
+	
+	/// Wraps an event that goes straight to its destination, without propagating or bubbling.
+	pub fn new_direct(event: &'a mut Event) -> Wrapper<'a> {
+		Wrapper {
+			event,
+			phase: Phase::Action,
+			node_id: None,
+			can_propagate: false,
+			can_default: true,
+			can_bubble: false,
+		}
+	}
+	
+	/// Returns the phase the event is in.
+	pub fn get_phase(&self) -> Phase {
+		self.phase
+	}
+	
+	/// Returns the node whose components are handling the event,
+	/// or `None` while the event is handled by a lens.
+	pub fn get_node_id(&self) -> Option<usize> {
+		self.node_id
+	}
+	
 	/// Downcast the wrapped event into the given type, if possible.
 	pub fn downcast<E: Event>(&mut self) -> Option<&E> {
 		self.event.downcast_ref()
@@ -58,19 +178,40 @@ impl<'a> Wrapper<'a> {
 	}
 	
 	/// Prevents the event from being evaluated by its destination.
+	///
+	/// The event still bubbles back up afterwards.
 	pub fn prevent_default(&mut self) {
 		self.can_default = false;
 	}
 	
-	/// Stops the flow of the event toward its destination.
+	/// Stops the event where it is: it reaches no further nodes, and not its destination either.
+	///
+	/// The other components of the current node still get the event.
 	pub fn stop_propagation(&mut self) {
 		self.can_propagate = false;
+		self.can_default = false;
+		self.can_bubble = false;
 	}
 	
 	/// Stops the flow of the event back towards its source.
 	pub fn stop_bubbling(&mut self) {
 		self.can_bubble = false;
 	}
+	
+	/// Returns `true` if the event has been stopped from reaching further nodes on its way down.
+	pub fn is_propagation_stopped(&self) -> bool {
+		!self.can_propagate
+	}
+	
+	/// Returns `true` if the event will not be, or was not, evaluated by its destination.
+	pub fn is_default_prevented(&self) -> bool {
+		!self.can_default
+	}
+	
+	/// Returns `true` if the event has been stopped from flowing back up.
+	pub fn is_bubbling_stopped(&self) -> bool {
+		!self.can_bubble
+	}
 }
 
 /// Implementation details for event handling.
@@ -115,25 +256,12 @@ impl super::Router {
 		}
 		
 		// Holder for event state.
-		let mut event_wrapper = Wrapper {
-			event,
-			
-			// Initial State
-			phase: Phase::Creation,
-			can_propagate: true,
-			can_default: true,
-			can_bubble: true,
-		};
+		let mut event_wrapper = Wrapper::new(event);
 		
-		// --- Event Propagation
+		// --- Event Propagation: from the root down to the lensed node.
 		event_wrapper.phase = Phase::Propagation;
 		for node_id in lens.path.iter() {
-			let (node, comps)
-				= nodes.get_mut_node_with_comps_by_id(*node_id);
-			
-			node.map(|node| {
-				node.on_event(comps, &mut event_wrapper);
-			});
+			deliver_to_node(nodes, *node_id, &mut event_wrapper);
 			
 			if !event_wrapper.can_propagate {
 				break;
@@ -146,23 +274,17 @@ impl super::Router {
 			
 			(*lens_handler).on_event(
 				&mut event_wrapper,
-				&mut context::Context::new(lens, nodes)
+				&mut context::Context::new(lens, nodes, &mut self.queue)
 			)
 		} else {
 			lens::State::Idle
 		};
 		
-		// --- Event Bubbling
+		// --- Event Bubbling: from the lensed node back up to the root.
 		if event_wrapper.can_bubble {
 			event_wrapper.phase = Phase::Bubbling;
 			for node_id in lens.path.iter().rev() {
-				
-				let (node, comps)
-					= nodes.get_mut_node_with_comps_by_id(*node_id);
-				
-				node.map(|node| {
-					node.on_event(comps, &mut event_wrapper);
-				});
+				deliver_to_node(nodes, *node_id, &mut event_wrapper);
 				
 				if !event_wrapper.can_bubble {
 					break;
@@ -202,17 +324,11 @@ impl super::Router {
 			}
 		}
 		
-		let mut wrapper = Wrapper {
-			event,
-			phase: Phase::Action,
-			can_propagate: false,
-			can_default: true,
-			can_bubble: false,
-		};
+		let mut wrapper = Wrapper::new_direct(event);
 		
 		lens_handler.on_event(
 			&mut wrapper,
-			&mut context::Context::new(lens, nodes)
+			&mut context::Context::new(lens, nodes, &mut self.queue)
 		);
 		true
 	}
@@ -221,15 +337,10 @@ impl super::Router {
 	pub fn trigger_event_at_node_id(&mut self, node_id: usize, event: &mut Event) -> bool {
 		match self.nodes.nodes.get_mut(&node_id) {
 			Some(node) => {
-				let mut wrapper = Wrapper {
-					event,
-					phase: Phase::Action,
-					can_propagate: false,
-					can_default: true,
-					can_bubble: false,
-				};
+				let mut wrapper = Wrapper::new_direct(event);
+				wrapper.node_id = Some(node_id);
 				
-				let comps = self.nodes.comps.comps.get_mut(&node_id);
+				let comps = self.nodes.comps.comps.get(&node_id);
 				node.on_event(comps, &mut wrapper);
 				true
 			}
@@ -237,25 +348,654 @@ impl super::Router {
 		}
 	}
 	
-	/// Fires a single `Event` at a single `Node`, given a path.
-	#[allow(unused)]
-	pub fn fire_event_at_node(&mut self, path: &str, event: &mut Event) {
-		/*
-		let mut path_offset = 0;
-		let mut src_path = vec![];
-		let mut bubble_path = vec![];
+	/// Queues an event for the given target, to be dispatched by the next `Router::update`.
+	///
+	/// Relative node paths start at the root node.
+	pub fn queue_event(&mut self, target: Target, event: Box<Event>) {
+		self.queue.push(target, &[], event);
+	}
+	
+	/// Dispatches the events queued up until now, in the order they were fired.
+	///
+	/// Events fired while these are being dispatched stay queued until the next call,
+	/// so events that keep firing each other cannot stall the router.
+	pub fn dispatch_queued_events(&mut self) {
+		for mut queued in self.queue.take() {
+			match queued.target.clone() {
+				Target::Lens(name) => {
+					self.fire_event_at_lens(&name, &mut *queued.event);
+				},
+				
+				Target::Node(path) | Target::Subtree(path) | Target::LensesIn(path) => {
+					let node_path = match self.resolve_path(&path, &queued.origin) {
+						Ok(node_path) => node_path,
+						Err(e) => {
+							warn!("Dropped event for '{}': {}", path, e);
+							continue;
+						}
+					};
+					
+					let node_id = *node_path.last().unwrap_or(&0);
+					match queued.target {
+						Target::Node(_) => self.fire_event_at_node_path(&node_path, &mut *queued.event),
+						Target::Subtree(_) => self.fire_event_at_subtree_id(node_id, &mut *queued.event),
+						_ => self.fire_event_at_lenses_in_id(node_id, &mut *queued.event),
+					}
+				},
+				
+				Target::Broadcast => {
+					for lens_id in 0..self.lenses.lenses.len() {
+						self.fire_event_at_lens_id(lens_id, &mut *queued.event);
+					}
+				}
+			}
+		}
+	}
+	
+	/// Fires a single `Event` at a single `Node`, given a path from the root.
+	///
+	/// The event flows like an event fired at a lens: down from the root through the parents of
+	/// the node (propagation), to the components of the node itself (action), and back up (bubbling).
+	/// Returns `false` if the path could not be resolved.
+	pub fn fire_event_at_node(&mut self, path: &str, event: &mut Event) -> bool {
+		match self.resolve_path(path, &[]) {
+			Ok(node_path) => {
+				self.fire_event_at_node_path(&node_path, event);
+				true
+			},
+			Err(e) => {
+				warn!("Failed to fire event at node '{}': {}", path, e);
+				false
+			}
+		}
+	}
+	
+	/// Actual implementation for `fire_event_at_node`, given the ids of the nodes from the root to the target.
+	pub fn fire_event_at_node_path(&mut self, node_path: &[usize], event: &mut Event) {
+		let (target, parents) = match node_path.split_last() {
+			Some(x) => x,
+			None => return
+		};
+		
+		let nodes = &mut self.nodes;
+		let mut wrapper = Wrapper::new(event);
+		
+		// --- Event Propagation
+		wrapper.phase = Phase::Propagation;
+		for node_id in parents.iter() {
+			deliver_to_node(nodes, *node_id, &mut wrapper);
+			
+			if !wrapper.can_propagate {
+				break;
+			}
+		}
+		
+		// --- Event Action
+		if wrapper.can_default {
+			wrapper.phase = Phase::Action;
+			deliver_to_node(nodes, *target, &mut wrapper);
+		}
+		
+		// --- Event Bubbling
+		if wrapper.can_bubble {
+			wrapper.phase = Phase::Bubbling;
+			for node_id in parents.iter().rev() {
+				deliver_to_node(nodes, *node_id, &mut wrapper);
+				
+				if !wrapper.can_bubble {
+					break;
+				}
+			}
+		}
+	}
+	
+	/// Fires a single `Event` at every node in the subtree at the given path from the root,
+	/// parents before their children and siblings in the order they were created.
+	///
+	/// Every node gets the event directly, without propagation or bubbling. If the components of a
+	/// node stop its propagation, the nodes below it are skipped.
+	/// Returns `false` if the path could not be resolved.
+	pub fn fire_event_at_subtree(&mut self, path: &str, event: &mut Event) -> bool {
+		match self.resolve_path(path, &[]) {
+			Ok(node_path) => {
+				self.fire_event_at_subtree_id(*node_path.last().unwrap_or(&0), event);
+				true
+			},
+			Err(e) => {
+				warn!("Failed to fire event at subtree '{}': {}", path, e);
+				false
+			}
+		}
+	}
+	
+	/// Actual implementation for `fire_event_at_subtree`.
+	pub fn fire_event_at_subtree_id(&mut self, node_id: usize, event: &mut Event) {
+		let mut stack = vec![node_id];
+		
+		while let Some(node_id) = stack.pop() {
+			let mut wrapper = Wrapper::new(&mut *event);
+			wrapper.phase = Phase::Action;
+			wrapper.can_bubble = false;
+			
+			deliver_to_node(&mut self.nodes, node_id, &mut wrapper);
+			
+			if wrapper.can_propagate {
+				// Reversed, so the first child is handled first.
+				stack.extend(self.nodes.get_children_ids(node_id).into_iter().rev());
+			}
+		}
+	}
+	
+	/// Fires a single `Event` at every lens inside of the subtree at the given path from the root,
+	/// in the order the lenses were created.
+	///
+	/// Returns `false` if the path could not be resolved.
+	pub fn fire_event_at_lenses_in(&mut self, path: &str, event: &mut Event) -> bool {
+		match self.resolve_path(path, &[]) {
+			Ok(node_path) => {
+				self.fire_event_at_lenses_in_id(*node_path.last().unwrap_or(&0), event);
+				true
+			},
+			Err(e) => {
+				warn!("Failed to fire event at lenses in '{}': {}", path, e);
+				false
+			}
+		}
+	}
+	
+	/// Actual implementation for `fire_event_at_lenses_in`.
+	pub fn fire_event_at_lenses_in_id(&mut self, node_id: usize, event: &mut Event) {
+		let lens_ids: Vec<usize> = self.lenses.lenses.iter()
+			.enumerate()
+			.filter(|(_, lens)| lens.path.contains(&node_id))
+			.map(|(lens_id, _)| lens_id)
+			.collect();
+		
+		for lens_id in lens_ids {
+			self.fire_event_at_lens_id(lens_id, event);
+		}
+	}
+}
+
+/// Hands the event to the components of the given node.
+fn deliver_to_node(nodes: &mut super::node::Nodes, node_id: usize, wrapper: &mut Wrapper) {
+	let (node, comps) = nodes.get_mut_node_with_comps_by_id(node_id);
+	
+	wrapper.node_id = Some(node_id);
+	node.map(|node| {
+		node.on_event(comps, wrapper);
+	});
+	wrapper.node_id = None;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Router;
+	use super::super::comp::Component;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+	
+	/// What the lenses and components of a test saw, in order.
+	type Log = Rc<RefCell<Vec<String>>>;
+	
+	/// A passive event, so lenses get it even while they are moving.
+	struct Message(&'static str);
+	
+	impl Event for Message {
+		fn is_passive(&self) -> bool { true }
+	}
+	
+	/// Logs the messages of its lens as `<lens>:<message>`,
+	/// and fires the given replies when it gets their trigger.
+	struct Logger {
+		name: &'static str,
+		log: Log,
+		replies: Vec<(&'static str, Target, &'static str)>,
+	}
+	
+	impl lens::Handler for Logger {
+		fn on_event<'a>(&mut self, event: &mut Wrapper, context: &mut context::Context) -> lens::State {
+			if let Some(Message(message)) = event.downcast::<Message>() {
+				let message: &'static str = message;
+				self.log.borrow_mut().push(format!("{}:{}", self.name, message));
+				
+				for (trigger, target, reply) in self.replies.iter() {
+					if *trigger == message {
+						context.fire_event(target.clone(), Message(reply));
+					}
+				}
+			}
+			lens::State::Idle
+		}
+	}
+	
+	/// Logs the messages of its node as the node name, followed by
+	/// `v` while propagating, `*` in the action phase and `^` while bubbling.
+	struct Recorder {
+		name: &'static str,
+		log: Log,
+	}
+	
+	impl Component for Recorder {
+		fn get_type_name(&self) -> &'static str { "Recorder" }
+		fn on_attachment(&mut self, _node_id: usize) {}
+		fn on_detachment(&mut self, _node_id: usize) {}
+		fn on_load(&mut self) {}
+		fn on_unload(&mut self) {}
+		
+		fn on_event(&mut self, event: &mut Wrapper) {
+			if event.downcast::<Message>().is_none() {
+				return;
+			}
+			
+			let phase = match event.get_phase() {
+				Phase::Propagation => "v",
+				Phase::Action => "*",
+				Phase::Bubbling => "^",
+				Phase::Creation => "?",
+			};
+			self.log.borrow_mut().push(format!("{}{}", self.name, phase));
+		}
+	}
+	
+	/// Calls the given function on a message while it is in the given phase, without logging anything.
+	struct Interceptor {
+		message: &'static str,
+		phase: Phase,
+		reaction: fn(&mut Wrapper),
+	}
+	
+	impl Component for Interceptor {
+		fn get_type_name(&self) -> &'static str { "Interceptor" }
+		fn on_attachment(&mut self, _node_id: usize) {}
+		fn on_detachment(&mut self, _node_id: usize) {}
+		fn on_load(&mut self) {}
+		fn on_unload(&mut self) {}
 		
-		loop {
+		fn on_event(&mut self, event: &mut Wrapper) {
+			let matches = match event.downcast::<Message>() {
+				Some(Message(message)) => *message == self.message,
+				None => false
+			};
 			
-			let step = Router::path_next(
-				&self.nodes.nodes,
-				path,
-				&mut path_offset,
-				src_path.as_slice()
-			);
+			if matches && event.get_phase() == self.phase {
+				(self.reaction)(event);
+			}
+		}
+	}
+	
+	/// The nodes of a test router, and the log shared by everything in it.
+	struct Tree {
+		router: Router,
+		log: Log,
+		game: usize,
+		world: usize,
+		hud: usize,
+		menu: usize,
+	}
+	
+	/// A router with the nodes `/game/world`, `/game/hud` and `/menu`, without components.
+	fn tree() -> Tree {
+		let mut router = Router::new();
+		let game = router.new_node("game", None, &|_| {});
+		let world = router.new_node("world", Some(game), &|_| {});
+		let hud = router.new_node("hud", Some(game), &|_| {});
+		let menu = router.new_node("menu", None, &|_| {});
+		
+		Tree {
+			router,
+			log: Rc::new(RefCell::new(vec![])),
+			game,
+			world,
+			hud,
+			menu,
+		}
+	}
+	
+	impl Tree {
+		/// Attaches a `Recorder` to the given node.
+		fn record(&mut self, node_id: usize, name: &'static str) {
+			let log = self.log.clone();
+			self.router.nodes.set_node_component(node_id, Box::new(Recorder {name, log})).unwrap();
+		}
+		
+		/// Attaches an `Interceptor` to the given node.
+		fn intercept(&mut self, node_id: usize, message: &'static str, phase: Phase, reaction: fn(&mut Wrapper)) {
+			self.router.nodes.set_node_component(node_id, Box::new(Interceptor {message, phase, reaction})).unwrap();
+		}
+		
+		/// Adds a lens with a `Logger` that moves to the given path.
+		fn lens(&mut self, name: &'static str, path: &str, replies: Vec<(&'static str, Target, &'static str)>) {
+			let log = self.log.clone();
+			let path = path.to_string();
+			let replies = RefCell::new(Some(replies));
 			
-			// TODO: Implement firing of events at nodes.
+			self.router.new_lens(name, &|lens| {
+				lens.state = lens::State::Moving(path.clone(), 0);
+				Some(Box::new(Logger {
+					name,
+					log: log.clone(),
+					replies: replies.borrow_mut().take().unwrap(),
+				}))
+			});
+		}
+		
+		/// Lets the lenses reach their destinations, and forgets everything logged so far.
+		fn settle(&mut self) {
+			for _ in 0..10 {
+				self.router.update();
+			}
+			self.log.borrow_mut().clear();
+		}
+		
+		/// Takes everything logged so far, joined by spaces.
+		fn take_log(&self) -> String {
+			let log = self.log.borrow().join(" ");
+			self.log.borrow_mut().clear();
+			log
+		}
+	}
+	
+	#[test]
+	fn queued_events_keep_their_order() {
+		let mut tree = tree();
+		let menu = tree.menu;
+		tree.record(menu, "menu");
+		
+		tree.lens("a", "/game/world", vec![
+			("start", Target::Lens("b".to_string()), "one"),
+			("start", Target::Broadcast, "two"),
+			("start", Target::Node("/menu".to_string()), "three"),
+			("start", Target::Lens("a".to_string()), "four"),
+		]);
+		tree.lens("b", "/game/hud", vec![]);
+		tree.settle();
+		
+		// Nothing is dispatched before the event that fired them is done.
+		tree.router.fire_event_at_lens("a", &mut Message("start"));
+		assert_eq!(tree.take_log(), "a:start");
+		assert_eq!(tree.router.queue.len(), 4);
+		
+		tree.router.update();
+		assert_eq!(tree.take_log(), "b:one a:two b:two menu* a:four");
+		assert!(tree.router.queue.is_empty());
+	}
+	
+	#[test]
+	fn events_fired_while_dispatching_wait_for_the_next_update() {
+		let mut tree = tree();
+		tree.lens("a", "/game", vec![
+			("start", Target::Lens("b".to_string()), "one"),
+			("ping", Target::Lens("a".to_string()), "ping"),
+		]);
+		tree.lens("b", "/menu", vec![
+			("one", Target::Lens("a".to_string()), "two"),
+		]);
+		tree.settle();
+		
+		tree.router.fire_event_at_lens("a", &mut Message("start"));
+		tree.router.update();
+		assert_eq!(tree.take_log(), "a:start b:one");
+		assert_eq!(tree.router.queue.len(), 1);
+		
+		tree.router.update();
+		assert_eq!(tree.take_log(), "a:two");
+		assert!(tree.router.queue.is_empty());
+		
+		// A lens firing at itself gets one event per update, instead of stalling the router.
+		tree.router.fire_event_at_lens("a", &mut Message("ping"));
+		for _ in 0..3 {
+			tree.router.update();
 		}
-		*/
+		assert_eq!(tree.take_log(), "a:ping a:ping a:ping a:ping");
+		assert_eq!(tree.router.queue.len(), 1);
+	}
+	
+	#[test]
+	fn relative_paths_start_at_the_firing_lens() {
+		let mut tree = tree();
+		let (game, world, hud, menu) = (tree.game, tree.world, tree.hud, tree.menu);
+		tree.record(0, "root");
+		tree.record(game, "game");
+		tree.record(world, "world");
+		tree.record(hud, "hud");
+		tree.record(menu, "menu");
+		
+		tree.lens("a", "/game", vec![
+			("start", Target::Subtree("world".to_string()), "one"),
+			("start", Target::Subtree("./hud".to_string()), "two"),
+			("start", Target::Subtree("../menu".to_string()), "three"),
+			("start", Target::Subtree("/game/hud".to_string()), "four"),
+			("start", Target::Subtree("menu".to_string()), "five"),
+		]);
+		tree.settle();
+		
+		// `menu` is not a child of `/game`, so that event is dropped.
+		tree.router.fire_event_at_lens("a", &mut Message("start"));
+		tree.take_log();
+		tree.router.update();
+		assert_eq!(tree.take_log(), "world* hud* menu* hud*");
+		
+		// Events queued from outside of a lens start at the root node.
+		tree.router.queue_event(Target::Subtree("menu".to_string()), Box::new(Message("seven")));
+		tree.router.queue_event(Target::Subtree("game/world".to_string()), Box::new(Message("eight")));
+		tree.router.update();
+		assert_eq!(tree.take_log(), "menu* world*");
+	}
+	
+	#[test]
+	fn leaving_nodes_cuts_origins_short() {
+		let mut queue = Queue::new();
+		queue.push(Target::Broadcast, &[0, 1, 2], Box::new(Message("a")));
+		queue.push(Target::Broadcast, &[0, 4], Box::new(Message("b")));
+		queue.push(Target::Broadcast, &[0, 1], Box::new(Message("c")));
+		queue.push(Target::Broadcast, &[], Box::new(Message("d")));
+		
+		queue.leave_nodes(&[2, 1]);
+		
+		let origins: Vec<Vec<usize>> = queue.take().into_iter().map(|queued| queued.origin).collect();
+		assert_eq!(origins, vec![vec![0], vec![0, 4], vec![0], vec![]]);
+		assert!(queue.is_empty());
+	}
+	
+	#[test]
+	fn removing_the_origin_of_an_event_resolves_it_from_the_parent() {
+		let mut tree = tree();
+		let game = tree.game;
+		tree.record(0, "root");
+		
+		tree.lens("a", "/game/world", vec![
+			("start", Target::Node("./".to_string()), "one"),
+		]);
+		tree.settle();
+		
+		tree.router.fire_event_at_lens("a", &mut Message("start"));
+		tree.router.remove_subtree(game).unwrap();
+		tree.take_log();
+		
+		tree.router.update();
+		assert_eq!(tree.take_log(), "root*");
+	}
+	
+	/// A tree with a `Recorder` on the root, `/game` and `/game/world`, and a lens `a` at `/game/world`.
+	fn lensed_tree() -> Tree {
+		let mut tree = tree();
+		let (game, world) = (tree.game, tree.world);
+		tree.record(0, "root");
+		tree.record(game, "game");
+		tree.record(world, "world");
+		tree.lens("a", "/game/world", vec![]);
+		tree.settle();
+		tree
+	}
+	
+	#[test]
+	fn events_flow_down_to_the_lens_and_back_up() {
+		let mut tree = lensed_tree();
+		
+		tree.router.fire_event_at_lens("a", &mut Message("click"));
+		assert_eq!(tree.take_log(), "rootv gamev worldv a:click world^ game^ root^");
+		
+		// Triggering skips the nodes entirely.
+		tree.router.trigger_event_at_lens_id(0, &mut Message("click"));
+		assert_eq!(tree.take_log(), "a:click");
+	}
+	
+	#[test]
+	fn stop_propagation_blocks_the_action_and_bubbling() {
+		let mut tree = lensed_tree();
+		let game = tree.game;
+		tree.intercept(game, "click", Phase::Propagation, |event| event.stop_propagation());
+		
+		tree.router.fire_event_at_lens("a", &mut Message("click"));
+		assert_eq!(tree.take_log(), "rootv gamev");
+		
+		tree.router.fire_event_at_lens("a", &mut Message("scroll"));
+		assert_eq!(tree.take_log(), "rootv gamev worldv a:scroll world^ game^ root^");
+		
+		// While bubbling, it stops the event from reaching the nodes further up.
+		tree.intercept(game, "click", Phase::Bubbling, |event| event.stop_propagation());
+		tree.router.fire_event_at_lens("a", &mut Message("click"));
+		assert_eq!(tree.take_log(), "rootv gamev worldv a:click world^ game^");
+	}
+	
+	#[test]
+	fn prevent_default_still_bubbles() {
+		let mut tree = lensed_tree();
+		let game = tree.game;
+		tree.intercept(game, "click", Phase::Propagation, |event| event.prevent_default());
+		
+		tree.router.fire_event_at_lens("a", &mut Message("click"));
+		assert_eq!(tree.take_log(), "rootv gamev worldv world^ game^ root^");
+	}
+	
+	#[test]
+	fn stop_bubbling_keeps_the_action() {
+		let mut tree = lensed_tree();
+		let world = tree.world;
+		tree.intercept(world, "click", Phase::Bubbling, |event| event.stop_bubbling());
+		
+		tree.router.fire_event_at_lens("a", &mut Message("click"));
+		assert_eq!(tree.take_log(), "rootv gamev worldv a:click world^");
+		
+		// Stopped before the action, the event does not bubble at all.
+		tree.intercept(world, "click", Phase::Propagation, |event| event.stop_bubbling());
+		tree.router.fire_event_at_lens("a", &mut Message("click"));
+		assert_eq!(tree.take_log(), "rootv gamev worldv a:click");
+	}
+	
+	/// A tree with a `Recorder` on every node.
+	fn recorded_tree() -> Tree {
+		let mut tree = tree();
+		let (game, world, hud, menu) = (tree.game, tree.world, tree.hud, tree.menu);
+		tree.record(0, "root");
+		tree.record(game, "game");
+		tree.record(world, "world");
+		tree.record(hud, "hud");
+		tree.record(menu, "menu");
+		tree
+	}
+	
+	#[test]
+	fn node_events_flow_through_the_parents() {
+		let mut tree = recorded_tree();
+		let game = tree.game;
+		
+		assert!(tree.router.fire_event_at_node("/game/world", &mut Message("click")));
+		assert_eq!(tree.take_log(), "rootv gamev world* game^ root^");
+		
+		assert!(tree.router.fire_event_at_node("/", &mut Message("click")));
+		assert_eq!(tree.take_log(), "root*");
+		
+		tree.intercept(game, "click", Phase::Propagation, |event| event.prevent_default());
+		assert!(tree.router.fire_event_at_node("/game/world", &mut Message("click")));
+		assert_eq!(tree.take_log(), "rootv gamev game^ root^");
+		
+		tree.intercept(game, "click", Phase::Propagation, |event| event.stop_propagation());
+		assert!(tree.router.fire_event_at_node("/game/world", &mut Message("click")));
+		assert_eq!(tree.take_log(), "rootv gamev");
+		
+		assert!(!tree.router.fire_event_at_node("/game/nowhere", &mut Message("click")));
+		assert_eq!(tree.take_log(), "");
+	}
+	
+	#[test]
+	fn subtrees_are_dispatched_parent_first_in_creation_order() {
+		let mut tree = recorded_tree();
+		let game = tree.game;
+		
+		// Created last, so it comes after its older siblings, regardless of its name.
+		let arcade = tree.router.new_node("arcade", Some(game), &|_| {});
+		tree.record(arcade, "arcade");
+		
+		assert!(tree.router.fire_event_at_subtree("/", &mut Message("tick")));
+		assert_eq!(tree.take_log(), "root* game* world* hud* arcade* menu*");
+		
+		assert!(tree.router.fire_event_at_subtree("/game", &mut Message("tick")));
+		assert_eq!(tree.take_log(), "game* world* hud* arcade*");
+		
+		// A new node that reuses the id of a removed one still comes after its older siblings.
+		let world = tree.world;
+		assert!(tree.router.remove_node(world).is_ok());
+		let radar = tree.router.new_node("radar", Some(game), &|_| {});
+		assert_eq!(radar, world);
+		tree.record(radar, "radar");
+		
+		assert!(tree.router.fire_event_at_subtree("/game", &mut Message("tick")));
+		assert_eq!(tree.take_log(), "game* hud* arcade* radar*");
+		
+		// Stopping the propagation at a node skips its children, but not its siblings.
+		tree.intercept(game, "tick", Phase::Action, |event| event.stop_propagation());
+		assert!(tree.router.fire_event_at_subtree("/", &mut Message("tick")));
+		assert_eq!(tree.take_log(), "root* game* menu*");
+		
+		assert!(!tree.router.fire_event_at_subtree("/nowhere", &mut Message("tick")));
+		assert_eq!(tree.take_log(), "");
+	}
+	
+	#[test]
+	fn lenses_in_a_subtree() {
+		let mut tree = tree();
+		tree.lens("a", "/game/world", vec![]);
+		tree.lens("b", "/menu", vec![]);
+		tree.lens("c", "/game", vec![]);
+		tree.settle();
+		
+		assert!(tree.router.fire_event_at_lenses_in("/game", &mut Message("x")));
+		assert_eq!(tree.take_log(), "a:x c:x");
+		
+		assert!(tree.router.fire_event_at_lenses_in("/game/hud", &mut Message("x")));
+		assert_eq!(tree.take_log(), "");
+		
+		assert!(tree.router.fire_event_at_lenses_in("/", &mut Message("x")));
+		assert_eq!(tree.take_log(), "a:x b:x c:x");
+		
+		tree.router.queue_event(Target::LensesIn("menu".to_string()), Box::new(Message("y")));
+		tree.router.update();
+		assert_eq!(tree.take_log(), "b:y");
+	}
+	
+	#[test]
+	fn paths_resolve_like_lens_paths() {
+		let tree = tree();
+		let (router, game, world, hud, menu) = (&tree.router, tree.game, tree.world, tree.hud, tree.menu);
+		
+		// Absolute paths start at the root, wherever they are resolved from.
+		assert_eq!(router.resolve_path("/game/world", &[]), Ok(vec![0, game, world]));
+		assert_eq!(router.resolve_path("/game/world", &[0, menu]), Ok(vec![0, game, world]));
+		assert_eq!(router.resolve_path("/", &[0, game, world]), Ok(vec![0]));
+		
+		// Relative paths start at the end of the given path, or at the root.
+		assert_eq!(router.resolve_path("game", &[]), Ok(vec![0, game]));
+		assert_eq!(router.resolve_path("world", &[0, game]), Ok(vec![0, game, world]));
+		assert_eq!(router.resolve_path("./hud", &[0, game]), Ok(vec![0, game, hud]));
+		assert_eq!(router.resolve_path("../menu", &[0, game]), Ok(vec![0, menu]));
+		assert_eq!(router.resolve_path("hud/../world", &[0, game]), Ok(vec![0, game, world]));
+		assert_eq!(router.resolve_path("../../", &[0, game, world]), Ok(vec![0]));
+		
+		assert!(router.resolve_path("menu", &[0, game]).is_err());
+		assert!(router.resolve_path("/game/nowhere", &[]).is_err());
+		assert!(router.resolve_path("../", &[0]).is_err());
 	}
 }
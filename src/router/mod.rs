@@ -26,6 +26,9 @@ pub struct Router {
 	
 	/// The nodes and their components representing the routing tree.
 	pub nodes: node::Nodes,
+	
+	/// Events fired by lens handlers, waiting to be dispatched.
+	pub queue: event::Queue,
 }
 
 /// Functions for building the router.
@@ -37,6 +40,7 @@ impl Router {
 		Router {
 			lenses: lens::Lenses::new(),
 			nodes: node::Nodes::new(),
+			queue: event::Queue::new(),
 		}
 	}
 	
@@ -83,11 +87,89 @@ impl Router {
 		
 		constructor(&mut node);
 		
-		self.nodes.nodes.insert(node.id, node);
+		self.nodes.insert_node(node);
 		return id;
 	}
 }
 
+// Router teardown
+impl Router {
+	
+	/// Removes a node that has no children; see `Router::remove_subtree`.
+	pub fn remove_node(&mut self, node_id: usize) -> Result<(), node::NodeRemovalError> {
+		if ! self.nodes.get_children_ids(node_id).is_empty() {
+			return Err(node::NodeRemovalError::HasChildren{node_id});
+		}
+		
+		self.remove_subtree(node_id)
+	}
+	
+	/// Removes a node together with all of its descendants.
+	///
+	/// The teardown happens in a fixed order:
+	///
+	/// 1. Lenses inside of the subtree leave it, deepest node first, up to the parent of the subtree.
+	///    Components are unloaded as usual when the last lens leaves their node, in the order of their type names.
+	/// 2. The nodes are removed, children before their parents and siblings in the order they were created.
+	///    Their components are detached in the order of their type names, and their ids are freed.
+	/// 3. Lenses that were moving trough the subtree stop, and receive `MoveCompletionEvent::Aborted`.
+	///
+	/// # Panics
+	///
+	/// If one of the components is borrowed elsewhere.
+	pub fn remove_subtree(&mut self, node_id: usize) -> Result<(), node::NodeRemovalError> {
+		if self.nodes.get_node_by_id(node_id).is_none() {
+			return Err(node::NodeRemovalError::NodeNotFound{node_id});
+		}
+		
+		if node_id == 0 {
+			return Err(node::NodeRemovalError::RootNode);
+		}
+		
+		let removed = self.nodes.get_subtree_ids(node_id);
+		debug!("Removing router node #{} and {} descendants...", node_id, removed.len() - 1);
+		
+		let mut node_events: Vec<usize> = vec![];
+		let mut aborted: Vec<usize> = vec![];
+		
+		for (lens_id, lens) in self.lenses.lenses.iter_mut().enumerate() {
+			let position = match lens.path.iter().position(|id| removed.contains(id)) {
+				Some(position) => position,
+				None => continue
+			};
+			
+			while lens.path.len() > position {
+				node_events.push(lens.path.pop().unwrap());
+			}
+			
+			if lens.state == lens::State::Moving("".to_string(), 0) {
+				lens.state = lens::State::Idle;
+				aborted.push(lens_id);
+			}
+			
+			lens.path_str = self.nodes.get_path_as_string(&lens.path)
+				.expect("Failed to resolve path for lens.");
+		}
+		
+		for node_id in node_events {
+			self.trigger_event_at_node_id(node_id, &mut lens::MoveEvent::LeaveNode);
+		}
+		
+		for node_id in removed.iter() {
+			self.nodes.remove_node_components(*node_id);
+			self.nodes.remove_node(*node_id);
+		}
+		
+		self.queue.leave_nodes(&removed);
+		
+		for lens_id in aborted {
+			self.fire_event_at_lens_id(lens_id, &mut lens::MoveCompletionEvent::Aborted);
+		}
+		
+		Ok(())
+	}
+}
+
 // Router update handling
 impl Router {
 	
@@ -211,6 +293,8 @@ impl Router {
 			);
 		}
 		
+		self.dispatch_queued_events();
+		
 		return self.lenses.lenses.is_empty()
 	}
 }
@@ -315,7 +399,32 @@ impl Router {
 		*dst_off += end;
 		return PathItem::ToNode(next.id);
 	}
-	
+
+}
+
+impl Router {
+	/// Resolves a path into the ids of the nodes along it, following the same rules as a moving lens.
+	///
+	/// Relative paths start at the end of `from`, or at the root node if `from` is empty.
+	pub fn resolve_path(&self, path: &str, from: &[usize]) -> Result<Vec<usize>, String> {
+		let mut offset = 0;
+		let mut node_path = if from.is_empty() {vec![0]} else {from.to_vec()};
+		
+		loop {
+			match Router::path_next(&self.nodes, path, &mut offset, &node_path) {
+				PathItem::ToSelf => (),
+				PathItem::ToSuper => {
+					if node_path.len() <= 1 {
+						return Err(format!("Cannot go above the root node."));
+					}
+					node_path.pop();
+				},
+				PathItem::ToNode(node_id) => node_path.push(node_id),
+				PathItem::Error(e) => return Err(e),
+				PathItem::End => return Ok(node_path),
+			}
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -338,3 +447,201 @@ impl std::fmt::Display for PathItem {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{context, event, lens, Router};
+	use super::comp::Component;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+	
+	/// What the lenses and components of a test saw, in order.
+	type Log = Rc<RefCell<Vec<String>>>;
+	
+	/// Logs how the moves of its lens end, as `<lens>:<finished|aborted>@<path>`.
+	struct Mover {
+		name: &'static str,
+		log: Log,
+	}
+	
+	impl lens::Handler for Mover {
+		fn on_event<'a>(&mut self, event: &mut event::Wrapper, context: &mut context::Context) -> lens::State {
+			if let Some(completion) = event.downcast::<lens::MoveCompletionEvent>() {
+				let completion = match completion {
+					lens::MoveCompletionEvent::Finished => "finished",
+					lens::MoveCompletionEvent::Aborted => "aborted",
+				};
+				self.log.borrow_mut().push(format!("{}:{}@{}", self.name, completion, context.get_path_str()));
+			}
+			lens::State::Idle
+		}
+	}
+	
+	/// Two component types that log what happens to them as `<what>-<node>:<type>`;
+	/// `B` is attached first, so the order of attachment does not decide the order of the log.
+	struct A(&'static str, Log);
+	struct B(&'static str, Log);
+	
+	macro_rules! logging_component {
+		($type:ident) => {
+			impl Component for $type {
+				fn get_type_name(&self) -> &'static str { stringify!($type) }
+				fn on_attachment(&mut self, _node_id: usize) {}
+				
+				fn on_detachment(&mut self, _node_id: usize) {
+					self.1.borrow_mut().push(format!("detach-{}:{}", self.0, stringify!($type)));
+				}
+				
+				fn on_load(&mut self) {
+					self.1.borrow_mut().push(format!("load-{}:{}", self.0, stringify!($type)));
+				}
+				
+				fn on_unload(&mut self) {
+					self.1.borrow_mut().push(format!("unload-{}:{}", self.0, stringify!($type)));
+				}
+				
+				fn on_event(&mut self, _event: &mut event::Wrapper) {}
+			}
+		}
+	}
+	
+	logging_component!(A);
+	logging_component!(B);
+	
+	/// A router with the nodes `/game/world`, `/game/hud` and `/menu`, each with an `A` and a `B`.
+	fn router(log: &Log) -> Router {
+		let mut router = Router::new();
+		let game = router.new_node("game", None, &|_| {});
+		let world = router.new_node("world", Some(game), &|_| {});
+		let hud = router.new_node("hud", Some(game), &|_| {});
+		let menu = router.new_node("menu", None, &|_| {});
+		
+		for (node_id, name) in [(game, "game"), (world, "world"), (hud, "hud"), (menu, "menu")].iter() {
+			attach(&mut router, log, *node_id, name);
+		}
+		router
+	}
+	
+	fn attach(router: &mut Router, log: &Log, node_id: usize, name: &'static str) {
+		router.nodes.set_node_component(node_id, Box::new(B(name, log.clone()))).unwrap();
+		router.nodes.set_node_component(node_id, Box::new(A(name, log.clone()))).unwrap();
+	}
+	
+	/// Adds a lens that moves to the given path, and gives it the given number of updates.
+	fn lens(router: &mut Router, log: &Log, name: &'static str, path: &str, updates: usize) {
+		router.new_lens(name, &|lens| {
+			lens.state = lens::State::Moving(path.to_string(), 0);
+			Some(Box::new(Mover {name, log: log.clone()}))
+		});
+		
+		for _ in 0..updates {
+			router.update();
+		}
+	}
+	
+	fn path(router: &mut Router, name: &str) -> (Vec<usize>, String) {
+		let lens = router.lenses.get_lens_by_name(name).unwrap();
+		(lens.path.clone(), lens.path_str.clone())
+	}
+	
+	fn take_log(log: &Log) -> String {
+		let joined = log.borrow().join(" ");
+		log.borrow_mut().clear();
+		joined
+	}
+	
+	#[test]
+	fn lenses_inside_move_out_to_the_parent() {
+		let log: Log = Rc::new(RefCell::new(vec![]));
+		let mut router = router(&log);
+		let (game, world, hud, menu) = (1, 2, 3, 4);
+		
+		lens(&mut router, &log, "a", "/game/world", 10);
+		lens(&mut router, &log, "b", "/menu", 10);
+		lens(&mut router, &log, "c", "/game/hud", 10);
+		take_log(&log);
+		
+		assert!(router.remove_node(0).is_err());
+		assert!(router.remove_node(game).is_err());
+		assert!(router.remove_subtree(0).is_err());
+		assert!(router.remove_subtree(99).is_err());
+		assert_eq!(take_log(&log), "");
+		
+		router.remove_node(world).unwrap();
+		assert_eq!(take_log(&log), "unload-world:A unload-world:B detach-world:A detach-world:B");
+		assert_eq!(path(&mut router, "a"), (vec![0, game], "/game".to_string()));
+		
+		// The last lens to leave `/game` unloads it, before any component is detached.
+		router.remove_subtree(game).unwrap();
+		assert_eq!(take_log(&log), "unload-hud:A unload-hud:B unload-game:A unload-game:B detach-hud:A detach-hud:B detach-game:A detach-game:B");
+		assert_eq!(path(&mut router, "a"), (vec![0], "/".to_string()));
+		assert_eq!(path(&mut router, "c"), (vec![0], "/".to_string()));
+		assert_eq!(path(&mut router, "b"), (vec![0, menu], "/menu".to_string()));
+		
+		assert!(router.nodes.get_node_by_id(game).is_none());
+		assert!(router.nodes.get_node_by_id(hud).is_none());
+		assert!(router.nodes.comps.comps.get(&game).is_none());
+		
+		// The lenses that were moved out are idle, and stay where they are.
+		for _ in 0..5 {
+			router.update();
+		}
+		assert_eq!(take_log(&log), "");
+		assert_eq!(path(&mut router, "a"), (vec![0], "/".to_string()));
+	}
+	
+	#[test]
+	fn aborted_moves_are_reported() {
+		let log: Log = Rc::new(RefCell::new(vec![]));
+		let mut router = router(&log);
+		let game = 1;
+		
+		lens(&mut router, &log, "a", "/menu", 10);
+		assert_eq!(take_log(&log), "load-menu:A load-menu:B a:finished@/menu");
+		
+		// Two updates take `b` to the root node and then into `/game`, on its way to `/game/hud`.
+		lens(&mut router, &log, "b", "/game/hud", 2);
+		assert_eq!(path(&mut router, "b"), (vec![0, game], "/game".to_string()));
+		take_log(&log);
+		
+		router.remove_subtree(game).unwrap();
+		assert_eq!(take_log(&log), "unload-game:A unload-game:B detach-world:A detach-world:B detach-hud:A detach-hud:B detach-game:A detach-game:B b:aborted@/");
+		
+		// The move is over, and the idle lens at `/menu` is untouched.
+		for _ in 0..5 {
+			router.update();
+		}
+		assert_eq!(take_log(&log), "");
+		assert_eq!(path(&mut router, "b"), (vec![0], "/".to_string()));
+		assert_eq!(path(&mut router, "a").1, "/menu");
+	}
+	
+	#[test]
+	fn freed_ids_are_reused() {
+		let log: Log = Rc::new(RefCell::new(vec![]));
+		let mut router = router(&log);
+		let game = 1;
+		
+		router.remove_subtree(game).unwrap();
+		take_log(&log);
+		
+		let mut ids: Vec<usize> = ["x", "y", "z"].iter()
+			.map(|name| router.new_node(name, None, &|_| {}))
+			.collect();
+		ids.sort();
+		assert_eq!(ids, vec![1, 2, 3]);
+		assert_eq!(router.new_node("fresh", None, &|_| {}), 5);
+		
+		// Nodes with a reused id start out without the components of the removed node.
+		let x = router.resolve_path("/x", &[]).unwrap()[1];
+		assert!(router.nodes.comps.comps.get(&x).is_none());
+		
+		attach(&mut router, &log, x, "x");
+		lens(&mut router, &log, "a", "/x", 10);
+		assert_eq!(take_log(&log), "load-x:A load-x:B a:finished@/x");
+		
+		router.remove_node(x).unwrap();
+		assert_eq!(take_log(&log), "unload-x:A unload-x:B detach-x:A detach-x:B");
+		assert_eq!(path(&mut router, "a"), (vec![0], "/".to_string()));
+	}
+}
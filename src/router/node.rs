@@ -9,6 +9,9 @@ pub struct Node {
 	pub name: String,
 	pub id: usize,
 	lens_count:usize,
+	
+	/// When the node was inserted, relative to the other nodes; unlike ids, this is never reused.
+	sequence: usize,
 }
 
 impl Node {
@@ -20,11 +23,16 @@ impl Node {
 			parent,
 			name,
 			lens_count: 0,
+			sequence: 0,
 		}
 	}
 	
 	/// Function for processing and acting upon received events.
-	pub fn on_event(&mut self, components: Option<&mut FxHashMap<TypeId, Box<super::comp::Component>>>, event: &mut super::event::Wrapper) {
+	///
+	/// # Panics
+	///
+	/// If one of the components is borrowed elsewhere while the event is delivered.
+	pub fn on_event(&mut self, components: Option<&FxHashMap<TypeId, comp::ComponentCell>>, event: &mut super::event::Wrapper) {
 		// This is ugly as heck... but until one can 'forget' a mutable borrow... :(
 		if event.event.is::<MoveEvent>() {
 			let downcast_event = event.event.downcast_ref::<MoveEvent>();
@@ -36,18 +44,18 @@ impl Node {
 					MoveEvent::LeaveNode => {self.lens_count -= 1},
 				};
 				
+				// Components are loaded by the first lens to enter, and unloaded by the last to leave,
+				// in the order of their type names.
 				if old_lens_count != self.lens_count {
+					let components = components.map(sort_by_type_name).unwrap_or_default();
+					
 					if self.lens_count == 0 {
-						if let Some(components) = components {
-							for (_, component) in components {
-								(*component).on_unload();
-							}
+						for component in components {
+							component.borrow_mut().on_unload();
 						}
-					} else {
-						if let Some(components) = components {
-							for (_, component) in components {
-								(*component).on_load();
-							}
+					} else if old_lens_count == 0 {
+						for component in components {
+							component.borrow_mut().on_load();
 						}
 					}
 				}
@@ -56,7 +64,7 @@ impl Node {
 			// Not a move-event, so give it to the components.
 			components.map(|components| {
 				for (_, component) in components {
-					component.on_event(event);
+					component.borrow_mut().on_event(event);
 				}
 			});
 		}
@@ -73,6 +81,13 @@ impl Node {
 	}
 }
 
+/// The given components, in the order of their type names.
+fn sort_by_type_name(components: &FxHashMap<TypeId, comp::ComponentCell>) -> Vec<&comp::ComponentCell> {
+	let mut components: Vec<&comp::ComponentCell> = components.values().collect();
+	components.sort_by_key(|component| component.get_type_name());
+	components
+}
+
 impl PartialEq for Node {
 	/// Partial equality for nodes, using their ID's.
 	fn eq(&self, other: &Node) -> bool {
@@ -90,6 +105,12 @@ pub struct Nodes {
 	
 	/// Auto-incrementing counter for the next unique node-id.
 	pub next_id: usize,
+	
+	/// Ids of removed nodes, handed out again before the counter is incremented.
+	pub free_ids: Vec<usize>,
+	
+	/// Counter for the order in which nodes are inserted, which orders siblings.
+	pub next_sequence: usize,
 }
 
 impl Nodes {
@@ -100,6 +121,7 @@ impl Nodes {
 			parent: None,
 			name: "".to_string(),
 			lens_count: 0,
+			sequence: 0,
 		};
 		
 		let mut nodes = FxHashMap::default();
@@ -108,17 +130,30 @@ impl Nodes {
 		Nodes {
 			nodes,
 			next_id: 1,
+			free_ids: vec![],
+			next_sequence: 1,
 			comps: comp::Components::new(),
 		}
 	}
 	
-	/// Returns the next unique node-id.
+	/// Returns the next unique node-id, reusing the id of a removed node if there is one.
 	pub fn next_id(&mut self) -> usize {
+		if let Some(id) = self.free_ids.pop() {
+			return id;
+		}
+		
 		let id = self.next_id;
 		self.next_id += 1;
 		return id;
 	}
 	
+	/// Inserts a node into the routing-tree, after all of its siblings.
+	pub fn insert_node(&mut self, mut node: Node) {
+		node.sequence = self.next_sequence;
+		self.next_sequence += 1;
+		self.nodes.insert(node.id, node);
+	}
+	
 	/// Returns a formatted string representing the given path.
 	pub fn get_path_as_string(&self, path: &[usize]) -> Result<String, ()> {
 		let mut path_str = String::new();
@@ -146,8 +181,8 @@ impl Nodes {
 	}
 	
 	/// Mutably borrow the node (and its components) with the given id.
-	pub fn get_mut_node_with_comps_by_id(&mut self, id: usize) -> (Option<&mut Node>, Option<&mut FxHashMap<TypeId, Box<comp::Component>>>) {
-		(self.nodes.get_mut(&id), self.comps.comps.get_mut(&id))
+	pub fn get_mut_node_with_comps_by_id(&mut self, id: usize) -> (Option<&mut Node>, Option<&FxHashMap<TypeId, comp::ComponentCell>>) {
+		(self.nodes.get_mut(&id), self.comps.comps.get(&id))
 	}
 	
 	/// Borrow the node with the given id.
@@ -155,6 +190,38 @@ impl Nodes {
 		self.nodes.get(&id)
 	}
 	
+	/// Get the ids of the children of the given node, in the order they were created.
+	pub fn get_children_ids(&self, node_id: usize) -> Vec<usize> {
+		let mut children: Vec<&Node> = self.nodes.values()
+			.filter(|node| node.parent == Some(node_id))
+			.collect();
+		
+		children.sort_by_key(|node| node.sequence);
+		children.into_iter().map(|node| node.id).collect()
+	}
+	
+	/// Get the ids of the given node and all of its descendants, children before their parents.
+	pub fn get_subtree_ids(&self, node_id: usize) -> Vec<usize> {
+		let mut ids = vec![];
+		for child in self.get_children_ids(node_id) {
+			ids.extend(self.get_subtree_ids(child));
+		}
+		
+		ids.push(node_id);
+		ids
+	}
+	
+	/// Removes the given node from the routing-tree and frees its id.
+	///
+	/// This does neither touch the children nor the components of the node; see `Router::remove_subtree`.
+	pub fn remove_node(&mut self, node_id: usize) -> Option<Node> {
+		let node = self.nodes.remove(&node_id);
+		if node.is_some() {
+			self.free_ids.push(node_id);
+		}
+		node
+	}
+	
 	/// Get the id of the parent of the given node.
 	pub fn get_node_parent_id(&self, node_id: usize) -> Option<usize> {
 		match self.nodes.get(&node_id) {
@@ -167,4 +234,16 @@ impl Nodes {
 			None => None
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Fail)]
+pub enum NodeRemovalError {
+	#[fail(display = "Could not find node #{}", node_id)]
+	NodeNotFound {node_id: usize},
+	
+	#[fail(display = "The root node cannot be removed")]
+	RootNode,
+	
+	#[fail(display = "Node #{} still has children", node_id)]
+	HasChildren {node_id: usize},
+}
@@ -161,4 +161,9 @@ impl GameloopState {
 	pub fn get_ticks_per_second(&self) -> f64 {
 		self.last_tps
 	}
+	
+	/// Gets the fixed length (in seconds) of a single tick.
+	pub fn get_tick_length(&self) -> f64 {
+		self.skip_ticks
+	}
 }
\ No newline at end of file